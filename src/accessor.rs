@@ -0,0 +1,127 @@
+//! Where the compiler reads files from. Files are named as they are in
+//! import statements, with `/` separating directories.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+pub trait FileAccessor {
+    /// Returns the contents of the file imported as `name`. A file that
+    /// doesn't exist is reported with `io::ErrorKind::NotFound`.
+    fn open(&self, name: &str) -> io::Result<String>;
+}
+
+/// Reads files from disk, looking for each one under the include paths in
+/// order, like protoc's `-I` flags. Like protoc, it only accepts names in
+/// canonical form, so that each file has one name and imports can't reach
+/// outside the include paths.
+#[derive(Debug, Clone)]
+pub struct FileSystemAccessor {
+    include_paths: Vec<PathBuf>
+}
+
+impl FileSystemAccessor {
+    /// Returns an accessor for the given include paths, or for the current
+    /// directory if there are none.
+    pub fn new(include_paths: Vec<PathBuf>) -> Self {
+        if include_paths.is_empty() {
+            return FileSystemAccessor { include_paths: vec![PathBuf::from(".")] }
+        }
+        return FileSystemAccessor { include_paths }
+    }
+
+    pub fn include_paths(&self) -> &[PathBuf] {
+        return &self.include_paths
+    }
+}
+
+impl FileAccessor for FileSystemAccessor {
+    fn open(&self, name: &str) -> io::Result<String> {
+        if !is_canonical(name) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "backslashes, consecutive slashes, \".\", \"..\" and absolute paths are not allowed in import paths"))
+        }
+        for dir in self.include_paths.iter() {
+            let path = dir.join(name);
+            if path.is_file() {
+                return std::fs::read_to_string(path)
+            }
+        }
+        return Err(io::Error::new(io::ErrorKind::NotFound, "file not found"))
+    }
+}
+
+// Reports whether `name` is a relative path made of non-empty components
+// separated by single slashes, none of them `.` or `..`.
+fn is_canonical(name: &str) -> bool {
+    if name.contains('\\') || !name.split('/').all(|c| !c.is_empty() && c != "." && c != "..") {
+        return false
+    }
+    // Catches drive prefixes on Windows.
+    return Path::new(name).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Serves files from a map of names to contents.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAccessor {
+    files: HashMap<String, String>
+}
+
+impl MemoryAccessor {
+    pub fn new() -> Self {
+        return Default::default()
+    }
+
+    pub fn insert(&mut self, name: &str, contents: &str) -> &mut Self {
+        self.files.insert(name.to_string(), contents.to_string());
+        return self
+    }
+}
+
+impl FileAccessor for MemoryAccessor {
+    fn open(&self, name: &str) -> io::Result<String> {
+        match self.files.get(name) {
+            Some(contents) => return Ok(contents.clone()),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "file not found"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_accessor() {
+        let mut accessor = MemoryAccessor::new();
+        accessor.insert("a.proto", "syntax = \"proto3\";").insert("b/c.proto", "");
+        assert_eq!(accessor.open("a.proto").unwrap(), "syntax = \"proto3\";");
+        assert_eq!(accessor.open("b/c.proto").unwrap(), "");
+        assert_eq!(accessor.open("c.proto").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn file_system_accessor_searches_include_paths_in_order() {
+        let root = std::env::temp_dir().join(format!("protoreflect-accessor-{}", std::process::id()));
+        let (first, second) = (root.join("first"), root.join("second"));
+        std::fs::create_dir_all(first.join("pkg")).unwrap();
+        std::fs::create_dir_all(second.join("pkg")).unwrap();
+        std::fs::write(first.join("pkg/a.proto"), "first").unwrap();
+        std::fs::write(second.join("pkg/a.proto"), "second").unwrap();
+        std::fs::write(second.join("pkg/b.proto"), "only second").unwrap();
+
+        let accessor = FileSystemAccessor::new(vec![first, second]);
+        let results = (accessor.open("pkg/a.proto").ok(), accessor.open("pkg/b.proto").ok(), accessor.open("pkg/c.proto").map_err(|e| e.kind()));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(results, (Some("first".to_string()), Some("only second".to_string()), Err(io::ErrorKind::NotFound)));
+        assert_eq!(FileSystemAccessor::new(vec![]).include_paths(), [PathBuf::from(".")]);
+    }
+
+    #[test]
+    fn file_system_accessor_rejects_non_canonical_names() {
+        let accessor = FileSystemAccessor::new(vec![]);
+        for name in ["../a.proto", "a/../b.proto", "a/..", "/etc/hosts", "a//b.proto", "./a.proto", "a/./b.proto", "a\\b.proto", "a/", ""].iter() {
+            assert_eq!(accessor.open(name).unwrap_err().kind(), io::ErrorKind::InvalidInput, "{}", name);
+        }
+        assert_eq!(accessor.open("a/b.proto").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,1192 @@
+//! Builds the descriptor of a parsed file. The result matches what protoc
+//! makes of a file before linking: type names are kept as written, with
+//! the field type unset when it names a message or an enum, and options
+//! are stored as `uninterpreted_option`s. The `default` and `json_name`
+//! pseudo-options are applied to the field, and every field is given its
+//! JSON name, as in the output of `protoc --descriptor_set_out`.
+
+use std::collections::HashMap;
+use crate::descriptor::*;
+use crate::enums::{EnumElement, EnumNode};
+use crate::error::Error;
+use crate::field::{FieldNode, GroupNode, MapFieldNode};
+use crate::file::{FileElement, FileNode};
+use crate::identifiers::{IdentNode, _IdentValueNodeTrait};
+use crate::message::{ExtendElement, ExtendNode, MessageBody, MessageElement};
+use crate::node::NodeTrait;
+use crate::oneof::{OneOfElement, OneOfNode};
+use crate::options::{CompactOptionsNode, OptionNode};
+use crate::ranges::{RangeNode, ReservedNode};
+use crate::service::{MethodElement, RPCNode, ServiceElement, ServiceNode};
+use crate::source_pos::PosRange;
+use crate::values::{IntValueNodeTrait, StringValueNode, ValueNode, _FloatValueNodeTrait, _IntValueNodeTrait};
+
+/// The largest field number, plus one. `max` in the ranges of a message
+/// stands for this, as ranges of fields are stored with exclusive ends.
+const MAX_FIELD_NUMBER_END: i32 = 536870912;
+
+/// Field numbers protobuf keeps for itself.
+const RESERVED_FIELD_NUMBERS: std::ops::RangeInclusive<i64> = 19000..=19999;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Proto2,
+    Proto3,
+    Editions
+}
+
+impl Syntax {
+    fn name(&self) -> &'static str {
+        match self {
+            Syntax::Proto2 => return "proto2",
+            Syntax::Proto3 => return "proto3",
+            Syntax::Editions => return "editions"
+        }
+    }
+}
+
+struct Builder {
+    syntax: Syntax,
+    errors: Vec<Error>
+}
+
+/// Returns the descriptor of `file`, or every error found while building
+/// it. The descriptor is named after the file name the source was parsed
+/// with. Error nodes left by a partial parse are skipped.
+pub fn compile(file: &FileNode) -> Result<FileDescriptorProto, Vec<Error>> {
+    let (fd, errors) = compile_partial(file);
+    if errors.is_empty() {
+        return Ok(fd)
+    }
+    return Err(errors)
+}
+
+/// Builds the descriptor of `file`, carrying on past errors. Declarations
+/// with errors are kept in the descriptor as far as they could be built.
+pub fn compile_partial(file: &FileNode) -> (FileDescriptorProto, Vec<Error>) {
+    let syntax = if file.edition().is_some() {
+        Syntax::Editions
+    } else if file.syntax().is_some_and(|s| s.syntax().as_string() == "proto3") {
+        Syntax::Proto3
+    } else {
+        Syntax::Proto2
+    };
+    let mut c = Builder { syntax, errors: vec![] };
+    let fd = c.file(file);
+    return (fd, c.errors)
+}
+
+pub(crate) fn pos_range(node: &dyn NodeTrait) -> PosRange {
+    return PosRange { start: node.start().clone(), end: node.end().clone() }
+}
+
+impl Builder {
+    fn error(&mut self, node: &dyn NodeTrait, message: &str) {
+        self.errors.push(Error::invalid(pos_range(node), message));
+    }
+
+    fn file(&mut self, file: &FileNode) -> FileDescriptorProto {
+        let mut fd = FileDescriptorProto {
+            name: Some(file.start().filename().to_string()),
+            ..Default::default()
+        };
+        if let Some(edition) = file.edition() {
+            fd.syntax = Some(self.syntax.name().to_string());
+            fd.edition = Some(edition.edition().as_string());
+        } else if self.syntax == Syntax::Proto3 {
+            fd.syntax = Some(self.syntax.name().to_string());
+        }
+        for decl in file.decls().iter() {
+            match decl {
+                FileElement::Import(i) => {
+                    let name = i.name().as_string();
+                    if fd.dependency.contains(&name) {
+                        self.error(i.name(), &format!("import \"{}\" is listed twice", name));
+                        continue
+                    }
+                    let index = fd.dependency.len() as i32;
+                    if i.is_public() {
+                        fd.public_dependency.push(index);
+                    }
+                    if i.is_weak() {
+                        fd.weak_dependency.push(index);
+                    }
+                    fd.dependency.push(name);
+                },
+                FileElement::Package(p) => fd.package = Some(p.name().as_identifier()),
+                FileElement::Option(o) => {
+                    if let Some(opt) = self.option(o) {
+                        fd.options.get_or_insert_with(Default::default).uninterpreted_option.push(opt);
+                    }
+                },
+                FileElement::Message(m) => {
+                    let msg = self.message(m.name(), m.body());
+                    fd.message_type.push(msg);
+                },
+                FileElement::Enum(e) => {
+                    let en = self.enum_type(e);
+                    fd.enum_type.push(en);
+                },
+                FileElement::Extend(e) => self.extend(e, &mut fd.extension, &mut fd.message_type),
+                FileElement::Service(s) => {
+                    let service = self.service(s);
+                    fd.service.push(service);
+                },
+                FileElement::Empty(_) | FileElement::Error(_) => {}
+            }
+        }
+        return fd
+    }
+
+    fn message(&mut self, name: &IdentNode, body: &MessageBody) -> DescriptorProto {
+        let mut msg = DescriptorProto {
+            name: Some(name.as_identifier()),
+            ..Default::default()
+        };
+        // Ranges that end at `max`, whose end depends on options that may
+        // come after them.
+        let mut max_extension_ranges = vec![];
+        let mut max_reserved_ranges = vec![];
+        let mut extension_nodes = vec![];
+        let mut reserved_nodes = vec![];
+        for decl in body.decls().iter() {
+            match decl {
+                MessageElement::Field(f) => {
+                    let field = self.field(f, None);
+                    msg.field.push(field);
+                },
+                MessageElement::MapField(f) => {
+                    let (field, entry) = self.map_field(f);
+                    msg.nested_type.push(entry);
+                    msg.field.push(field);
+                },
+                MessageElement::Group(g) => {
+                    let (field, group) = self.group(g, None);
+                    msg.nested_type.push(group);
+                    msg.field.push(field);
+                },
+                MessageElement::OneOf(o) => self.one_of(o, &mut msg),
+                MessageElement::Message(m) => {
+                    let nested = self.message(m.name(), m.body());
+                    msg.nested_type.push(nested);
+                },
+                MessageElement::Enum(e) => {
+                    let en = self.enum_type(e);
+                    msg.enum_type.push(en);
+                },
+                MessageElement::Extend(e) => self.extend(e, &mut msg.extension, &mut msg.nested_type),
+                MessageElement::Option(o) => {
+                    if let Some(opt) = self.option(o) {
+                        msg.options.get_or_insert_with(Default::default).uninterpreted_option.push(opt);
+                    }
+                },
+                MessageElement::ExtensionRange(r) => {
+                    let options = r.options().map(|o| ExtensionRangeOptions {
+                        uninterpreted_option: self.compact_options(o),
+                        ..Default::default()
+                    });
+                    for range in r.ranges().iter() {
+                        if range.is_max() {
+                            max_extension_ranges.push(msg.extension_range.len());
+                        }
+                        let (start, end) = self.message_range(range, "extension");
+                        msg.extension_range.push(ExtensionRange { start: Some(start), end: Some(end), options: options.clone() });
+                        extension_nodes.push(range);
+                    }
+                },
+                MessageElement::Reserved(r) => {
+                    for range in r.ranges().iter() {
+                        if range.is_max() {
+                            max_reserved_ranges.push(msg.reserved_range.len());
+                        }
+                        let (start, end) = self.message_range(range, "reserved");
+                        msg.reserved_range.push(ReservedRange { start: Some(start), end: Some(end) });
+                        reserved_nodes.push(range);
+                    }
+                    msg.reserved_name.extend(reserved_names(r));
+                },
+                MessageElement::Empty(_) | MessageElement::Error(_) => {}
+            }
+        }
+        // Like protoc, look for the option before it is interpreted, so
+        // only `message_set_wire_format = true` counts.
+        let is_message_set = msg.options.as_ref().is_some_and(|o| o.uninterpreted_option.iter().any(|u| {
+            u.name.len() == 1 && u.name[0].name_part == "message_set_wire_format" && u.identifier_value.as_deref() == Some("true")
+        }));
+        let max_end = if is_message_set { i32::MAX } else { MAX_FIELD_NUMBER_END };
+        for i in max_extension_ranges {
+            msg.extension_range[i].end = Some(max_end);
+        }
+        for i in max_reserved_ranges {
+            msg.reserved_range[i].end = Some(max_end);
+        }
+        self.check_ranges(&msg, &extension_nodes, &reserved_nodes);
+        self.check_fields(body, &msg);
+        add_synthetic_oneofs(&mut msg);
+        return msg
+    }
+
+    /// Checks that no reserved or extension range of a message overlaps
+    /// one declared before it, and that no extension range overlaps a
+    /// reserved range.
+    fn check_ranges(&mut self, msg: &DescriptorProto, extension_nodes: &[&RangeNode], reserved_nodes: &[&RangeNode]) {
+        let reserved: Vec<(i64, i64)> = msg.reserved_range.iter().map(|r| (r.start.unwrap_or_default() as i64, r.end.unwrap_or_default() as i64)).collect();
+        let extensions: Vec<(i64, i64)> = msg.extension_range.iter().map(|r| (r.start.unwrap_or_default() as i64, r.end.unwrap_or_default() as i64)).collect();
+        for (i, range) in reserved.iter().enumerate() {
+            if let Some(other) = overlap(*range, &reserved[..i]) {
+                self.error(reserved_nodes[i], &format!("reserved range {} overlaps with already-defined range {}", range_text(*range), range_text(other)));
+            }
+        }
+        for (i, range) in extensions.iter().enumerate() {
+            if let Some(other) = overlap(*range, &extensions[..i]) {
+                self.error(extension_nodes[i], &format!("extension range {} overlaps with already-defined range {}", range_text(*range), range_text(other)));
+            }
+            if let Some(other) = overlap(*range, &reserved) {
+                self.error(extension_nodes[i], &format!("extension range {} overlaps with reserved range {}", range_text(*range), range_text(other)));
+            }
+        }
+    }
+
+    /// Checks that no two fields of a message share a number, and that no
+    /// field uses a reserved number or name or a number in an extension
+    /// range.
+    fn check_fields(&mut self, body: &MessageBody, msg: &DescriptorProto) {
+        let mut used: HashMap<i32, &str> = HashMap::new();
+        for (field, (name, tag)) in msg.field.iter().zip(field_nodes(body)) {
+            let number = field.number.unwrap_or_default();
+            let field_name = field.name.as_deref().unwrap_or_default();
+            if msg.reserved_name.iter().any(|n| n == field_name) {
+                self.error(name, &format!("field name \"{}\" is reserved", field_name));
+            }
+            if number <= 0 {
+                // Already reported as out of range.
+                continue
+            }
+            if let Some(previous) = used.get(&number) {
+                self.error(tag, &format!("field number {} is already used by \"{}\"", number, previous));
+            } else {
+                used.insert(number, field_name);
+            }
+            if msg.reserved_range.iter().any(|r| r.start.unwrap_or_default() <= number && number < r.end.unwrap_or_default()) {
+                self.error(tag, &format!("field number {} is reserved", number));
+            }
+            if msg.extension_range.iter().any(|r| r.start.unwrap_or_default() <= number && number < r.end.unwrap_or_default()) {
+                self.error(tag, &format!("field number {} is in an extension range", number));
+            }
+        }
+    }
+
+    fn one_of(&mut self, o: &OneOfNode, msg: &mut DescriptorProto) {
+        let index = msg.oneof_decl.len() as i32;
+        let mut oneof = OneofDescriptorProto {
+            name: Some(o.name().as_identifier()),
+            ..Default::default()
+        };
+        let mut has_fields = false;
+        for decl in o.decls().iter() {
+            match decl {
+                OneOfElement::Option(opt) => {
+                    if let Some(opt) = self.option(opt) {
+                        oneof.options.get_or_insert_with(Default::default).uninterpreted_option.push(opt);
+                    }
+                },
+                OneOfElement::Field(f) => {
+                    let field = self.field(f, Some(index));
+                    msg.field.push(field);
+                    has_fields = true;
+                },
+                OneOfElement::Group(g) => {
+                    let (field, group) = self.group(g, Some(index));
+                    msg.nested_type.push(group);
+                    msg.field.push(field);
+                    has_fields = true;
+                },
+                OneOfElement::Empty(_) | OneOfElement::Error(_) => {}
+            }
+        }
+        if !has_fields {
+            self.error(o.name(), &format!("oneof \"{}\" must have at least one field", o.name().as_identifier()));
+        }
+        msg.oneof_decl.push(oneof);
+    }
+
+    fn extend(&mut self, e: &ExtendNode, extensions: &mut Vec<FieldDescriptorProto>, nested: &mut Vec<DescriptorProto>) {
+        let mut has_fields = false;
+        for decl in e.decls().iter() {
+            match decl {
+                ExtendElement::Field(f) => {
+                    let field = self.field(f, None);
+                    extensions.push(field);
+                    has_fields = true;
+                },
+                ExtendElement::Group(g) => {
+                    let (field, group) = self.group(g, None);
+                    nested.push(group);
+                    extensions.push(field);
+                    has_fields = true;
+                },
+                ExtendElement::Empty(_) | ExtendElement::Error(_) => {}
+            }
+        }
+        if !has_fields {
+            self.error(e.extendee(), "extend blocks must have at least one field");
+        }
+    }
+
+    /// Returns the label of a field or group, checking it against the
+    /// syntax of the file. Fields in oneofs have no label in the source
+    /// but are optional.
+    fn label(&mut self, keyword: Option<&str>, node: &dyn NodeTrait, in_one_of: bool) -> (FieldLabel, bool) {
+        let mut proto3_optional = false;
+        let label = match keyword {
+            Some("required") => FieldLabel::Required,
+            Some("repeated") => FieldLabel::Repeated,
+            Some(_) => FieldLabel::Optional,
+            None => {
+                if self.syntax == Syntax::Proto2 && !in_one_of {
+                    self.error(node, "fields in proto2 must be labeled \"optional\", \"required\" or \"repeated\"");
+                }
+                FieldLabel::Optional
+            }
+        };
+        match (self.syntax, keyword) {
+            (Syntax::Proto3, Some("required")) => self.error(node, "required fields are not allowed in proto3"),
+            (Syntax::Proto3, Some("optional")) => proto3_optional = true,
+            (Syntax::Editions, Some(k @ "required")) | (Syntax::Editions, Some(k @ "optional")) => {
+                self.error(node, &format!("label \"{}\" is not allowed in editions", k));
+            },
+            _ => {}
+        }
+        return (label, proto3_optional)
+    }
+
+    fn field(&mut self, f: &FieldNode, one_of: Option<i32>) -> FieldDescriptorProto {
+        let name = f.name().as_identifier();
+        let keyword = f.label().map(|l| l.as_identifier());
+        let (label, proto3_optional) = self.label(keyword.as_deref(), f, one_of.is_some());
+        let mut field = FieldDescriptorProto {
+            name: Some(name.clone()),
+            number: Some(self.field_number(f.tag(), f.extendee().is_some())),
+            label: Some(label),
+            extendee: f.extendee().map(|e| e.as_identifier()),
+            oneof_index: one_of,
+            ..Default::default()
+        };
+        if proto3_optional {
+            field.proto3_optional = Some(true);
+        }
+        set_type(&mut field, &f.field_type().as_identifier());
+        if let Some(opts) = f.options() {
+            self.field_options(opts, &mut field);
+        }
+        if field.json_name.is_none() {
+            field.json_name = Some(json_name(&name));
+        }
+        return field
+    }
+
+    fn group(&mut self, g: &GroupNode, one_of: Option<i32>) -> (FieldDescriptorProto, DescriptorProto) {
+        if self.syntax != Syntax::Proto2 {
+            self.error(g.keyword(), &format!("groups are not allowed in {}", self.syntax.name()));
+        }
+        let type_name = g.name().as_identifier();
+        let name = type_name.to_ascii_lowercase();
+        let keyword = g.label().map(|l| l.as_identifier());
+        let (label, _) = self.label(keyword.as_deref(), g, one_of.is_some());
+        let mut field = FieldDescriptorProto {
+            name: Some(name.clone()),
+            number: Some(self.field_number(g.tag(), g.extendee().is_some())),
+            label: Some(label),
+            r#type: Some(FieldType::Group),
+            type_name: Some(type_name),
+            extendee: g.extendee().map(|e| e.as_identifier()),
+            oneof_index: one_of,
+            ..Default::default()
+        };
+        if let Some(opts) = g.options() {
+            self.field_options(opts, &mut field);
+        }
+        if field.json_name.is_none() {
+            field.json_name = Some(json_name(&name));
+        }
+        let msg = self.message(g.name(), g.body());
+        return (field, msg)
+    }
+
+    fn map_field(&mut self, f: &MapFieldNode) -> (FieldDescriptorProto, DescriptorProto) {
+        let name = f.name().as_identifier();
+        let entry_name = map_entry_name(&name);
+        let map_type = f.map_type();
+
+        let key_type = map_type.key_type().as_identifier();
+        let mut key = FieldDescriptorProto {
+            name: Some("key".to_string()),
+            number: Some(1),
+            label: Some(FieldLabel::Optional),
+            json_name: Some("key".to_string()),
+            ..Default::default()
+        };
+        set_type(&mut key, &key_type);
+        match key.r#type {
+            Some(FieldType::Double) | Some(FieldType::Float) | Some(FieldType::Bytes) | None => {
+                self.error(map_type.key_type(), &format!("invalid map key type \"{}\": keys must be integers, bools or strings", key_type));
+            },
+            _ => {}
+        }
+        let mut value = FieldDescriptorProto {
+            name: Some("value".to_string()),
+            number: Some(2),
+            label: Some(FieldLabel::Optional),
+            json_name: Some("value".to_string()),
+            ..Default::default()
+        };
+        set_type(&mut value, &map_type.value_type().as_identifier());
+        let entry = DescriptorProto {
+            name: Some(entry_name.clone()),
+            field: vec![key, value],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let mut field = FieldDescriptorProto {
+            name: Some(name.clone()),
+            number: Some(self.field_number(f.tag(), false)),
+            label: Some(FieldLabel::Repeated),
+            r#type: Some(FieldType::Message),
+            type_name: Some(entry_name),
+            ..Default::default()
+        };
+        if let Some(opts) = f.options() {
+            self.field_options(opts, &mut field);
+        }
+        if field.json_name.is_none() {
+            field.json_name = Some(json_name(&name));
+        }
+        return (field, entry)
+    }
+
+    /// Stores the compact options of a field, applying `default` and
+    /// `json_name` to the field itself.
+    fn field_options(&mut self, opts: &CompactOptionsNode, field: &mut FieldDescriptorProto) {
+        for opt in opts.options().iter() {
+            let parts = opt.name().parts();
+            let pseudo = if parts.len() == 1 && !parts[0].is_extension() { Some(parts[0].name().as_identifier()) } else { None };
+            match pseudo.as_deref() {
+                Some("default") => {
+                    if field.default_value.is_some() {
+                        self.error(opt.name(), "option \"default\" is already set");
+                    }
+                    field.default_value = self.default_value(field, opt.val());
+                },
+                Some("json_name") => {
+                    if field.json_name.is_some() {
+                        self.error(opt.name(), "option \"json_name\" is already set");
+                    }
+                    if field.extendee.is_some() {
+                        self.error(opt.name(), "option \"json_name\" is not allowed on extensions");
+                    }
+                    match opt.val().as_string() {
+                        Some(s) => field.json_name = Some(s),
+                        None => self.error(opt.val(), "json_name must be a string")
+                    }
+                },
+                _ => {
+                    match pseudo.as_deref() {
+                        Some("packed") if !packable(field) => {
+                            self.error(opt.name(), "[packed = true] can only be specified for repeated primitive fields");
+                        },
+                        Some("ctype") if field.r#type != Some(FieldType::String) && field.r#type != Some(FieldType::Bytes) => {
+                            self.error(opt.name(), "ctype option is not allowed on non-string fields");
+                        },
+                        _ => {}
+                    }
+                    if let Some(u) = self.option(opt) {
+                        field.options.get_or_insert_with(Default::default).uninterpreted_option.push(u);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the default value of a field as descriptors store it: as
+    /// text, with numbers in canonical form and bytes escaped.
+    fn default_value(&mut self, field: &FieldDescriptorProto, val: &ValueNode) -> Option<String> {
+        if self.syntax == Syntax::Proto3 {
+            self.error(val, "default values are not allowed in proto3");
+            return None
+        }
+        if field.label == Some(FieldLabel::Repeated) {
+            self.error(val, "repeated fields can't have default values");
+            return None
+        }
+        let (min, max): (i64, u64) = match field.r#type {
+            None => {
+                // The type isn't known until linking, so the linker checks
+                // the value once it knows whether the type is an enum or a
+                // message. Keywords are identifiers too when they name a
+                // value.
+                match val {
+                    ValueNode::Identifier(i) => return Some(i.as_identifier()),
+                    ValueNode::Bool(b) => return Some(b.keyword().as_identifier()),
+                    ValueNode::SpecialFloat(f) => return Some(f.keyword().as_identifier()),
+                    _ => return None
+                }
+            },
+            Some(FieldType::Group) | Some(FieldType::Message) => {
+                self.error(val, "messages can't have default values");
+                return None
+            },
+            Some(FieldType::Bool) => {
+                if let ValueNode::Bool(b) = val {
+                    return Some(b.keyword().as_identifier())
+                }
+                self.error(val, "expected \"true\" or \"false\"");
+                return None
+            },
+            Some(FieldType::String) | Some(FieldType::Bytes) => {
+                match val.as_string_value() {
+                    Some(s) if field.r#type == Some(FieldType::Bytes) => return Some(c_escape(&s.as_bytes())),
+                    Some(s) => return Some(s.as_string()),
+                    None => {
+                        self.error(val, "expected string for field default value");
+                        return None
+                    }
+                }
+            },
+            Some(FieldType::Float) | Some(FieldType::Double) => {
+                let v = match val {
+                    ValueNode::NegativeInt(i) => i.as_int64().0 as f64,
+                    ValueNode::PositiveUint(i) => i.as_uint64().0 as f64,
+                    _ => match val.as_float_value() {
+                        Some(f) => f.as_float(),
+                        None => {
+                            self.error(val, "expected number for field default value");
+                            return None
+                        }
+                    }
+                };
+                if field.r#type == Some(FieldType::Float) {
+                    return Some(simple_ftoa(v as f32))
+                }
+                return Some(simple_dtoa(v))
+            },
+            Some(FieldType::Int32) | Some(FieldType::Sint32) | Some(FieldType::Sfixed32) => (i32::MIN as i64, i32::MAX as u64),
+            Some(FieldType::Int64) | Some(FieldType::Sint64) | Some(FieldType::Sfixed64) => (i64::MIN, i64::MAX as u64),
+            Some(FieldType::Uint32) | Some(FieldType::Fixed32) => (0, u32::MAX as u64),
+            Some(FieldType::Uint64) | Some(FieldType::Fixed64) | Some(FieldType::Enum) => (0, u64::MAX)
+        };
+        let i = match val.as_int_value() {
+            Some(i) => i,
+            None => {
+                self.error(val, "expected integer for field default value");
+                return None
+            }
+        };
+        if let ValueNode::NegativeInt(_) = val {
+            let (v, _) = i.as_int64();
+            if min == 0 && v != 0 {
+                self.error(val, "unsigned fields can't have negative default values");
+                return None
+            }
+            if v < min {
+                self.error(val, "integer out of range for field default value");
+                return None
+            }
+            return Some(v.to_string())
+        }
+        let (v, _) = i.as_uint64();
+        if v > max {
+            self.error(val, "integer out of range for field default value");
+            return None
+        }
+        return Some(v.to_string())
+    }
+
+    fn enum_type(&mut self, e: &EnumNode) -> EnumDescriptorProto {
+        let mut en = EnumDescriptorProto {
+            name: Some(e.name().as_identifier()),
+            ..Default::default()
+        };
+        for decl in e.decls().iter() {
+            match decl {
+                EnumElement::Option(o) => {
+                    if let Some(opt) = self.option(o) {
+                        en.options.get_or_insert_with(Default::default).uninterpreted_option.push(opt);
+                    }
+                },
+                EnumElement::Value(v) => {
+                    let number = self.int32(v.number(), "enum value");
+                    if en.value.is_empty() && number != 0 && self.syntax == Syntax::Proto3 {
+                        self.error(v.number(), "the first enum value must be zero in proto3");
+                    }
+                    let mut value = EnumValueDescriptorProto {
+                        name: Some(v.name().as_identifier()),
+                        number: Some(number),
+                        ..Default::default()
+                    };
+                    if let Some(opts) = v.options() {
+                        value.options = Some(EnumValueOptions {
+                            uninterpreted_option: self.compact_options(opts),
+                            ..Default::default()
+                        });
+                    }
+                    en.value.push(value);
+                },
+                EnumElement::Reserved(r) => {
+                    for range in r.ranges().iter() {
+                        let start = self.int32(range.start_value(), "enum value");
+                        let end = match range.end_value() {
+                            Some(end) => self.int32(end, "enum value"),
+                            None if range.is_max() => i32::MAX,
+                            None => start
+                        };
+                        if end < start {
+                            self.error(range, "reserved range end number must be greater than or equal to start number");
+                        }
+                        // Enum ranges include their end.
+                        let exclusive = (start as i64, end as i64 + 1);
+                        let earlier: Vec<(i64, i64)> = en.reserved_range.iter().map(|r| (r.start.unwrap_or_default() as i64, r.end.unwrap_or_default() as i64 + 1)).collect();
+                        if let Some(other) = overlap(exclusive, &earlier) {
+                            self.error(range, &format!("reserved range {} overlaps with already-defined range {}", range_text(exclusive), range_text(other)));
+                        }
+                        en.reserved_range.push(EnumReservedRange { start: Some(start), end: Some(end) });
+                    }
+                    en.reserved_name.extend(reserved_names(r));
+                },
+                EnumElement::Empty(_) | EnumElement::Error(_) => {}
+            }
+        }
+        if en.value.is_empty() {
+            self.error(e.name(), &format!("enum \"{}\" must have at least one value", e.name().as_identifier()));
+        }
+        self.check_enum_values(e, &en);
+        return en
+    }
+
+    /// Checks that enum values only share numbers when the enum allows
+    /// aliases, and that no value uses a reserved number or name.
+    fn check_enum_values(&mut self, e: &EnumNode, en: &EnumDescriptorProto) {
+        // Like protoc, look for the option before it is interpreted.
+        let allow_alias = en.options.as_ref().is_some_and(|o| o.uninterpreted_option.iter().any(|u| {
+            u.name.len() == 1 && u.name[0].name_part == "allow_alias" && u.identifier_value.as_deref() == Some("true")
+        }));
+        let nodes = e.decls().iter().filter_map(|d| match d {
+            EnumElement::Value(v) => Some(v),
+            _ => None
+        });
+        let mut used: HashMap<i32, &str> = HashMap::new();
+        let mut has_alias = false;
+        for (value, node) in en.value.iter().zip(nodes) {
+            let number = value.number.unwrap_or_default();
+            let name = value.name.as_deref().unwrap_or_default();
+            match used.get(&number) {
+                Some(previous) => {
+                    has_alias = true;
+                    if !allow_alias {
+                        self.error(node.number(), &format!("\"{}\" uses the same enum value as \"{}\"; set option allow_alias = true if this is intended", name, previous));
+                    }
+                },
+                None => {
+                    used.insert(number, name);
+                }
+            }
+            if en.reserved_range.iter().any(|r| r.start.unwrap_or_default() <= number && number <= r.end.unwrap_or_default()) {
+                self.error(node.number(), &format!("enum value {} is reserved", number));
+            }
+            if en.reserved_name.iter().any(|n| n == name) {
+                self.error(node.name(), &format!("enum value name \"{}\" is reserved", name));
+            }
+        }
+        if allow_alias && !has_alias {
+            self.error(e.name(), &format!("enum \"{}\" allows aliases but no two of its values share a number", e.name().as_identifier()));
+        }
+    }
+
+    fn service(&mut self, s: &ServiceNode) -> ServiceDescriptorProto {
+        let mut service = ServiceDescriptorProto {
+            name: Some(s.name().as_identifier()),
+            ..Default::default()
+        };
+        for decl in s.decls().iter() {
+            match decl {
+                ServiceElement::Option(o) => {
+                    if let Some(opt) = self.option(o) {
+                        service.options.get_or_insert_with(Default::default).uninterpreted_option.push(opt);
+                    }
+                },
+                ServiceElement::RPC(r) => {
+                    let method = self.method(r);
+                    service.method.push(method);
+                },
+                ServiceElement::Empty(_) | ServiceElement::Error(_) => {}
+            }
+        }
+        return service
+    }
+
+    fn method(&mut self, r: &RPCNode) -> MethodDescriptorProto {
+        let mut method = MethodDescriptorProto {
+            name: Some(r.name().as_identifier()),
+            input_type: Some(r.input().message_type().as_identifier()),
+            output_type: Some(r.output().message_type().as_identifier()),
+            ..Default::default()
+        };
+        if r.input().is_stream() {
+            method.client_streaming = Some(true);
+        }
+        if r.output().is_stream() {
+            method.server_streaming = Some(true);
+        }
+        for decl in r.decls().iter() {
+            match decl {
+                MethodElement::Option(o) => {
+                    if let Some(opt) = self.option(o) {
+                        method.options.get_or_insert_with(Default::default).uninterpreted_option.push(opt);
+                    }
+                },
+                MethodElement::Empty(_) | MethodElement::Error(_) => {}
+            }
+        }
+        return method
+    }
+
+    /// Returns the start and exclusive end of a range of field numbers. A
+    /// range ending at `max` is given the end for normal messages. `what`
+    /// names the range in errors: "reserved" or "extension".
+    fn message_range(&mut self, range: &RangeNode, what: &str) -> (i32, i32) {
+        let start = self.int32(range.start_value(), "field number");
+        let end = match range.end_value() {
+            Some(end) => self.int32(end, "field number"),
+            None if range.is_max() => return (start, MAX_FIELD_NUMBER_END),
+            None => start
+        };
+        if end < start {
+            self.error(range, &format!("{} range end number must be greater than or equal to start number", what));
+        }
+        return (start, end.saturating_add(1))
+    }
+
+    /// Returns the number of a field, checking it is one protoc accepts.
+    /// Extensions may go past the largest field number in message sets,
+    /// so their numbers are checked against the extendee when linking.
+    fn field_number(&mut self, tag: &dyn IntValueNodeTrait, is_extension: bool) -> i32 {
+        let (n, ok) = tag.as_int64();
+        if ok && n <= 0 {
+            self.error(tag, "field numbers must be positive integers");
+        } else if !ok || n > i32::MAX as i64 || (!is_extension && n >= MAX_FIELD_NUMBER_END as i64) {
+            self.error(tag, &format!("field numbers can't be greater than {}", MAX_FIELD_NUMBER_END - 1));
+        } else if RESERVED_FIELD_NUMBERS.contains(&n) {
+            self.error(tag, &format!("field numbers {} through {} are reserved for the protocol buffer library implementation", RESERVED_FIELD_NUMBERS.start(), RESERVED_FIELD_NUMBERS.end()));
+        }
+        return n.clamp(0, i32::MAX as i64) as i32
+    }
+
+    fn int32(&mut self, v: &dyn IntValueNodeTrait, what: &str) -> i32 {
+        let (n, ok) = v.as_int64();
+        if !ok || n < i32::MIN as i64 || n > i32::MAX as i64 {
+            self.error(v, &format!("{} out of range", what));
+            return 0
+        }
+        return n as i32
+    }
+
+    fn compact_options(&mut self, opts: &CompactOptionsNode) -> Vec<UninterpretedOption> {
+        return opts.options().iter().filter_map(|o| self.option(o)).collect()
+    }
+
+    /// Returns the option as written, or `None` if its value can't be an
+    /// option value.
+    fn option(&mut self, o: &OptionNode) -> Option<UninterpretedOption> {
+        let mut opt = UninterpretedOption {
+            name: o.name().parts().iter().map(|p| NamePart {
+                name_part: p.name().as_identifier(),
+                is_extension: p.is_extension()
+            }).collect(),
+            ..Default::default()
+        };
+        match o.val() {
+            ValueNode::String(_) | ValueNode::CompoundString(_) => {
+                opt.string_value = o.val().as_string_value().map(|s| s.as_bytes());
+            },
+            ValueNode::Uint(_) | ValueNode::PositiveUint(_) => {
+                opt.positive_int_value = o.val().as_int_value().map(|i| i.as_uint64().0);
+            },
+            ValueNode::NegativeInt(i) => opt.negative_int_value = Some(i.as_int64().0),
+            ValueNode::Float(f) => opt.double_value = Some(f.as_float()),
+            ValueNode::SignedFloat(f) => {
+                // protoc reads `-nan` as a plain NaN.
+                let v = f.as_float();
+                opt.double_value = Some(if v.is_nan() { f64::NAN } else { v });
+            },
+            ValueNode::SpecialFloat(f) => opt.identifier_value = Some(f.keyword().as_identifier()),
+            ValueNode::Bool(b) => opt.identifier_value = Some(b.keyword().as_identifier()),
+            ValueNode::Identifier(i) => opt.identifier_value = Some(i.as_identifier()),
+            ValueNode::Message(m) => {
+                let mut tokens = vec![];
+                terminals(m, &mut tokens);
+                // Leave out the braces around the message.
+                let inner = &tokens[1..tokens.len() - 1];
+                opt.aggregate_value = Some(inner.join(" "));
+            },
+            ValueNode::Array(a) => {
+                self.error(a, "array values are only allowed inside message literals");
+                return None
+            }
+        }
+        return Some(opt)
+    }
+}
+
+// Returns the name and number nodes of the fields of a message, in the
+// order they are added to its descriptor.
+fn field_nodes(body: &MessageBody) -> Vec<(&dyn NodeTrait, &dyn NodeTrait)> {
+    let mut nodes: Vec<(&dyn NodeTrait, &dyn NodeTrait)> = vec![];
+    for decl in body.decls().iter() {
+        match decl {
+            MessageElement::Field(f) => nodes.push((f.name(), f.tag())),
+            MessageElement::MapField(f) => nodes.push((f.name(), f.tag())),
+            MessageElement::Group(g) => nodes.push((g.name(), g.tag())),
+            MessageElement::OneOf(o) => {
+                for decl in o.decls().iter() {
+                    match decl {
+                        OneOfElement::Field(f) => nodes.push((f.name(), f.tag())),
+                        OneOfElement::Group(g) => nodes.push((g.name(), g.tag())),
+                        _ => {}
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    return nodes
+}
+
+// Returns the first of `others` that shares a number with `range`. Ranges
+// have exclusive ends, and empty ones, given end first, overlap nothing.
+fn overlap(range: (i64, i64), others: &[(i64, i64)]) -> Option<(i64, i64)> {
+    if range.0 >= range.1 {
+        return None
+    }
+    return others.iter().copied().find(|o| o.0 < o.1 && o.0 < range.1 && range.0 < o.1)
+}
+
+// Formats a range with an exclusive end the way it is written in source.
+fn range_text(range: (i64, i64)) -> String {
+    return format!("{} to {}", range.0, range.1 - 1)
+}
+
+fn reserved_names(r: &ReservedNode) -> Vec<String> {
+    let mut names: Vec<String> = r.names().iter().map(|n| n.as_string()).collect();
+    names.extend(r.identifiers().iter().map(|i| i.as_identifier()));
+    return names
+}
+
+// Collects the raw text of every token under `node`.
+fn terminals(node: &dyn NodeTrait, out: &mut Vec<String>) {
+    if let Some(t) = node.as_terminal() {
+        out.push(t.raw_text().to_string());
+        return
+    }
+    if let Some(c) = node.as_composite() {
+        for child in c.children().iter() {
+            terminals(child.as_ref(), out);
+        }
+    }
+}
+
+pub(crate) fn scalar_type(name: &str) -> Option<FieldType> {
+    match name {
+        "double" => return Some(FieldType::Double),
+        "float" => return Some(FieldType::Float),
+        "int64" => return Some(FieldType::Int64),
+        "uint64" => return Some(FieldType::Uint64),
+        "int32" => return Some(FieldType::Int32),
+        "fixed64" => return Some(FieldType::Fixed64),
+        "fixed32" => return Some(FieldType::Fixed32),
+        "bool" => return Some(FieldType::Bool),
+        "string" => return Some(FieldType::String),
+        "bytes" => return Some(FieldType::Bytes),
+        "uint32" => return Some(FieldType::Uint32),
+        "sfixed32" => return Some(FieldType::Sfixed32),
+        "sfixed64" => return Some(FieldType::Sfixed64),
+        "sint32" => return Some(FieldType::Sint32),
+        "sint64" => return Some(FieldType::Sint64),
+        _ => return None
+    }
+}
+
+/// Sets the type of a field from its name in the source: the type itself
+/// for scalars, or the type name for messages and enums.
+fn set_type(field: &mut FieldDescriptorProto, name: &str) {
+    match scalar_type(name) {
+        Some(t) => field.r#type = Some(t),
+        None => field.type_name = Some(name.to_string())
+    }
+}
+
+/// Adds a oneof for each proto3 `optional` field, named after the field
+/// with a `_` in front, and an `X` for each clash with another name.
+fn add_synthetic_oneofs(msg: &mut DescriptorProto) {
+    let mut names: Vec<String> = msg.field.iter().filter_map(|f| f.name.clone()).collect();
+    names.extend(msg.oneof_decl.iter().filter_map(|o| o.name.clone()));
+    for i in 0..msg.field.len() {
+        if msg.field[i].proto3_optional != Some(true) {
+            continue
+        }
+        let mut name = msg.field[i].name.clone().unwrap_or_default();
+        if !name.starts_with('_') {
+            name.insert(0, '_');
+        }
+        while names.contains(&name) {
+            name.insert(0, 'X');
+        }
+        names.push(name.clone());
+        msg.field[i].oneof_index = Some(msg.oneof_decl.len() as i32);
+        msg.oneof_decl.push(OneofDescriptorProto { name: Some(name), options: None });
+    }
+}
+
+/// Returns the JSON name protoc gives a field: its name with each
+/// underscore dropped and the letter after it capitalized.
+// Reports whether `packed` may be set on a field. Fields whose type isn't
+// known until linking are enums or messages, so they may be packed only if
+// they turn out to be enums.
+fn packable(field: &FieldDescriptorProto) -> bool {
+    if field.label != Some(FieldLabel::Repeated) {
+        return false
+    }
+    match field.r#type {
+        Some(FieldType::String) | Some(FieldType::Bytes) | Some(FieldType::Message) | Some(FieldType::Group) => return false,
+        _ => return true
+    }
+}
+
+pub fn json_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut capitalize = false;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if capitalize {
+            out.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            out.push(c);
+        }
+    }
+    return out
+}
+
+/// Returns the name of the entry message of a map field: the field name in
+/// CamelCase followed by `Entry`.
+pub fn map_entry_name(field_name: &str) -> String {
+    let mut out = String::with_capacity(field_name.len() + 5);
+    let mut capitalize = true;
+    for c in field_name.chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if capitalize {
+            out.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out.push_str("Entry");
+    return out
+}
+
+// Escapes bytes the way protoc writes the defaults of bytes fields.
+fn c_escape(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &b in bytes.iter() {
+        match b {
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            b'"' => out.push_str("\\\""),
+            b'\'' => out.push_str("\\'"),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b))
+        }
+    }
+    return out
+}
+
+// Formats like C's `%.<precision>g`.
+fn format_g(v: f64, precision: usize) -> String {
+    if v == 0.0 {
+        return if v.is_sign_negative() { "-0".to_string() } else { "0".to_string() }
+    }
+    let sci = format!("{:.*e}", precision - 1, v);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if exp < -4 || exp >= precision as i32 {
+        let mantissa = trim_fraction(mantissa);
+        return format!("{}e{}{:02}", mantissa, if exp < 0 { '-' } else { '+' }, exp.abs())
+    }
+    let fixed = format!("{:.*}", (precision as i32 - 1 - exp) as usize, v);
+    return trim_fraction(&fixed).to_string()
+}
+
+fn trim_fraction(s: &str) -> &str {
+    if !s.contains('.') {
+        return s
+    }
+    return s.trim_end_matches('0').trim_end_matches('.')
+}
+
+fn special_float(v: f64) -> Option<String> {
+    if v.is_nan() {
+        return Some("nan".to_string())
+    }
+    if v.is_infinite() {
+        return Some(if v < 0.0 { "-inf".to_string() } else { "inf".to_string() })
+    }
+    return None
+}
+
+// The shortest of `%.15g` and `%.17g` that reads back as the same double.
+fn simple_dtoa(v: f64) -> String {
+    if let Some(s) = special_float(v) {
+        return s
+    }
+    let s = format_g(v, 15);
+    if s.parse::<f64>() == Ok(v) {
+        return s
+    }
+    return format_g(v, 17)
+}
+
+// The shortest of `%.6g` and `%.9g` that reads back as the same float.
+fn simple_ftoa(v: f32) -> String {
+    if let Some(s) = special_float(v as f64) {
+        return s
+    }
+    let s = format_g(v as f64, 6);
+    if s.parse::<f32>() == Ok(v) {
+        return s
+    }
+    return format_g(v as f64, 9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn errors(source: &str) -> Vec<String> {
+        let file = parse("a.proto", source).unwrap();
+        return compile(&file).err().unwrap_or_default().iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn builds_the_descriptor() {
+        let source = "syntax = \"proto3\";\npackage a.b;\nmessage M {\n  optional int32 foo_bar = 1;\n  map<string, M> m = 2;\n  oneof o { string s = 4; }\n  reserved 10 to 12;\n}\nservice S { rpc R (M) returns (stream M); }\n";
+        let fd = compile(&parse("a.proto", source).unwrap()).unwrap();
+        assert_eq!(fd.name.as_deref(), Some("a.proto"));
+        assert_eq!(fd.package.as_deref(), Some("a.b"));
+        assert_eq!(fd.syntax.as_deref(), Some("proto3"));
+
+        let m = &fd.message_type[0];
+        let foo_bar = &m.field[0];
+        assert_eq!(foo_bar.json_name.as_deref(), Some("fooBar"));
+        assert_eq!((foo_bar.label, foo_bar.r#type), (Some(FieldLabel::Optional), Some(FieldType::Int32)));
+        assert_eq!((foo_bar.oneof_index, foo_bar.proto3_optional), (Some(1), Some(true)));
+        let oneofs: Vec<&str> = m.oneof_decl.iter().map(|o| o.name.as_deref().unwrap()).collect();
+        assert_eq!(oneofs, vec!["o", "_foo_bar"]);
+        assert_eq!(m.field[2].oneof_index, Some(0));
+
+        assert_eq!(m.field[1].type_name.as_deref(), Some("MEntry"));
+        let entry = &m.nested_type[0];
+        assert_eq!(entry.name.as_deref(), Some("MEntry"));
+        assert_eq!(entry.options.as_ref().unwrap().map_entry, Some(true));
+        assert_eq!(entry.field[1].type_name.as_deref(), Some("M"));
+
+        assert_eq!((m.reserved_range[0].start, m.reserved_range[0].end), (Some(10), Some(13)));
+        assert_eq!(fd.service[0].method[0].server_streaming, Some(true));
+    }
+
+    #[test]
+    fn rule_violations() {
+        assert_eq!(errors("syntax = \"proto3\";\nmessage M { int32 a = 1 [default = 1]; required int32 b = 2; }\nenum E { A = 1; }\n"), vec![
+            "a.proto:2:36: default values are not allowed in proto3",
+            "a.proto:2:40: required fields are not allowed in proto3",
+            "a.proto:3:14: the first enum value must be zero in proto3"
+        ]);
+        assert_eq!(errors("message M { int32 a = 1; repeated int32 b = 2 [default = 1]; optional uint32 c = 3 [default = -1]; map<float, int32> m = 4; }\n"), vec![
+            "a.proto:1:13: fields in proto2 must be labeled \"optional\", \"required\" or \"repeated\"",
+            "a.proto:1:58: repeated fields can't have default values",
+            "a.proto:1:95: unsigned fields can't have negative default values",
+            "a.proto:1:104: invalid map key type \"float\": keys must be integers, bools or strings"
+        ]);
+    }
+
+    #[test]
+    fn field_numbers() {
+        let source = "syntax = \"proto2\";\nmessage M {\n  optional int32 a = 0;\n  optional int32 b = 19000;\n  optional int32 c = 600000000;\n  optional int32 d = 1;\n  optional int32 e = 1;\n  optional int32 f = 7;\n  optional int32 g = 8;\n  optional int32 h = 150;\n  reserved 7;\n  reserved \"g\";\n  extensions 100 to 199;\n}\n";
+        assert_eq!(errors(source), vec![
+            "a.proto:3:22: field numbers must be positive integers",
+            "a.proto:4:22: field numbers 19000 through 19999 are reserved for the protocol buffer library implementation",
+            "a.proto:5:22: field numbers can't be greater than 536870911",
+            "a.proto:7:22: field number 1 is already used by \"d\"",
+            "a.proto:8:22: field number 7 is reserved",
+            "a.proto:9:18: field name \"g\" is reserved",
+            "a.proto:10:22: field number 150 is in an extension range"
+        ]);
+    }
+
+    #[test]
+    fn enum_values() {
+        assert_eq!(errors("enum E { Z = 0; Y = 0; }"), vec![
+            "a.proto:1:21: \"Y\" uses the same enum value as \"Z\"; set option allow_alias = true if this is intended"
+        ]);
+        assert!(errors("enum E { option allow_alias = true; Z = 0; Y = 0; }").is_empty());
+        assert_eq!(errors("enum E { Z = 0; Q = 5; reserved 5; reserved \"Q\"; }"), vec![
+            "a.proto:1:21: enum value 5 is reserved",
+            "a.proto:1:17: enum value name \"Q\" is reserved"
+        ]);
+    }
+
+    #[test]
+    fn reserved_and_extension_ranges() {
+        let source = "message M {\n  reserved 5 to 1;\n  extensions 1 to 0;\n  reserved 10 to 20, 15;\n  extensions 100 to 200;\n  extensions 150 to max;\n  extensions 18;\n}\nenum E {\n  Z = 0;\n  reserved 3 to 2;\n  reserved 1 to 4, 4 to max;\n}\n";
+        assert_eq!(errors(source), vec![
+            "a.proto:2:12: reserved range end number must be greater than or equal to start number",
+            "a.proto:3:14: extension range end number must be greater than or equal to start number",
+            "a.proto:4:22: reserved range 15 to 15 overlaps with already-defined range 10 to 20",
+            "a.proto:6:14: extension range 150 to 536870911 overlaps with already-defined range 100 to 200",
+            "a.proto:7:14: extension range 18 to 18 overlaps with reserved range 10 to 20",
+            "a.proto:11:12: reserved range end number must be greater than or equal to start number",
+            "a.proto:12:20: reserved range 4 to 2147483647 overlaps with already-defined range 1 to 4"
+        ]);
+    }
+
+    #[test]
+    fn packed_and_ctype() {
+        let source = "message M {\n  optional int32 a = 1 [packed = true];\n  repeated string b = 2 [packed = false];\n  map<int32, int32> c = 3 [packed = true];\n  optional int32 d = 4 [ctype = CORD];\n  repeated E e = 5 [packed = true, ctype = CORD];\n  repeated int32 f = 6 [packed = true];\n  optional bytes g = 7 [ctype = CORD];\n}\nenum E { Z = 0; }\n";
+        assert_eq!(errors(source), vec![
+            "a.proto:2:25: [packed = true] can only be specified for repeated primitive fields",
+            "a.proto:3:26: [packed = true] can only be specified for repeated primitive fields",
+            "a.proto:4:28: [packed = true] can only be specified for repeated primitive fields",
+            "a.proto:5:25: ctype option is not allowed on non-string fields",
+            "a.proto:6:36: ctype option is not allowed on non-string fields"
+        ]);
+    }
+
+    #[test]
+    fn imports_listed_twice() {
+        assert_eq!(errors("import \"a.proto\";\nimport \"a.proto\";\n"), vec!["a.proto:2:8: import \"a.proto\" is listed twice"]);
+    }
+
+    #[test]
+    fn keyword_enum_defaults() {
+        let file = parse("a.proto", "enum inf { nan = 0; }\nmessage M { optional inf i = 1 [default = nan]; }\n").unwrap();
+        let fd = compile(&file).unwrap();
+        assert_eq!(fd.message_type[0].field[0].default_value.as_deref(), Some("nan"));
+    }
+}
@@ -0,0 +1,224 @@
+//! Compiles a file together with everything it imports.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use crate::accessor::FileAccessor;
+use crate::compile::pos_range;
+use crate::descriptor::FileDescriptorProto;
+use crate::error::Error;
+use crate::file::{FileElement, FileNode};
+use crate::interpret::interpret_options;
+use crate::linker::Linker;
+use crate::parser::parse_partial;
+use crate::source_pos::PosRange;
+use crate::wellknown;
+
+/// Reads, parses and links files through a `FileAccessor`. Files are kept
+/// once compiled, so each is read only once however many files import it.
+/// The well-known types are used from the bundled copies when the accessor
+/// doesn't have them.
+pub struct Compiler {
+    accessor: Box<dyn FileAccessor>,
+    linker: Linker,
+    files: HashMap<String, FileNode>,
+    descriptors: HashMap<String, FileDescriptorProto>,
+    // The errors found in each file. A file that could not be read has the
+    // error of reading it, at the first import naming it.
+    errors: HashMap<String, Vec<Error>>,
+    // Files that could not be read, or that import such a file.
+    // Files importing them are not linked, as their errors would only
+    // repeat the import's.
+    failed: HashSet<String>
+}
+
+impl Compiler {
+    pub fn new(accessor: Box<dyn FileAccessor>) -> Self {
+        return Compiler {
+            accessor,
+            linker: Linker::new(),
+            files: HashMap::new(),
+            descriptors: HashMap::new(),
+            errors: HashMap::new(),
+            failed: HashSet::new()
+        }
+    }
+
+    /// Compiles the file named `name` and every file it imports, directly
+    /// or not. Returns the descriptors of all of them in dependency order,
+    /// so each comes after the files it imports and `name` comes last.
+    pub fn compile(&mut self, name: &str) -> Result<Vec<FileDescriptorProto>, Vec<Error>> {
+        self.load(name, None, &mut vec![]);
+        let mut order = vec![];
+        self.collect(name, &mut HashSet::new(), &mut order);
+        let errors: Vec<Error> = order.iter().flat_map(|f| self.errors[f].iter().cloned()).collect();
+        if !errors.is_empty() {
+            return Err(errors)
+        }
+        return Ok(order.iter().map(|f| self.descriptors[f].clone()).collect())
+    }
+
+    pub fn linker(&self) -> &Linker {
+        return &self.linker
+    }
+
+    /// Returns the syntax tree of a file compiled so far.
+    pub fn file(&self, name: &str) -> Option<&FileNode> {
+        return self.files.get(name)
+    }
+
+    /// Returns the descriptor of a file compiled so far without errors.
+    pub fn descriptor(&self, name: &str) -> Option<&FileDescriptorProto> {
+        return self.descriptors.get(name)
+    }
+
+    // Compiles `name` after the files it imports. `from` is the import
+    // naming it and `stack` the chain of imports that led to it.
+    fn load(&mut self, name: &str, from: Option<PosRange>, stack: &mut Vec<String>) {
+        if self.files.contains_key(name) || self.failed.contains(name) {
+            return
+        }
+        let source = match self.accessor.open(name) {
+            Ok(source) => source,
+            Err(e) => match wellknown::source(name) {
+                Some(source) if e.kind() == io::ErrorKind::NotFound => source.to_string(),
+                _ => {
+                    let error = Error::File { pos_range: from, name: name.to_string(), message: e.to_string().into() };
+                    self.errors.insert(name.to_string(), vec![error]);
+                    self.failed.insert(name.to_string());
+                    return
+                }
+            }
+        };
+        let (file, parse_errors) = parse_partial(name, &source);
+        let mut errors = parse_errors.errors().to_vec();
+
+        stack.push(name.to_string());
+        let mut imports_failed = false;
+        for decl in file.decls().iter() {
+            if let FileElement::Import(i) = decl {
+                let dep = i.name().as_string();
+                if let Some(start) = stack.iter().position(|f| *f == dep) {
+                    let mut chain = stack[start..].to_vec();
+                    chain.push(dep);
+                    errors.push(Error::ImportCycle { pos_range: pos_range(i.name()), chain });
+                    imports_failed = true;
+                    continue
+                }
+                self.load(&dep, Some(pos_range(i.name())), stack);
+                imports_failed |= self.failed.contains(&dep);
+            }
+        }
+        stack.pop();
+
+        if imports_failed {
+            self.failed.insert(name.to_string());
+        } else {
+            match self.linker.link(&file) {
+                Ok(mut fd) => {
+                    let deps: Vec<&FileDescriptorProto> = self.descriptors.values().collect();
+                    errors.extend(interpret_options(&file, &mut fd, &self.linker, &deps));
+                    self.descriptors.insert(name.to_string(), fd);
+                },
+                Err(errs) => errors.extend(errs)
+            }
+        }
+        self.errors.insert(name.to_string(), errors);
+        self.files.insert(name.to_string(), file);
+    }
+
+    // Adds `name` and the files it imports to `out`, imports first.
+    fn collect(&self, name: &str, seen: &mut HashSet<String>, out: &mut Vec<String>) {
+        if !seen.insert(name.to_string()) || !self.errors.contains_key(name) {
+            return
+        }
+        if let Some(file) = self.files.get(name) {
+            for decl in file.decls().iter() {
+                if let FileElement::Import(i) = decl {
+                    self.collect(&i.name().as_string(), seen, out);
+                }
+            }
+        }
+        out.push(name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessor::MemoryAccessor;
+
+    fn compiler(files: &[(&str, &str)]) -> Compiler {
+        let mut accessor = MemoryAccessor::new();
+        for (name, contents) in files.iter() {
+            accessor.insert(name, contents);
+        }
+        return Compiler::new(Box::new(accessor))
+    }
+
+    fn messages(errors: Vec<Error>) -> Vec<String> {
+        return errors.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn follows_imports() {
+        let mut c = compiler(&[
+            ("a.proto", "package a;\nimport \"b.proto\";\nimport \"c.proto\";\nmessage A { optional b.B b = 1; optional c.C c = 2; }\n"),
+            ("b.proto", "package b;\nimport public \"c.proto\";\nmessage B { optional c.C c = 1; }\n"),
+            ("c.proto", "package c;\nmessage C {}\n")
+        ]);
+        let files = c.compile("a.proto").unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_deref().unwrap()).collect();
+        assert_eq!(names, vec!["c.proto", "b.proto", "a.proto"]);
+        let types: Vec<Option<&str>> = files[2].message_type[0].field.iter().map(|f| f.type_name.as_deref()).collect();
+        assert_eq!(types, vec![Some(".b.B"), Some(".c.C")]);
+        assert!(c.file("c.proto").is_some());
+        assert!(c.descriptor("b.proto").is_some());
+        assert!(c.linker().symbol("c.C").is_some());
+    }
+
+    #[test]
+    fn missing_imports() {
+        let mut c = compiler(&[
+            ("a.proto", "import \"b.proto\";\nmessage A { optional B b = 1; }\n"),
+            ("b.proto", "import \"missing.proto\";\nmessage B {}\n")
+        ]);
+        assert_eq!(messages(c.compile("a.proto").unwrap_err()), vec!["b.proto:1:8: could not read \"missing.proto\": file not found"]);
+        assert_eq!(messages(c.compile("nope.proto").unwrap_err()), vec!["could not read \"nope.proto\": file not found"]);
+    }
+
+    #[test]
+    fn import_cycles() {
+        let mut c = compiler(&[
+            ("a.proto", "import \"b.proto\";\n"),
+            ("b.proto", "import \"c.proto\";\n"),
+            ("c.proto", "import \"a.proto\";\n")
+        ]);
+        assert_eq!(messages(c.compile("a.proto").unwrap_err()), vec!["c.proto:1:8: import cycle: a.proto -> b.proto -> c.proto -> a.proto"]);
+    }
+
+    #[test]
+    fn well_known_types_are_bundled() {
+        let mut c = compiler(&[("a.proto", "import \"google/protobuf/timestamp.proto\";\nimport \"google/protobuf/descriptor.proto\";\nmessage A { optional google.protobuf.Timestamp t = 1; optional google.protobuf.FileDescriptorSet s = 2; }\n")]);
+        let files = c.compile("a.proto").unwrap();
+        let types: Vec<Option<&str>> = files.last().unwrap().message_type[0].field.iter().map(|f| f.type_name.as_deref()).collect();
+        assert_eq!(types, vec![Some(".google.protobuf.Timestamp"), Some(".google.protobuf.FileDescriptorSet")]);
+    }
+
+    #[test]
+    fn the_accessor_comes_before_the_bundled_files() {
+        let mut c = compiler(&[
+            ("a.proto", "import \"google/protobuf/empty.proto\";\n"),
+            ("google/protobuf/empty.proto", "package google.protobuf;\nmessage Empty { optional int32 x = 1; }\n")
+        ]);
+        let files = c.compile("a.proto").unwrap();
+        assert_eq!(files[0].message_type[0].field.len(), 1);
+    }
+
+    #[test]
+    fn every_bundled_file_compiles() {
+        for name in wellknown::names() {
+            let mut c = compiler(&[]);
+            assert!(c.compile(name).is_ok(), "{}", name);
+        }
+    }
+}
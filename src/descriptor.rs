@@ -0,0 +1,441 @@
+//! The messages of `google/protobuf/descriptor.proto`, which describe
+//! proto files to code generators and to runtime reflection.
+//!
+//! Each message is a struct with one public field per proto field. Optional
+//! fields are `Option`s that are `None` when unset, and repeated fields are
+//! `Vec`s. Nested messages and enums are named after the message they are
+//! declared in when their own name would be ambiguous, so
+//! `FieldDescriptorProto.Type` is `FieldType`.
+//!
+//! The option messages keep the fields the model doesn't define, such as
+//! custom options, in `unknown_fields`, in binary form.
+
+macro_rules! proto_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $number:literal => $proto_name:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            pub fn number(&self) -> i32 {
+                match self {
+                    $($name::$variant => return $number),*
+                }
+            }
+
+            pub fn from_number(number: i32) -> Option<Self> {
+                match number {
+                    $($number => return Some($name::$variant),)*
+                    _ => return None
+                }
+            }
+
+            /// Returns the name of the value in descriptor.proto, such as
+            /// `TYPE_INT32`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($name::$variant => return $proto_name),*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($proto_name => return Some($name::$variant),)*
+                    _ => return None
+                }
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileDescriptorSet {
+    pub file: Vec<FileDescriptorProto>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileDescriptorProto {
+    pub name: Option<String>,
+    pub package: Option<String>,
+    pub dependency: Vec<String>,
+    /// Indexes into `dependency` of the public imports.
+    pub public_dependency: Vec<i32>,
+    /// Indexes into `dependency` of the weak imports.
+    pub weak_dependency: Vec<i32>,
+    pub message_type: Vec<DescriptorProto>,
+    pub enum_type: Vec<EnumDescriptorProto>,
+    pub service: Vec<ServiceDescriptorProto>,
+    pub extension: Vec<FieldDescriptorProto>,
+    pub options: Option<FileOptions>,
+    pub source_code_info: Option<SourceCodeInfo>,
+    /// `proto3` or `editions`. Unset for proto2.
+    pub syntax: Option<String>,
+    pub edition: Option<String>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DescriptorProto {
+    pub name: Option<String>,
+    pub field: Vec<FieldDescriptorProto>,
+    pub extension: Vec<FieldDescriptorProto>,
+    pub nested_type: Vec<DescriptorProto>,
+    pub enum_type: Vec<EnumDescriptorProto>,
+    pub extension_range: Vec<ExtensionRange>,
+    pub oneof_decl: Vec<OneofDescriptorProto>,
+    pub options: Option<MessageOptions>,
+    pub reserved_range: Vec<ReservedRange>,
+    pub reserved_name: Vec<String>
+}
+
+/// `DescriptorProto.ExtensionRange`. `end` is exclusive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtensionRange {
+    pub start: Option<i32>,
+    pub end: Option<i32>,
+    pub options: Option<ExtensionRangeOptions>
+}
+
+/// `DescriptorProto.ReservedRange`. `end` is exclusive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReservedRange {
+    pub start: Option<i32>,
+    pub end: Option<i32>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtensionRangeOptions {
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub declaration: Vec<Declaration>,
+    pub verification: Option<VerificationState>,
+    pub unknown_fields: Vec<u8>
+}
+
+/// `ExtensionRangeOptions.Declaration`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Declaration {
+    pub number: Option<i32>,
+    pub full_name: Option<String>,
+    pub r#type: Option<String>,
+    pub is_repeated: Option<bool>,
+    pub reserved: Option<bool>,
+    pub repeated: Option<bool>
+}
+
+proto_enum!(
+    /// `ExtensionRangeOptions.VerificationState`.
+    VerificationState {
+        Declaration = 0 => "DECLARATION",
+        Unverified = 1 => "UNVERIFIED"
+    }
+);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldDescriptorProto {
+    pub name: Option<String>,
+    pub number: Option<i32>,
+    pub label: Option<FieldLabel>,
+    /// Unset until a named type is resolved to a message or an enum.
+    pub r#type: Option<FieldType>,
+    pub type_name: Option<String>,
+    pub extendee: Option<String>,
+    pub default_value: Option<String>,
+    pub oneof_index: Option<i32>,
+    pub json_name: Option<String>,
+    pub options: Option<FieldOptions>,
+    pub proto3_optional: Option<bool>
+}
+
+proto_enum!(
+    /// `FieldDescriptorProto.Type`.
+    FieldType {
+        Double = 1 => "TYPE_DOUBLE",
+        Float = 2 => "TYPE_FLOAT",
+        Int64 = 3 => "TYPE_INT64",
+        Uint64 = 4 => "TYPE_UINT64",
+        Int32 = 5 => "TYPE_INT32",
+        Fixed64 = 6 => "TYPE_FIXED64",
+        Fixed32 = 7 => "TYPE_FIXED32",
+        Bool = 8 => "TYPE_BOOL",
+        String = 9 => "TYPE_STRING",
+        Group = 10 => "TYPE_GROUP",
+        Message = 11 => "TYPE_MESSAGE",
+        Bytes = 12 => "TYPE_BYTES",
+        Uint32 = 13 => "TYPE_UINT32",
+        Enum = 14 => "TYPE_ENUM",
+        Sfixed32 = 15 => "TYPE_SFIXED32",
+        Sfixed64 = 16 => "TYPE_SFIXED64",
+        Sint32 = 17 => "TYPE_SINT32",
+        Sint64 = 18 => "TYPE_SINT64"
+    }
+);
+
+proto_enum!(
+    /// `FieldDescriptorProto.Label`.
+    FieldLabel {
+        Optional = 1 => "LABEL_OPTIONAL",
+        Required = 2 => "LABEL_REQUIRED",
+        Repeated = 3 => "LABEL_REPEATED"
+    }
+);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OneofDescriptorProto {
+    pub name: Option<String>,
+    pub options: Option<OneofOptions>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnumDescriptorProto {
+    pub name: Option<String>,
+    pub value: Vec<EnumValueDescriptorProto>,
+    pub options: Option<EnumOptions>,
+    pub reserved_range: Vec<EnumReservedRange>,
+    pub reserved_name: Vec<String>
+}
+
+/// `EnumDescriptorProto.EnumReservedRange`. Unlike the ranges of
+/// messages, `end` is inclusive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnumReservedRange {
+    pub start: Option<i32>,
+    pub end: Option<i32>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnumValueDescriptorProto {
+    pub name: Option<String>,
+    pub number: Option<i32>,
+    pub options: Option<EnumValueOptions>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServiceDescriptorProto {
+    pub name: Option<String>,
+    pub method: Vec<MethodDescriptorProto>,
+    pub options: Option<ServiceOptions>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MethodDescriptorProto {
+    pub name: Option<String>,
+    pub input_type: Option<String>,
+    pub output_type: Option<String>,
+    pub options: Option<MethodOptions>,
+    pub client_streaming: Option<bool>,
+    pub server_streaming: Option<bool>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileOptions {
+    pub java_package: Option<String>,
+    pub java_outer_classname: Option<String>,
+    pub java_multiple_files: Option<bool>,
+    pub java_generate_equals_and_hash: Option<bool>,
+    pub java_string_check_utf8: Option<bool>,
+    pub optimize_for: Option<OptimizeMode>,
+    pub go_package: Option<String>,
+    pub cc_generic_services: Option<bool>,
+    pub java_generic_services: Option<bool>,
+    pub py_generic_services: Option<bool>,
+    pub php_generic_services: Option<bool>,
+    pub deprecated: Option<bool>,
+    pub cc_enable_arenas: Option<bool>,
+    pub objc_class_prefix: Option<String>,
+    pub csharp_namespace: Option<String>,
+    pub swift_prefix: Option<String>,
+    pub php_class_prefix: Option<String>,
+    pub php_namespace: Option<String>,
+    pub php_metadata_namespace: Option<String>,
+    pub ruby_package: Option<String>,
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+proto_enum!(
+    /// `FileOptions.OptimizeMode`.
+    OptimizeMode {
+        Speed = 1 => "SPEED",
+        CodeSize = 2 => "CODE_SIZE",
+        LiteRuntime = 3 => "LITE_RUNTIME"
+    }
+);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageOptions {
+    pub message_set_wire_format: Option<bool>,
+    pub no_standard_descriptor_accessor: Option<bool>,
+    pub deprecated: Option<bool>,
+    pub map_entry: Option<bool>,
+    pub deprecated_legacy_json_field_conflicts: Option<bool>,
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldOptions {
+    pub ctype: Option<CType>,
+    pub packed: Option<bool>,
+    pub jstype: Option<JSType>,
+    pub lazy: Option<bool>,
+    pub unverified_lazy: Option<bool>,
+    pub deprecated: Option<bool>,
+    pub weak: Option<bool>,
+    pub debug_redact: Option<bool>,
+    pub retention: Option<OptionRetention>,
+    pub target: Option<OptionTargetType>,
+    pub targets: Vec<OptionTargetType>,
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+proto_enum!(
+    /// `FieldOptions.CType`.
+    CType {
+        String = 0 => "STRING",
+        Cord = 1 => "CORD",
+        StringPiece = 2 => "STRING_PIECE"
+    }
+);
+
+proto_enum!(
+    /// `FieldOptions.JSType`.
+    JSType {
+        JsNormal = 0 => "JS_NORMAL",
+        JsString = 1 => "JS_STRING",
+        JsNumber = 2 => "JS_NUMBER"
+    }
+);
+
+proto_enum!(
+    /// `FieldOptions.OptionRetention`.
+    OptionRetention {
+        RetentionUnknown = 0 => "RETENTION_UNKNOWN",
+        RetentionRuntime = 1 => "RETENTION_RUNTIME",
+        RetentionSource = 2 => "RETENTION_SOURCE"
+    }
+);
+
+proto_enum!(
+    /// `FieldOptions.OptionTargetType`.
+    OptionTargetType {
+        TargetTypeUnknown = 0 => "TARGET_TYPE_UNKNOWN",
+        TargetTypeFile = 1 => "TARGET_TYPE_FILE",
+        TargetTypeExtensionRange = 2 => "TARGET_TYPE_EXTENSION_RANGE",
+        TargetTypeMessage = 3 => "TARGET_TYPE_MESSAGE",
+        TargetTypeField = 4 => "TARGET_TYPE_FIELD",
+        TargetTypeOneof = 5 => "TARGET_TYPE_ONEOF",
+        TargetTypeEnum = 6 => "TARGET_TYPE_ENUM",
+        TargetTypeEnumEntry = 7 => "TARGET_TYPE_ENUM_ENTRY",
+        TargetTypeService = 8 => "TARGET_TYPE_SERVICE",
+        TargetTypeMethod = 9 => "TARGET_TYPE_METHOD"
+    }
+);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OneofOptions {
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnumOptions {
+    pub allow_alias: Option<bool>,
+    pub deprecated: Option<bool>,
+    pub deprecated_legacy_json_field_conflicts: Option<bool>,
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnumValueOptions {
+    pub deprecated: Option<bool>,
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServiceOptions {
+    pub deprecated: Option<bool>,
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MethodOptions {
+    pub deprecated: Option<bool>,
+    pub idempotency_level: Option<IdempotencyLevel>,
+    pub uninterpreted_option: Vec<UninterpretedOption>,
+    pub unknown_fields: Vec<u8>
+}
+
+proto_enum!(
+    /// `MethodOptions.IdempotencyLevel`.
+    IdempotencyLevel {
+        IdempotencyUnknown = 0 => "IDEMPOTENCY_UNKNOWN",
+        NoSideEffects = 1 => "NO_SIDE_EFFECTS",
+        Idempotent = 2 => "IDEMPOTENT"
+    }
+);
+
+/// An option as written in the source, before its name is resolved and
+/// its value checked. Exactly one of the value fields is set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UninterpretedOption {
+    pub name: Vec<NamePart>,
+    pub identifier_value: Option<String>,
+    pub positive_int_value: Option<u64>,
+    pub negative_int_value: Option<i64>,
+    pub double_value: Option<f64>,
+    pub string_value: Option<Vec<u8>>,
+    /// The text of a message literal, without its braces.
+    pub aggregate_value: Option<String>
+}
+
+/// `UninterpretedOption.NamePart`. `foo.(bar.baz).qux` has the parts
+/// `foo`, `bar.baz` and `qux`, and only `bar.baz` is an extension.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamePart {
+    pub name_part: String,
+    pub is_extension: bool
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceCodeInfo {
+    pub location: Vec<Location>
+}
+
+/// `SourceCodeInfo.Location`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    pub path: Vec<i32>,
+    pub span: Vec<i32>,
+    pub leading_comments: Option<String>,
+    pub trailing_comments: Option<String>,
+    pub leading_detached_comments: Vec<String>
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeneratedCodeInfo {
+    pub annotation: Vec<Annotation>
+}
+
+/// `GeneratedCodeInfo.Annotation`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotation {
+    pub path: Vec<i32>,
+    pub source_file: Option<String>,
+    pub begin: Option<i32>,
+    pub end: Option<i32>,
+    pub semantic: Option<Semantic>
+}
+
+proto_enum!(
+    /// `GeneratedCodeInfo.Annotation.Semantic`.
+    Semantic {
+        None = 0 => "NONE",
+        Set = 1 => "SET",
+        Alias = 2 => "ALIAS"
+    }
+);
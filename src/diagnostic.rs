@@ -0,0 +1,420 @@
+//! Renders errors for people to read, with the lines of source they point
+//! at and carets under the offending text:
+//!
+//! ```text
+//! error: duplicate symbol "Foo"
+//!  --> foo.proto:12:9
+//!    |
+//!  4 | message Foo {}
+//!    |         --- previously defined here
+//! ...
+//! 12 | message Foo {
+//!    |         ^^^
+//!    |
+//!    = note: symbols must be unique within a package
+//! ```
+
+use std::fmt::Write;
+use crate::error::Error;
+use crate::source_pos::PosRange;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => return "error",
+            Severity::Warning => return "warning",
+            Severity::Note => return "note"
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => return RED,
+            Severity::Warning => return YELLOW,
+            Severity::Note => return GREEN
+        }
+    }
+}
+
+/// A span of source with a message attached to it.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pos_range: PosRange,
+    message: String
+}
+
+impl Label {
+    pub fn new(pos_range: PosRange, message: &str) -> Self {
+        return Label {
+            pos_range,
+            message: message.to_string()
+        }
+    }
+
+    pub fn pos_range(&self) -> &PosRange {
+        return &self.pos_range
+    }
+
+    pub fn message(&self) -> &str {
+        return &self.message
+    }
+}
+
+/// A message about a place in the source. The primary span is what the
+/// message is about; secondary labels point at related places, such as an
+/// earlier definition of the same name.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    severity: Severity,
+    message: String,
+    primary: Option<Label>,
+    secondary: Vec<Label>,
+    notes: Vec<String>
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: &str) -> Self {
+        return Diagnostic {
+            severity,
+            message: message.to_string(),
+            primary: None,
+            secondary: vec![],
+            notes: vec![]
+        }
+    }
+
+    pub fn error(message: &str) -> Self {
+        return Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: &str) -> Self {
+        return Diagnostic::new(Severity::Warning, message)
+    }
+
+    /// Sets the span the diagnostic is about. `label` is printed next to
+    /// its carets and may be empty.
+    pub fn with_primary(mut self, pos_range: PosRange, label: &str) -> Self {
+        self.primary = Some(Label::new(pos_range, label));
+        return self
+    }
+
+    pub fn with_label(mut self, pos_range: PosRange, message: &str) -> Self {
+        self.secondary.push(Label::new(pos_range, message));
+        return self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        return self
+    }
+
+    pub fn severity(&self) -> Severity {
+        return self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        return &self.message
+    }
+
+    pub fn primary(&self) -> Option<&Label> {
+        return self.primary.as_ref()
+    }
+
+    pub fn secondary(&self) -> &[Label] {
+        return &self.secondary
+    }
+
+    pub fn notes(&self) -> &[String] {
+        return &self.notes
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(e: &Error) -> Self {
+        let mut diag = Diagnostic::error(&e.message());
+        match e {
+            Error::Duplicate { previous, .. } => diag = diag.with_label(previous.as_ref().clone(), "previously defined here"),
+            Error::Ambiguous { .. } => {
+                diag = diag.with_note("the innermost scope is searched first; use a fully qualified name starting with \".\" to refer to a name in an outer scope");
+            },
+            _ => {}
+        }
+        match e.pos_range() {
+            Some(r) => return diag.with_primary(r.clone(), ""),
+            None => return diag
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Turns diagnostics into text, either plain or with ANSI colors for a
+/// terminal.
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    color: bool
+}
+
+// One underlined piece of a line. Spans over several lines are split into
+// a piece on their first line and one on their last.
+struct Mark<'a> {
+    line: i32,
+    line_start: usize,
+    from: usize,
+    to: usize,
+    primary: bool,
+    label: &'a str
+}
+
+impl Renderer {
+    pub fn plain() -> Self {
+        return Renderer { color: false }
+    }
+
+    pub fn colored() -> Self {
+        return Renderer { color: true }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if !self.color || text.is_empty() {
+            return text.to_string()
+        }
+        return format!("{}{}{}", style, text, RESET)
+    }
+
+    /// Renders `diag`. `source` is the text of the file the primary span
+    /// is in; labels in other files are listed by position only.
+    pub fn render(&self, diag: &Diagnostic, source: &str) -> String {
+        // Writing to a String cannot fail.
+        let mut out = String::new();
+        let sev = diag.severity();
+        writeln!(out, "{}{}", self.paint(sev.color(), sev.name()), self.paint(BOLD, &format!(": {}", diag.message()))).unwrap();
+
+        let filename = diag.primary().map(|p| p.pos_range().start.filename().to_string());
+        let mut marks = vec![];
+        let mut elsewhere = vec![];
+        if let Some(p) = diag.primary() {
+            push_marks(&mut marks, source, p, true);
+        }
+        for l in diag.secondary().iter() {
+            if Some(l.pos_range().start.filename()) == filename.as_deref() {
+                push_marks(&mut marks, source, l, false);
+            } else {
+                elsewhere.push(l);
+            }
+        }
+        marks.sort_by_key(|m| (m.line, !m.primary, m.from));
+
+        let width = marks.iter().map(|m| m.line.to_string().len()).max().unwrap_or(1);
+        let gutter = self.paint(BLUE, &format!("{} |", " ".repeat(width)));
+        if let Some(p) = diag.primary() {
+            writeln!(out, "{}{} {}", " ".repeat(width), self.paint(BLUE, "-->"), p.pos_range().start).unwrap();
+        }
+        if !marks.is_empty() {
+            writeln!(out, "{}", gutter).unwrap();
+        }
+        let mut prev_line = None;
+        for m in marks.iter() {
+            if prev_line != Some(m.line) {
+                if prev_line.is_some_and(|l| m.line > l + 1) {
+                    writeln!(out, "{}", self.paint(BLUE, "...")).unwrap();
+                }
+                let text = line_text(source, m.line_start);
+                writeln!(out, "{} {}", self.paint(BLUE, &format!("{:>w$} |", m.line, w = width)), expand_tabs(text)).unwrap();
+                prev_line = Some(m.line);
+            }
+            let text = line_text(source, m.line_start);
+            let pad = display_width(&text[..m.from - m.line_start]);
+            let len = std::cmp::max(1, display_width(&text[m.from - m.line_start..m.to - m.line_start]));
+            let (ch, style) = if m.primary { ("^", sev.color()) } else { ("-", BLUE) };
+            let mut underline = ch.repeat(len);
+            if !m.label.is_empty() {
+                underline.push(' ');
+                underline.push_str(m.label);
+            }
+            writeln!(out, "{} {}{}", gutter, " ".repeat(pad), self.paint(style, &underline)).unwrap();
+        }
+        for l in elsewhere.iter() {
+            writeln!(out, "{}{} {}: {}", " ".repeat(width), self.paint(BLUE, " ="), l.pos_range().start, l.message()).unwrap();
+        }
+        if !diag.notes().is_empty() {
+            if !marks.is_empty() {
+                writeln!(out, "{}", gutter).unwrap();
+            }
+            for n in diag.notes().iter() {
+                writeln!(out, "{}{} {}: {}", " ".repeat(width), self.paint(BLUE, " ="), self.paint(BOLD, "note"), n).unwrap();
+            }
+        }
+        return out
+    }
+
+    /// Renders every error in `errors` against the same source, with a
+    /// blank line between them.
+    pub fn render_errors(&self, errors: &[Error], source: &str) -> String {
+        let rendered: Vec<String> = errors.iter().map(|e| self.render(&Diagnostic::from(e), source)).collect();
+        return rendered.join("\n")
+    }
+}
+
+// Adds the marks for `label`. Positions past the end of `source`, which
+// can happen when it is not the text the label was made from, are clamped.
+fn push_marks<'a>(marks: &mut Vec<Mark<'a>>, source: &str, label: &'a Label, primary: bool) {
+    let r = label.pos_range();
+    let from = clamp(source, r.start.offset());
+    let mut to = std::cmp::max(from, clamp(source, r.end.offset()));
+    // A span that ends with a line break is shown as ending on the line
+    // before it.
+    while to > from && source[..to].ends_with(['\n', '\r']) {
+        to -= 1;
+    }
+    let start_line_start = source[..from].rfind('\n').map_or(0, |i| i + 1);
+    let start_line_end = start_line_start + line_text(source, start_line_start).len();
+    if to <= start_line_end {
+        marks.push(Mark { line: line_of(source, from), line_start: start_line_start, from, to, primary, label: label.message() });
+        return
+    }
+    marks.push(Mark { line: line_of(source, from), line_start: start_line_start, from, to: start_line_end, primary, label: "" });
+    let end_line_start = source[..to].rfind('\n').map_or(0, |i| i + 1);
+    let text = line_text(source, end_line_start);
+    let indent = text.len() - text.trim_start().len();
+    let first = std::cmp::min(end_line_start + indent, to);
+    marks.push(Mark { line: line_of(source, to), line_start: end_line_start, from: first, to, primary, label: label.message() });
+}
+
+fn line_of(source: &str, offset: usize) -> i32 {
+    return source[..offset].matches('\n').count() as i32 + 1
+}
+
+fn clamp(source: &str, offset: i32) -> usize {
+    let mut i = std::cmp::min(std::cmp::max(offset, 0) as usize, source.len());
+    while !source.is_char_boundary(i) {
+        i -= 1;
+    }
+    return i
+}
+
+// Returns the line that starts at `line_start`, without its line ending.
+fn line_text(source: &str, line_start: usize) -> &str {
+    let rest = &source[line_start..];
+    let line = match rest.find('\n') {
+        Some(i) => &rest[..i],
+        None => rest
+    };
+    return line.strip_suffix('\r').unwrap_or(line)
+}
+
+const TAB_WIDTH: usize = 4;
+
+fn expand_tabs(text: &str) -> String {
+    return text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+fn display_width(text: &str) -> usize {
+    return text.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parser::parse;
+    use crate::linker::Linker;
+
+    // Returns the range of the `n`th token of `source`.
+    fn token(source: &str, filename: &str, n: usize) -> PosRange {
+        return lex(filename, source).unwrap()[n].info().pos_range().clone()
+    }
+
+    #[test]
+    fn renders_a_parse_error() {
+        let source = "syntax = \"proto3\";\nmessage M {\n  int32 x = ;\n}\n";
+        let errors = parse("a.proto", source).err().unwrap();
+        let rendered = Renderer::plain().render_errors(errors.errors(), source);
+        assert_eq!(rendered, concat!(
+            "error: syntax error: unexpected ';', expecting int literal\n",
+            " --> a.proto:3:13\n",
+            "  |\n",
+            "3 |   int32 x = ;\n",
+            "  |             ^\n"
+        ));
+    }
+
+    #[test]
+    fn renders_labels_and_notes() {
+        let source = "message Foo {}\n\n\nmessage Foo {\n\tint32 x = 1;\n}\n";
+        let diag = Diagnostic::error("duplicate symbol \"Foo\"")
+            .with_primary(token(source, "a.proto", 5), "")
+            .with_label(token(source, "a.proto", 1), "previously defined here")
+            .with_label(token("message Foo {}", "b.proto", 1), "also defined here")
+            .with_note("symbols must be unique within a package");
+        assert_eq!(Renderer::plain().render(&diag, source), concat!(
+            "error: duplicate symbol \"Foo\"\n",
+            " --> a.proto:4:9\n",
+            "  |\n",
+            "1 | message Foo {}\n",
+            "  |         --- previously defined here\n",
+            "...\n",
+            "4 | message Foo {\n",
+            "  |         ^^^\n",
+            "  = b.proto:1:9: also defined here\n",
+            "  |\n",
+            "  = note: symbols must be unique within a package\n"
+        ));
+    }
+
+    #[test]
+    fn renders_a_span_over_several_lines() {
+        let source = "message M {\n  int32 x = 1;\n}\n";
+        let mut range = token(source, "a.proto", 0);
+        range.end = token(source, "a.proto", 8).end;
+        let diag = Diagnostic::warning("unused message").with_primary(range, "declared here");
+        assert_eq!(Renderer::plain().render(&diag, source), concat!(
+            "warning: unused message\n",
+            " --> a.proto:1:1\n",
+            "  |\n",
+            "1 | message M {\n",
+            "  | ^^^^^^^^^^^\n",
+            "...\n",
+            "3 | }\n",
+            "  | ^ declared here\n"
+        ));
+    }
+
+    #[test]
+    fn colors_are_optional() {
+        let diag = Diagnostic::error("oops");
+        assert_eq!(Renderer::plain().render(&diag, ""), "error: oops\n");
+        assert_eq!(Renderer::colored().render(&diag, ""), "\x1b[1;31merror\x1b[0m\x1b[1m: oops\x1b[0m\n");
+    }
+
+    fn linker_diagnostics(source: &str) -> Vec<Diagnostic> {
+        let file = parse("a.proto", source).unwrap();
+        return Linker::new().link(&file).err().unwrap().iter().map(Diagnostic::from).collect()
+    }
+
+    #[test]
+    fn linker_errors_carry_context() {
+        let diags = linker_diagnostics("package foo.bar;\nmessage Outer {}\nmessage Outer {}\nmessage M { optional bar.Outer.Nope x = 1; }\n");
+        assert_eq!(diags[0].secondary()[0].message(), "previously defined here");
+        assert_eq!(diags[0].secondary()[0].pos_range().start.line(), 2);
+        assert_eq!(diags[1].notes().len(), 1);
+    }
+
+    #[test]
+    fn ambiguity_note_points_to_qualified_names() {
+        let diags = linker_diagnostics("package foo.bar;\nmessage Outer {}\nmessage M { optional bar.Outer.Nope x = 1; }\n");
+        assert_eq!(diags[0].notes(), ["the innermost scope is searched first; use a fully qualified name starting with \".\" to refer to a name in an outer scope"]);
+    }
+}
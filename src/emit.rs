@@ -0,0 +1,175 @@
+//! Machine-readable forms of diagnostics, for CI systems and other tools:
+//! newline-delimited JSON, SARIF 2.1.0, and the `file:line:col: message`
+//! lines printed by protoc.
+
+use crate::diagnostic::{Diagnostic, Label, Severity};
+use crate::source_pos::{PosRange, SourcePos};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    return out
+}
+
+fn json_array(items: Vec<String>) -> String {
+    return format!("[{}]", items.join(","))
+}
+
+fn json_pos(p: &SourcePos) -> String {
+    return format!("{{\"line\":{},\"column\":{},\"offset\":{}}}", p.line(), p.col(), p.offset())
+}
+
+fn json_span(r: &PosRange) -> String {
+    return format!("\"file\":{},\"start\":{},\"end\":{}", json_string(r.start.filename()), json_pos(&r.start), json_pos(&r.end))
+}
+
+/// Returns one JSON object per line for each diagnostic, like
+///
+/// ```text
+/// {"severity":"error","message":"...","file":"a.proto","start":{"line":3,"column":5,"offset":40},"end":{...},"labels":[],"notes":[]}
+/// ```
+///
+/// `file`, `start` and `end` are null when the diagnostic has no position.
+/// Each label has a `message` along with its own `file`, `start` and `end`.
+pub fn to_ndjson(diags: &[Diagnostic]) -> String {
+    let mut out = String::new();
+    for d in diags.iter() {
+        let span = match d.primary() {
+            Some(p) => json_span(p.pos_range()),
+            None => "\"file\":null,\"start\":null,\"end\":null".to_string()
+        };
+        let labels = d.secondary().iter().map(|l| format!("{{\"message\":{},{}}}", json_string(l.message()), json_span(l.pos_range()))).collect();
+        let notes = d.notes().iter().map(|n| json_string(n)).collect();
+        out.push_str(&format!("{{\"severity\":\"{}\",\"message\":{},{},\"labels\":{},\"notes\":{}}}\n",
+            d.severity().name(), json_string(d.message()), span, json_array(labels), json_array(notes)));
+    }
+    return out
+}
+
+fn sarif_location(l: &Label) -> String {
+    let r = l.pos_range();
+    let region = format!("{{\"startLine\":{},\"startColumn\":{},\"endLine\":{},\"endColumn\":{},\"byteOffset\":{},\"byteLength\":{}}}",
+        r.start.line(), r.start.col(), r.end.line(), r.end.col(), r.start.offset(), std::cmp::max(0, r.end.offset() - r.start.offset()));
+    return format!("\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{}}},\"region\":{}}}", json_string(r.start.filename()), region)
+}
+
+/// Returns a SARIF 2.1.0 log with a single run holding one result per
+/// diagnostic. Secondary labels become related locations, and notes are
+/// added to the end of the message text.
+pub fn to_sarif(diags: &[Diagnostic]) -> String {
+    let mut results = vec![];
+    for d in diags.iter() {
+        let mut text = d.message().to_string();
+        for n in d.notes().iter() {
+            text.push_str("\nnote: ");
+            text.push_str(n);
+        }
+        let mut result = format!("{{\"level\":\"{}\",\"message\":{{\"text\":{}}}", d.severity().name(), json_string(&text));
+        if let Some(p) = d.primary() {
+            result.push_str(&format!(",\"locations\":[{{{}}}]", sarif_location(p)));
+        }
+        if !d.secondary().is_empty() {
+            let related = d.secondary().iter().enumerate().map(|(i, l)| {
+                format!("{{\"id\":{},{},\"message\":{{\"text\":{}}}}}", i, sarif_location(l), json_string(l.message()))
+            }).collect();
+            result.push_str(&format!(",\"relatedLocations\":{}", json_array(related)));
+        }
+        result.push('}');
+        results.push(result);
+    }
+    let driver = format!("{{\"name\":\"protoreflect\",\"version\":\"{}\"}}", env!("CARGO_PKG_VERSION"));
+    return format!("{{\"$schema\":\"{}\",\"version\":\"2.1.0\",\"runs\":[{{\"tool\":{{\"driver\":{}}},\"results\":{}}}]}}\n", SARIF_SCHEMA, driver, json_array(results))
+}
+
+/// Returns the diagnostics as protoc prints them, one per line:
+/// `file:line:col: message` for errors and `file:line:col: warning: message`
+/// for warnings. Labels and notes have no place in this format and are
+/// left out.
+pub fn to_protoc(diags: &[Diagnostic]) -> String {
+    let mut out = String::new();
+    for d in diags.iter() {
+        if let Some(p) = d.primary() {
+            let start = &p.pos_range().start;
+            if start.line() > 0 {
+                out.push_str(&format!("{}:{}:{}: ", start.filename(), start.line(), start.col()));
+            } else {
+                out.push_str(&format!("{}: ", start.filename()));
+            }
+        }
+        if d.severity() != Severity::Error {
+            out.push_str(d.severity().name());
+            out.push_str(": ");
+        }
+        out.push_str(d.message());
+        out.push('\n');
+    }
+    return out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        let source = "message Foo {}\nmessage Foo {}\n";
+        let tokens = lex("a.proto", source).unwrap();
+        let range = |i: usize| tokens[i].info().pos_range().clone();
+        return vec![
+            Diagnostic::error("duplicate symbol \"Foo\"")
+                .with_primary(range(5), "")
+                .with_label(range(1), "previously defined here")
+                .with_note("symbols must be unique"),
+            Diagnostic::warning("tab\there"),
+            Diagnostic::error("no position")
+        ]
+    }
+
+    #[test]
+    fn ndjson() {
+        assert_eq!(to_ndjson(&diagnostics()), concat!(
+            "{\"severity\":\"error\",\"message\":\"duplicate symbol \\\"Foo\\\"\",",
+            "\"file\":\"a.proto\",\"start\":{\"line\":2,\"column\":9,\"offset\":23},\"end\":{\"line\":2,\"column\":12,\"offset\":26},",
+            "\"labels\":[{\"message\":\"previously defined here\",\"file\":\"a.proto\",",
+            "\"start\":{\"line\":1,\"column\":9,\"offset\":8},\"end\":{\"line\":1,\"column\":12,\"offset\":11}}],",
+            "\"notes\":[\"symbols must be unique\"]}\n",
+            "{\"severity\":\"warning\",\"message\":\"tab\\there\",\"file\":null,\"start\":null,\"end\":null,\"labels\":[],\"notes\":[]}\n",
+            "{\"severity\":\"error\",\"message\":\"no position\",\"file\":null,\"start\":null,\"end\":null,\"labels\":[],\"notes\":[]}\n"
+        ));
+    }
+
+    #[test]
+    fn sarif() {
+        assert_eq!(to_sarif(&diagnostics()), concat!(
+            "{\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\"version\":\"2.1.0\",",
+            "\"runs\":[{\"tool\":{\"driver\":{\"name\":\"protoreflect\",\"version\":\"", env!("CARGO_PKG_VERSION"), "\"}},\"results\":[",
+            "{\"level\":\"error\",\"message\":{\"text\":\"duplicate symbol \\\"Foo\\\"\\nnote: symbols must be unique\"},",
+            "\"locations\":[{\"physicalLocation\":{\"artifactLocation\":{\"uri\":\"a.proto\"},",
+            "\"region\":{\"startLine\":2,\"startColumn\":9,\"endLine\":2,\"endColumn\":12,\"byteOffset\":23,\"byteLength\":3}}}],",
+            "\"relatedLocations\":[{\"id\":0,\"physicalLocation\":{\"artifactLocation\":{\"uri\":\"a.proto\"},",
+            "\"region\":{\"startLine\":1,\"startColumn\":9,\"endLine\":1,\"endColumn\":12,\"byteOffset\":8,\"byteLength\":3}},",
+            "\"message\":{\"text\":\"previously defined here\"}}]},",
+            "{\"level\":\"warning\",\"message\":{\"text\":\"tab\\there\"}},",
+            "{\"level\":\"error\",\"message\":{\"text\":\"no position\"}}]}]}\n"
+        ));
+    }
+
+    #[test]
+    fn protoc() {
+        assert_eq!(to_protoc(&diagnostics()), "a.proto:2:9: duplicate symbol \"Foo\"\nwarning: tab\there\nno position\n");
+    }
+}
@@ -1,27 +1,30 @@
-use crate::node::{CompositeNode, RuneNode, NodeTrait};
+use std::any::Any;
+use crate::node::{NodeKind, CompositeNode, RuneNode, EmptyDeclNode, ErrorNode, NodeTrait, TerminalNodeTrait, CompositeNodeTrait, node_enum};
 use crate::identifiers::{IdentNode, KeywordNode};
 use crate::values::{IntValueNodeTrait};
-use crate::options::{CompactOptionsNode};
+use crate::options::{CompactOptionsNode, OptionNode};
+use crate::ranges::ReservedNode;
+use crate::source_pos::{SourcePos, Comment};
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut};
 
+#[derive(Clone)]
 pub struct EnumNode {
     composite_node: CompositeNode,
     keyword: KeywordNode,
     name: IdentNode,
     open_brace: RuneNode,
-    decls: Vec<Box<dyn EnumElementTrait>>,
+    decls: Vec<EnumElement>,
     close_brace: RuneNode
 }
 
 impl EnumNode {
-    pub fn file_element() {}
-    pub fn msg_element() {}
-    pub fn new(keyword: KeywordNode, name: IdentNode, open_brace: RuneNode, decls: Vec<Box<dyn EnumElementTrait>>, close_brace: RuneNode) -> Self {
+    pub fn new(keyword: KeywordNode, name: IdentNode, open_brace: RuneNode, decls: Vec<EnumElement>, close_brace: RuneNode) -> Self {
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(4 + decls.len());
         children.push(Box::new(keyword.clone()));
         children.push(Box::new(name.clone()));
         children.push(Box::new(open_brace.clone()));
-        for (_, decl) in decls.iter().enumerate() {
-            children.push(decl.as_node_trait().clone());
+        for decl in decls.iter() {
+            children.push(decl.as_node_trait());
         }
         children.push(Box::new(close_brace.clone()));
 
@@ -37,47 +40,121 @@ impl EnumNode {
             decls
         }
     }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn open_brace(&self) -> &RuneNode {
+        return &self.open_brace
+    }
+
+    pub fn decls(&self) -> &[EnumElement] {
+        return &self.decls
+    }
+
+    pub fn close_brace(&self) -> &RuneNode {
+        return &self.close_brace
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.open_brace);
+        for decl in self.decls.iter_mut() {
+            walk_mut(v, decl.as_node_mut());
+        }
+        walk_mut(v, &mut self.close_brace);
+        *self = EnumNode::new(self.keyword.clone(), self.name.clone(), self.open_brace.clone(), self.decls.clone(), self.close_brace.clone());
+    }
 }
 
-pub trait _EnumElementTrait {
-    fn enum_element(&self);
+impl NodeTrait for EnumNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_enum(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_enum_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Enum
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
-pub trait EnumElementTrait: _EnumElementTrait + NodeTrait {
-    fn as_node_trait(&self) -> Box<dyn NodeTrait>;
-    fn as_enum_element_trait(&self) -> Box<dyn _EnumElementTrait>;
+/// A declaration that may appear in the body of an enum.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum EnumElement {
+    Option(OptionNode),
+    Value(EnumValueNode),
+    Reserved(ReservedNode),
+    Empty(EmptyDeclNode),
+    Error(ErrorNode)
 }
 
+node_enum!(EnumElement { Option, Value, Reserved, Empty, Error });
+
 pub trait EnumValueDeclNodeTrait {
     fn get_name(&self) -> Box<dyn NodeTrait>;
     fn get_number(&self) -> Box<dyn NodeTrait>;
 }
 
-pub struct EnumValueNode<T> {
+#[derive(Clone)]
+pub struct EnumValueNode {
     composite_node: CompositeNode,
     name: IdentNode,
     equals: RuneNode,
     number: Box<dyn IntValueNodeTrait>,
-    options: CompactOptionsNode<T>,
+    options: Option<CompactOptionsNode>,
     semicolon: RuneNode
 }
 
-impl<T> _EnumElementTrait for EnumValueNode<T> {
-    fn enum_element(&self) {} 
-}
-
-impl<T: Clone + 'static> EnumValueNode<T> {
-    pub fn new(name: IdentNode, equals: RuneNode, number: Box<dyn IntValueNodeTrait>, opts: CompactOptionsNode<T>, semicolon: RuneNode) -> Self {
+impl EnumValueNode {
+    pub fn new(name: IdentNode, equals: RuneNode, number: Box<dyn IntValueNodeTrait>, opts: Option<CompactOptionsNode>, semicolon: RuneNode) -> Self {
         let mut num_children = 4;
-        if opts.get_elements().len() > 0 {
+        if opts.is_some() {
             num_children += 1;
         }
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(num_children);
         children.push(Box::new(name.clone()));
         children.push(Box::new(equals.clone()));
         children.push(number.as_node_trait());
-        if opts.get_elements().len() > 0 {
-            children.push(Box::new(opts.clone()));
+        if let Some(opts) = opts.clone() {
+            children.push(Box::new(opts));
         }
         children.push(Box::new(semicolon.clone()));
         let mut comp = CompositeNode::new();
@@ -91,9 +168,80 @@ impl<T: Clone + 'static> EnumValueNode<T> {
             semicolon
         }
     }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn equals(&self) -> &RuneNode {
+        return &self.equals
+    }
+
+    pub fn number(&self) -> &dyn IntValueNodeTrait {
+        return self.number.as_ref()
+    }
+
+    pub fn options(&self) -> Option<&CompactOptionsNode> {
+        return self.options.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.equals);
+        walk_mut(v, self.number.as_mut());
+        walk_opt_mut(v, self.options.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        *self = EnumValueNode::new(self.name.clone(), self.equals.clone(), self.number.clone(), self.options.clone(), self.semicolon.clone());
+    }
+}
+
+impl NodeTrait for EnumValueNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_enum_value(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_enum_value_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::EnumValue
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
-impl <T> EnumValueDeclNodeTrait for EnumValueNode<T> {
+impl EnumValueDeclNodeTrait for EnumValueNode {
     fn get_name(&self) -> Box<dyn NodeTrait> {
         return Box::new(self.name.clone())
     }
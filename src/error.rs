@@ -0,0 +1,211 @@
+//! The errors reported while reading proto source and building nodes.
+
+use crate::node::{NodeKind, NodeTrait};
+use crate::source_pos::PosRange;
+
+/// An error in proto source, or in the parts given to a node constructor.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Source text that does not fit the grammar, including text that
+    /// cannot be split into tokens.
+    Syntax {
+        pos_range: PosRange,
+        message: String
+    },
+    /// A list whose separators do not line up with its elements.
+    MismatchedSeparators {
+        pos_range: PosRange,
+        expected: usize,
+        found: usize
+    },
+    /// A node that needs at least one component was given none. The range
+    /// is missing when the node has no other children to take it from.
+    EmptyCompound {
+        pos_range: Option<PosRange>,
+        kind: NodeKind
+    },
+    /// An integer literal too large for the value it stands for.
+    NumericOverflow {
+        pos_range: PosRange,
+        literal: String
+    },
+    /// A declaration that parses but breaks a rule of the language, such
+    /// as a default value on a repeated field.
+    Invalid {
+        pos_range: PosRange,
+        message: String
+    },
+    /// A name declared twice. `previous` is the first declaration, which
+    /// may be in another file.
+    Duplicate {
+        pos_range: PosRange,
+        name: String,
+        previous: Box<PosRange>
+    },
+    /// A name that matches no declaration visible from where it is used.
+    /// `hint` explains a near miss, such as a declaration in a file that
+    /// isn't imported.
+    Unresolved {
+        pos_range: PosRange,
+        name: String,
+        hint: Option<Box<str>>
+    },
+    /// A relative name whose first part matched a declaration in an inner
+    /// scope, so that it resolved to `resolved_to`, which doesn't exist,
+    /// rather than to a declaration further out.
+    Ambiguous {
+        pos_range: PosRange,
+        name: String,
+        resolved_to: Box<str>
+    },
+    /// A file that could not be read. The range is that of the import
+    /// naming the file, and is missing for the file compilation started
+    /// from.
+    File {
+        pos_range: Option<PosRange>,
+        name: String,
+        message: Box<str>
+    },
+    /// An import that leads back to the file it is in. `chain` lists the
+    /// files from the first to import itself again, ending with that file.
+    ImportCycle {
+        pos_range: PosRange,
+        chain: Vec<String>
+    },
+    /// Binary protobuf data that can't be decoded. `offset` is the
+    /// position in the data where decoding failed.
+    Decode {
+        offset: usize,
+        message: String
+    }
+}
+
+impl Error {
+    pub fn syntax(pos_range: PosRange, message: &str) -> Self {
+        return Error::Syntax {
+            pos_range,
+            message: message.to_string()
+        }
+    }
+
+    pub fn invalid(pos_range: PosRange, message: &str) -> Self {
+        return Error::Invalid {
+            pos_range,
+            message: message.to_string()
+        }
+    }
+
+    pub fn pos_range(&self) -> Option<&PosRange> {
+        match self {
+            Error::Syntax { pos_range, .. } => return Some(pos_range),
+            Error::MismatchedSeparators { pos_range, .. } => return Some(pos_range),
+            Error::EmptyCompound { pos_range, .. } => return pos_range.as_ref(),
+            Error::NumericOverflow { pos_range, .. } => return Some(pos_range),
+            Error::Invalid { pos_range, .. } => return Some(pos_range),
+            Error::Duplicate { pos_range, .. } => return Some(pos_range),
+            Error::Unresolved { pos_range, .. } => return Some(pos_range),
+            Error::Ambiguous { pos_range, .. } => return Some(pos_range),
+            Error::File { pos_range, .. } => return pos_range.as_ref(),
+            Error::ImportCycle { pos_range, .. } => return Some(pos_range),
+            Error::Decode { .. } => return None
+        }
+    }
+
+    /// Returns the description of the error, without its position.
+    pub fn message(&self) -> String {
+        match self {
+            Error::Syntax { message, .. } => return message.clone(),
+            Error::MismatchedSeparators { expected, found, .. } => return format!("expected {} separators, found {}", expected, found),
+            Error::EmptyCompound { kind, .. } => return format!("{:?} must have at least one component", kind),
+            Error::NumericOverflow { .. } => return "integer literal out of range".to_string(),
+            Error::Invalid { message, .. } => return message.clone(),
+            Error::Duplicate { pos_range, name, previous } => {
+                if previous.start.filename() != pos_range.start.filename() {
+                    return format!("\"{}\" is already defined in \"{}\"", name, previous.start.filename())
+                }
+                return format!("\"{}\" is already defined", name)
+            },
+            Error::Unresolved { name, hint: Some(hint), .. } => return format!("\"{}\" is not defined; {}", name, hint),
+            Error::Unresolved { name, hint: None, .. } => return format!("\"{}\" is not defined", name),
+            Error::Ambiguous { name, resolved_to, .. } => {
+                return format!("\"{}\" resolves to \"{}\", which is not defined", name, resolved_to)
+            },
+            Error::File { name, message, .. } => return format!("could not read \"{}\": {}", name, message),
+            Error::ImportCycle { chain, .. } => return format!("import cycle: {}", chain.join(" -> ")),
+            Error::Decode { offset, message } => return format!("invalid protobuf data at byte {}: {}", offset, message)
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pos_range() {
+            Some(r) => return write!(f, "{}: {}", r.start, self.message()),
+            None => return write!(f, "{}", self.message())
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Returns the range from the first to the last of `nodes`, which must not
+/// all be empty.
+fn span(nodes: &[&dyn NodeTrait]) -> PosRange {
+    let first = nodes.iter().min_by_key(|n| n.start().offset()).unwrap();
+    let last = nodes.iter().max_by_key(|n| n.end().offset()).unwrap();
+    return PosRange { start: first.start().clone(), end: last.end().clone() }
+}
+
+/// Checks that there is exactly one separator between each pair of
+/// `elements`.
+pub(crate) fn check_separators<T: NodeTrait, S: NodeTrait>(elements: &[T], seps: &[S]) -> Result<(), Error> {
+    let expected = elements.len().saturating_sub(1);
+    if seps.len() == expected {
+        return Ok(())
+    }
+    let mut nodes: Vec<&dyn NodeTrait> = vec![];
+    for e in elements.iter() {
+        nodes.push(e);
+    }
+    for s in seps.iter() {
+        nodes.push(s);
+    }
+    return Err(Error::MismatchedSeparators { pos_range: span(&nodes), expected, found: seps.len() })
+}
+
+/// Checks that `elements` is not empty. `around` are the other children of
+/// the node, used to locate the error.
+pub(crate) fn check_not_empty<T>(elements: &[T], kind: NodeKind, around: &[&dyn NodeTrait]) -> Result<(), Error> {
+    if !elements.is_empty() {
+        return Ok(())
+    }
+    let pos_range = if around.is_empty() { None } else { Some(span(around)) };
+    return Err(Error::EmptyCompound { pos_range, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identifiers::{CompoundIdentNode, IdentNode};
+    use crate::lexer::lex;
+    use crate::node::RuneNode;
+    use crate::values::{_IntValueNodeTrait, NegativeIntLiteralNode, UintLiteralNode};
+
+    #[test]
+    fn constructors_check_their_parts() {
+        let tokens = lex("a.proto", ". a b - 9223372036854775809").unwrap();
+        let info = |i: usize| tokens[i].info().clone();
+        let dot = RuneNode::new('.', info(0));
+        let err = CompoundIdentNode::new(Some(dot), vec![], vec![]).err().unwrap();
+        assert!(matches!(err, Error::EmptyCompound { kind: NodeKind::CompoundIdent, .. }));
+        assert_eq!(err.to_string(), "a.proto:1:1: CompoundIdent must have at least one component");
+        let idents = vec![IdentNode::new("a", info(1)), IdentNode::new("b", info(2))];
+        let err = CompoundIdentNode::new(None, idents, vec![]).err().unwrap();
+        assert_eq!(err.to_string(), "a.proto:1:3: expected 1 separators, found 0");
+        let minus = RuneNode::new('-', info(3));
+        let err = NegativeIntLiteralNode::new(minus, UintLiteralNode::new(9223372036854775809, info(4))).err().unwrap();
+        assert_eq!(err.to_string(), "a.proto:1:7: integer literal out of range");
+        let minus = RuneNode::new('-', info(3));
+        assert_eq!(NegativeIntLiteralNode::new(minus, UintLiteralNode::new(9223372036854775808, info(4))).unwrap().as_int64(), (i64::MIN, true));
+    }
+}
@@ -0,0 +1,550 @@
+use std::any::Any;
+use crate::node::{NodeKind, NodeTrait, CompositeNode, RuneNode, TerminalNodeTrait, CompositeNodeTrait};
+use crate::identifiers::{IdentNode, KeywordNode, IdentValueNodeTrait, _IdentValueNodeTrait};
+use crate::values::IntValueNodeTrait;
+use crate::options::CompactOptionsNode;
+use crate::message::{MessageBody, MessageDeclNodeTrait, MessageElement};
+use crate::source_pos::{SourcePos, Comment};
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut};
+
+/// A normal field declaration, such as `repeated string names = 3;`.
+#[derive(Clone)]
+pub struct FieldNode {
+    composite_node: CompositeNode,
+    label: Option<KeywordNode>,
+    fld_type: Box<dyn IdentValueNodeTrait>,
+    name: IdentNode,
+    equals: RuneNode,
+    tag: Box<dyn IntValueNodeTrait>,
+    options: Option<CompactOptionsNode>,
+    semicolon: RuneNode,
+    // The message being extended, for fields declared in an extend block.
+    extendee: Option<Box<dyn IdentValueNodeTrait>>
+}
+
+impl FieldNode {
+    pub fn new(label: Option<KeywordNode>, fld_type: Box<dyn IdentValueNodeTrait>, name: IdentNode, equals: RuneNode, tag: Box<dyn IntValueNodeTrait>, opts: Option<CompactOptionsNode>, semicolon: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(7);
+        if let Some(label) = label.clone() {
+            children.push(Box::new(label));
+        }
+        children.push(fld_type.as_node_trait());
+        children.push(Box::new(name.clone()));
+        children.push(Box::new(equals.clone()));
+        children.push(tag.as_node_trait());
+        if let Some(opts) = opts.clone() {
+            children.push(Box::new(opts));
+        }
+        children.push(Box::new(semicolon.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return FieldNode {
+            composite_node: comp,
+            label,
+            fld_type,
+            name,
+            equals,
+            tag,
+            options: opts,
+            semicolon,
+            extendee: None
+        }
+    }
+
+    pub fn label(&self) -> Option<&KeywordNode> {
+        return self.label.as_ref()
+    }
+
+    pub fn is_repeated(&self) -> bool {
+        return label_is(&self.label, "repeated")
+    }
+
+    pub fn is_required(&self) -> bool {
+        return label_is(&self.label, "required")
+    }
+
+    pub fn is_optional(&self) -> bool {
+        return label_is(&self.label, "optional")
+    }
+
+    pub fn field_type(&self) -> &dyn IdentValueNodeTrait {
+        return self.fld_type.as_ref()
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn equals(&self) -> &RuneNode {
+        return &self.equals
+    }
+
+    pub fn tag(&self) -> &dyn IntValueNodeTrait {
+        return self.tag.as_ref()
+    }
+
+    pub fn options(&self) -> Option<&CompactOptionsNode> {
+        return self.options.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub fn extendee(&self) -> Option<&dyn IdentValueNodeTrait> {
+        return self.extendee.as_deref()
+    }
+
+    pub(crate) fn set_extendee(&mut self, extendee: Box<dyn IdentValueNodeTrait>) {
+        self.extendee = Some(extendee);
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_opt_mut(v, self.label.as_mut());
+        walk_mut(v, self.fld_type.as_mut());
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.equals);
+        walk_mut(v, self.tag.as_mut());
+        walk_opt_mut(v, self.options.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        let extendee = self.extendee.take();
+        *self = FieldNode::new(self.label.clone(), self.fld_type.clone(), self.name.clone(), self.equals.clone(), self.tag.clone(), self.options.clone(), self.semicolon.clone());
+        self.extendee = extendee;
+    }
+}
+
+fn label_is(label: &Option<KeywordNode>, kw: &str) -> bool {
+    match label {
+        Some(l) => return l.as_identifier() == kw,
+        None => return false
+    }
+}
+
+impl NodeTrait for FieldNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_field(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_field_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Field
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A proto2 group, which declares a field and the nested message that is
+/// its type at the same time.
+#[derive(Clone)]
+pub struct GroupNode {
+    composite_node: CompositeNode,
+    label: Option<KeywordNode>,
+    keyword: KeywordNode,
+    name: IdentNode,
+    equals: RuneNode,
+    tag: Box<dyn IntValueNodeTrait>,
+    options: Option<CompactOptionsNode>,
+    message_body: MessageBody,
+    extendee: Option<Box<dyn IdentValueNodeTrait>>
+}
+
+impl GroupNode {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(label: Option<KeywordNode>, keyword: KeywordNode, name: IdentNode, equals: RuneNode, tag: Box<dyn IntValueNodeTrait>, opts: Option<CompactOptionsNode>, open_brace: RuneNode, decls: Vec<MessageElement>, close_brace: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(8 + decls.len());
+        if let Some(label) = label.clone() {
+            children.push(Box::new(label));
+        }
+        children.push(Box::new(keyword.clone()));
+        children.push(Box::new(name.clone()));
+        children.push(Box::new(equals.clone()));
+        children.push(tag.as_node_trait());
+        if let Some(opts) = opts.clone() {
+            children.push(Box::new(opts));
+        }
+        children.push(Box::new(open_brace.clone()));
+        for decl in decls.iter() {
+            children.push(decl.as_node_trait());
+        }
+        children.push(Box::new(close_brace.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        let mut msg_body = MessageBody::new();
+        msg_body.populate_body(open_brace, decls, close_brace);
+
+        return GroupNode {
+            composite_node: comp,
+            label,
+            keyword,
+            name,
+            equals,
+            tag,
+            options: opts,
+            message_body: msg_body,
+            extendee: None
+        }
+    }
+
+    pub fn label(&self) -> Option<&KeywordNode> {
+        return self.label.as_ref()
+    }
+
+    pub fn is_repeated(&self) -> bool {
+        return label_is(&self.label, "repeated")
+    }
+
+    pub fn is_required(&self) -> bool {
+        return label_is(&self.label, "required")
+    }
+
+    pub fn is_optional(&self) -> bool {
+        return label_is(&self.label, "optional")
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn equals(&self) -> &RuneNode {
+        return &self.equals
+    }
+
+    pub fn tag(&self) -> &dyn IntValueNodeTrait {
+        return self.tag.as_ref()
+    }
+
+    pub fn options(&self) -> Option<&CompactOptionsNode> {
+        return self.options.as_ref()
+    }
+
+    pub fn body(&self) -> &MessageBody {
+        return &self.message_body
+    }
+
+    pub fn extendee(&self) -> Option<&dyn IdentValueNodeTrait> {
+        return self.extendee.as_deref()
+    }
+
+    pub(crate) fn set_extendee(&mut self, extendee: Box<dyn IdentValueNodeTrait>) {
+        self.extendee = Some(extendee);
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_opt_mut(v, self.label.as_mut());
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.equals);
+        walk_mut(v, self.tag.as_mut());
+        walk_opt_mut(v, self.options.as_mut());
+        self.message_body.walk_children_mut(v);
+        let extendee = self.extendee.take();
+        let body = &self.message_body;
+        *self = GroupNode::new(self.label.clone(), self.keyword.clone(), self.name.clone(), self.equals.clone(), self.tag.clone(), self.options.clone(), body.open_brace().unwrap().clone(), body.decls().to_vec(), body.close_brace().unwrap().clone());
+        self.extendee = extendee;
+    }
+}
+
+impl MessageDeclNodeTrait for GroupNode {
+    fn message_name(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.name.clone())
+    }
+}
+
+impl NodeTrait for GroupNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_group(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_group_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Group
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// The `map<K, V>` type of a map field.
+#[derive(Clone)]
+pub struct MapTypeNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    open_angle: RuneNode,
+    key_type: IdentNode,
+    comma: RuneNode,
+    value_type: Box<dyn IdentValueNodeTrait>,
+    close_angle: RuneNode
+}
+
+impl MapTypeNode {
+    pub fn new(keyword: KeywordNode, open_angle: RuneNode, key_type: IdentNode, comma: RuneNode, value_type: Box<dyn IdentValueNodeTrait>, close_angle: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(keyword.clone()), Box::new(open_angle.clone()), Box::new(key_type.clone()), Box::new(comma.clone()), value_type.as_node_trait(), Box::new(close_angle.clone())];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return MapTypeNode {
+            composite_node: comp,
+            keyword,
+            open_angle,
+            key_type,
+            comma,
+            value_type,
+            close_angle
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn open_angle(&self) -> &RuneNode {
+        return &self.open_angle
+    }
+
+    pub fn key_type(&self) -> &IdentNode {
+        return &self.key_type
+    }
+
+    pub fn comma(&self) -> &RuneNode {
+        return &self.comma
+    }
+
+    pub fn value_type(&self) -> &dyn IdentValueNodeTrait {
+        return self.value_type.as_ref()
+    }
+
+    pub fn close_angle(&self) -> &RuneNode {
+        return &self.close_angle
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.open_angle);
+        walk_mut(v, &mut self.key_type);
+        walk_mut(v, &mut self.comma);
+        walk_mut(v, self.value_type.as_mut());
+        walk_mut(v, &mut self.close_angle);
+        *self = MapTypeNode::new(self.keyword.clone(), self.open_angle.clone(), self.key_type.clone(), self.comma.clone(), self.value_type.clone(), self.close_angle.clone());
+    }
+}
+
+impl NodeTrait for MapTypeNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_map_type(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_map_type_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::MapType
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A map field, such as `map<string, int32> counts = 4;`.
+#[derive(Clone)]
+pub struct MapFieldNode {
+    composite_node: CompositeNode,
+    map_type: MapTypeNode,
+    name: IdentNode,
+    equals: RuneNode,
+    tag: Box<dyn IntValueNodeTrait>,
+    options: Option<CompactOptionsNode>,
+    semicolon: RuneNode
+}
+
+impl MapFieldNode {
+    pub fn new(map_type: MapTypeNode, name: IdentNode, equals: RuneNode, tag: Box<dyn IntValueNodeTrait>, opts: Option<CompactOptionsNode>, semicolon: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(6);
+        children.push(Box::new(map_type.clone()));
+        children.push(Box::new(name.clone()));
+        children.push(Box::new(equals.clone()));
+        children.push(tag.as_node_trait());
+        if let Some(opts) = opts.clone() {
+            children.push(Box::new(opts));
+        }
+        children.push(Box::new(semicolon.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return MapFieldNode {
+            composite_node: comp,
+            map_type,
+            name,
+            equals,
+            tag,
+            options: opts,
+            semicolon
+        }
+    }
+
+    pub fn map_type(&self) -> &MapTypeNode {
+        return &self.map_type
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn equals(&self) -> &RuneNode {
+        return &self.equals
+    }
+
+    pub fn tag(&self) -> &dyn IntValueNodeTrait {
+        return self.tag.as_ref()
+    }
+
+    pub fn options(&self) -> Option<&CompactOptionsNode> {
+        return self.options.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.map_type);
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.equals);
+        walk_mut(v, self.tag.as_mut());
+        walk_opt_mut(v, self.options.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        *self = MapFieldNode::new(self.map_type.clone(), self.name.clone(), self.equals.clone(), self.tag.clone(), self.options.clone(), self.semicolon.clone());
+    }
+}
+
+impl NodeTrait for MapFieldNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_map_field(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_map_field_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::MapField
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
@@ -0,0 +1,542 @@
+use std::any::Any;
+use crate::node::{NodeKind, NodeTrait, CompositeNode, TerminalNode, RuneNode, EmptyDeclNode, ErrorNode, TerminalNodeTrait, CompositeNodeTrait, node_enum};
+use crate::identifiers::{KeywordNode, IdentValueNodeTrait};
+use crate::values::StringValueNodeTrait;
+use crate::source_pos::{SourcePos, Comment};
+use crate::options::OptionNode;
+use crate::message::{MessageNode, ExtendNode};
+use crate::enums::EnumNode;
+use crate::service::ServiceNode;
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut};
+
+/// The root of the AST for a single proto source file.
+#[derive(Clone)]
+pub struct FileNode {
+    composite_node: CompositeNode,
+    syntax: Option<SyntaxNode>,
+    edition: Option<EditionNode>,
+    decls: Vec<FileElement>,
+    // A synthetic terminal that carries the comments and whitespace
+    // found after the last declaration.
+    eof: TerminalNode
+}
+
+impl FileNode {
+    /// Returns a FileNode for a file that starts with an optional
+    /// `syntax` declaration.
+    pub fn new(syntax: Option<SyntaxNode>, decls: Vec<FileElement>, eof: TerminalNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(decls.len() + 2);
+        if let Some(syntax) = syntax.clone() {
+            children.push(Box::new(syntax));
+        }
+        for decl in decls.iter() {
+            children.push(decl.as_node_trait());
+        }
+        children.push(Box::new(eof.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return FileNode {
+            composite_node: comp,
+            syntax,
+            edition: None,
+            decls,
+            eof
+        }
+    }
+
+    /// Returns a FileNode for a file that starts with an `edition`
+    /// declaration.
+    pub fn new_with_edition(edition: EditionNode, decls: Vec<FileElement>, eof: TerminalNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(decls.len() + 2);
+        children.push(Box::new(edition.clone()));
+        for decl in decls.iter() {
+            children.push(decl.as_node_trait());
+        }
+        children.push(Box::new(eof.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return FileNode {
+            composite_node: comp,
+            syntax: None,
+            edition: Some(edition),
+            decls,
+            eof
+        }
+    }
+
+    pub fn syntax(&self) -> Option<&SyntaxNode> {
+        return self.syntax.as_ref()
+    }
+
+    pub fn edition(&self) -> Option<&EditionNode> {
+        return self.edition.as_ref()
+    }
+
+    pub fn decls(&self) -> &[FileElement] {
+        return &self.decls
+    }
+
+    pub fn eof(&self) -> &TerminalNode {
+        return &self.eof
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_opt_mut(v, self.syntax.as_mut());
+        walk_opt_mut(v, self.edition.as_mut());
+        for decl in self.decls.iter_mut() {
+            walk_mut(v, decl.as_node_mut());
+        }
+        walk_mut(v, &mut self.eof);
+        *self = match self.edition.clone() {
+            Some(edition) => FileNode::new_with_edition(edition, self.decls.clone(), self.eof.clone()),
+            None => FileNode::new(self.syntax.clone(), self.decls.clone(), self.eof.clone())
+        };
+    }
+}
+
+impl NodeTrait for FileNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_file(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_file_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::File
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A declaration that may appear at the top level of a file.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum FileElement {
+    Import(ImportNode),
+    Package(PackageNode),
+    Option(OptionNode),
+    Message(MessageNode),
+    Enum(EnumNode),
+    Extend(ExtendNode),
+    Service(ServiceNode),
+    Empty(EmptyDeclNode),
+    Error(ErrorNode)
+}
+
+node_enum!(FileElement { Import, Package, Option, Message, Enum, Extend, Service, Empty, Error });
+
+/// A `syntax = "proto3";` declaration.
+#[derive(Clone)]
+pub struct SyntaxNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    equals: RuneNode,
+    syntax: Box<dyn StringValueNodeTrait>,
+    semicolon: RuneNode
+}
+
+impl SyntaxNode {
+    pub fn new(keyword: KeywordNode, equals: RuneNode, syntax: Box<dyn StringValueNodeTrait>, semicolon: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(keyword.clone()), Box::new(equals.clone()), syntax.as_node_trait(), Box::new(semicolon.clone())];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return SyntaxNode {
+            composite_node: comp,
+            keyword,
+            equals,
+            syntax,
+            semicolon
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn equals(&self) -> &RuneNode {
+        return &self.equals
+    }
+
+    pub fn syntax(&self) -> &dyn StringValueNodeTrait {
+        return self.syntax.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.equals);
+        walk_mut(v, self.syntax.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        *self = SyntaxNode::new(self.keyword.clone(), self.equals.clone(), self.syntax.clone(), self.semicolon.clone());
+    }
+}
+
+impl NodeTrait for SyntaxNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_syntax(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_syntax_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Syntax
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// An `edition = "2023";` declaration.
+#[derive(Clone)]
+pub struct EditionNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    equals: RuneNode,
+    edition: Box<dyn StringValueNodeTrait>,
+    semicolon: RuneNode
+}
+
+impl EditionNode {
+    pub fn new(keyword: KeywordNode, equals: RuneNode, edition: Box<dyn StringValueNodeTrait>, semicolon: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(keyword.clone()), Box::new(equals.clone()), edition.as_node_trait(), Box::new(semicolon.clone())];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return EditionNode {
+            composite_node: comp,
+            keyword,
+            equals,
+            edition,
+            semicolon
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn equals(&self) -> &RuneNode {
+        return &self.equals
+    }
+
+    pub fn edition(&self) -> &dyn StringValueNodeTrait {
+        return self.edition.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.equals);
+        walk_mut(v, self.edition.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        *self = EditionNode::new(self.keyword.clone(), self.equals.clone(), self.edition.clone(), self.semicolon.clone());
+    }
+}
+
+impl NodeTrait for EditionNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_edition(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_edition_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Edition
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A `package foo.bar;` declaration.
+#[derive(Clone)]
+pub struct PackageNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    name: Box<dyn IdentValueNodeTrait>,
+    semicolon: RuneNode
+}
+
+impl PackageNode {
+    pub fn new(keyword: KeywordNode, name: Box<dyn IdentValueNodeTrait>, semicolon: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(keyword.clone()), name.as_node_trait(), Box::new(semicolon.clone())];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return PackageNode {
+            composite_node: comp,
+            keyword,
+            name,
+            semicolon
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn name(&self) -> &dyn IdentValueNodeTrait {
+        return self.name.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, self.name.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        *self = PackageNode::new(self.keyword.clone(), self.name.clone(), self.semicolon.clone());
+    }
+}
+
+impl NodeTrait for PackageNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_package(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_package_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Package
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// An `import "foo.proto";` declaration, possibly marked `public` or
+/// `weak`.
+#[derive(Clone)]
+pub struct ImportNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    public: Option<KeywordNode>,
+    weak: Option<KeywordNode>,
+    name: Box<dyn StringValueNodeTrait>,
+    semicolon: RuneNode
+}
+
+impl ImportNode {
+    /// Returns an ImportNode. At most one of `public` and `weak` may be
+    /// given.
+    pub fn new(keyword: KeywordNode, public: Option<KeywordNode>, weak: Option<KeywordNode>, name: Box<dyn StringValueNodeTrait>, semicolon: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(4);
+        children.push(Box::new(keyword.clone()));
+        if let Some(public) = public.clone() {
+            children.push(Box::new(public));
+        }
+        if let Some(weak) = weak.clone() {
+            children.push(Box::new(weak));
+        }
+        children.push(name.as_node_trait());
+        children.push(Box::new(semicolon.clone()));
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return ImportNode {
+            composite_node: comp,
+            keyword,
+            public,
+            weak,
+            name,
+            semicolon
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn public(&self) -> Option<&KeywordNode> {
+        return self.public.as_ref()
+    }
+
+    pub fn weak(&self) -> Option<&KeywordNode> {
+        return self.weak.as_ref()
+    }
+
+    pub fn is_public(&self) -> bool {
+        return self.public.is_some()
+    }
+
+    pub fn is_weak(&self) -> bool {
+        return self.weak.is_some()
+    }
+
+    pub fn name(&self) -> &dyn StringValueNodeTrait {
+        return self.name.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_opt_mut(v, self.public.as_mut());
+        walk_opt_mut(v, self.weak.as_mut());
+        walk_mut(v, self.name.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        *self = ImportNode::new(self.keyword.clone(), self.public.clone(), self.weak.clone(), self.name.clone(), self.semicolon.clone());
+    }
+}
+
+impl NodeTrait for ImportNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_import(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_import_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Import
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
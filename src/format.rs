@@ -0,0 +1,768 @@
+//! A canonical formatter for proto source files.
+//!
+//! The formatter works on the tokens of a parsed file, in order, and
+//! re-emits them with normalized indentation and spacing. Comments stay
+//! attached to the token they were attributed to by the lexer, so leading,
+//! trailing and detached comments keep describing the same declaration.
+//! Blank lines between declarations are kept, but runs of them are
+//! collapsed into one.
+
+use crate::node::{NodeTrait, TerminalNodeTrait};
+use crate::file::FileNode;
+use crate::source_pos::Comment;
+
+/// Style settings for [`format`].
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Number of spaces per indentation level.
+    pub indent_width: usize,
+    /// Lines longer than this have their compact options wrapped one per
+    /// line.
+    pub max_line_length: usize,
+    /// Always put each compact option on its own line when a declaration
+    /// has more than one.
+    pub wrap_compact_options: bool,
+    /// Line up the `=` of consecutive fields and enum values.
+    pub align_field_numbers: bool,
+    /// Sort each run of consecutive `import` statements by path.
+    pub sort_imports: bool
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        return FormatOptions {
+            indent_width: 2,
+            max_line_length: 100,
+            wrap_compact_options: false,
+            align_field_numbers: false,
+            sort_imports: true
+        }
+    }
+}
+
+/// Returns the canonical source text for `file`.
+pub fn format(file: &FileNode, opts: &FormatOptions) -> String {
+    let mut toks = vec![];
+    collect_tokens(file, &mut toks);
+    // The last token is always the end of file, which only carries the
+    // comments found after the last declaration.
+    toks.pop();
+    let eof = Tok::new(file.eof());
+
+    let mut pos = 0;
+    let mut stmts = parse_stmts(&toks, &mut pos);
+    if opts.sort_imports {
+        sort_imports(&mut stmts);
+    }
+
+    let mut f = Formatter::new(opts);
+    for (i, stmt) in stmts.iter().enumerate() {
+        f.stmt(stmt, 0, i == 0);
+    }
+    f.close_comments(&eof, 0, stmts.is_empty());
+    return f.finish()
+}
+
+#[derive(Clone)]
+struct Tok {
+    raw: String,
+    leading_whitespace: String,
+    leading: Vec<Comment>,
+    trailing: Vec<Comment>
+}
+
+impl Tok {
+    fn new(terminal: &dyn TerminalNodeTrait) -> Self {
+        return Tok {
+            raw: terminal.raw_text().to_string(),
+            leading_whitespace: terminal.leading_whitespace().to_string(),
+            leading: terminal.leading_comments(),
+            trailing: terminal.trailing_comments()
+        }
+    }
+
+    fn is(&self, s: &str) -> bool {
+        return self.raw == s
+    }
+
+    fn is_string(&self) -> bool {
+        return self.raw.starts_with('"') || self.raw.starts_with('\'')
+    }
+}
+
+fn collect_tokens(node: &dyn NodeTrait, toks: &mut Vec<Tok>) {
+    if let Some(terminal) = node.as_terminal() {
+        toks.push(Tok::new(terminal));
+        return
+    }
+    if let Some(composite) = node.as_composite() {
+        for child in composite.children().iter() {
+            collect_tokens(child.as_ref(), toks);
+        }
+    }
+}
+
+fn is_blank_gap(whitespace: &str) -> bool {
+    return whitespace.matches('\n').count() >= 2
+}
+
+fn is_line_comment(c: &Comment) -> bool {
+    return c.text().starts_with("//")
+}
+
+/// A declaration: the tokens up to and including its `;` or the `{` that
+/// opens its body, then the body and closing brace if it has one. In a
+/// partial tree the file can end before the `;` or the closing brace, so
+/// `complete` is false and the brace is missing.
+struct Stmt {
+    blank_before: bool,
+    head: Vec<Tok>,
+    complete: bool,
+    body: Option<(Vec<Stmt>, Option<Tok>)>
+}
+
+fn parse_stmts(toks: &[Tok], pos: &mut usize) -> Vec<Stmt> {
+    let mut stmts = vec![];
+    while *pos < toks.len() && !toks[*pos].is("}") {
+        stmts.push(parse_stmt(toks, pos));
+    }
+    return stmts
+}
+
+fn parse_stmt(toks: &[Tok], pos: &mut usize) -> Stmt {
+    let first = &toks[*pos];
+    let gap = match first.leading.first() {
+        Some(c) => c.leading_whitespace(),
+        None => &first.leading_whitespace
+    };
+    let blank_before = is_blank_gap(gap);
+
+    let mut head: Vec<Tok> = vec![];
+    // Nesting of parentheses, brackets and message literals. Statements
+    // only end at depth zero.
+    let mut depth = 0;
+    while *pos < toks.len() {
+        let tok = toks[*pos].clone();
+        *pos += 1;
+        let prev = head.last().map(|t| t.raw.as_str());
+        match tok.raw.as_str() {
+            "(" | "[" => depth += 1,
+            ")" | "]" => depth -= 1,
+            "<" if prev != Some("map") => depth += 1,
+            ">" if depth > 0 => depth -= 1,
+            "{" if depth > 0 || prev == Some("=") => depth += 1,
+            "}" => depth -= 1,
+            "{" => {
+                head.push(tok);
+                let body = parse_stmts(toks, pos);
+                let close = toks.get(*pos).cloned();
+                *pos += 1;
+                return Stmt { blank_before, head, complete: true, body: Some((body, close)) }
+            },
+            ";" if depth == 0 => {
+                head.push(tok);
+                return Stmt { blank_before, head, complete: true, body: None }
+            },
+            _ => {}
+        }
+        head.push(tok);
+    }
+    return Stmt { blank_before, head, complete: false, body: None }
+}
+
+fn import_path(stmt: &Stmt) -> String {
+    for tok in stmt.head.iter() {
+        if tok.is_string() {
+            // In a partial tree the string may be missing its closing quote.
+            let quoted = &tok.raw[1..];
+            return quoted.strip_suffix(&tok.raw[..1]).unwrap_or(quoted).to_string()
+        }
+    }
+    return String::new()
+}
+
+fn sort_imports(stmts: &mut [Stmt]) {
+    let mut i = 0;
+    while i < stmts.len() {
+        if !stmts[i].head[0].is("import") {
+            i += 1;
+            continue
+        }
+        let start = i;
+        while i < stmts.len() && stmts[i].head[0].is("import") {
+            i += 1;
+        }
+        let blank_before = stmts[start].blank_before;
+        let run = &mut stmts[start..i];
+        run.sort_by_key(import_path);
+        for stmt in run.iter_mut() {
+            stmt.blank_before = false;
+        }
+        run[0].blank_before = blank_before;
+    }
+}
+
+struct Line {
+    indent: usize,
+    text: String,
+    // Byte offset of the ` =` to line up, for fields and enum values.
+    align: Option<usize>,
+    is_comment: bool
+}
+
+struct Formatter<'a> {
+    opts: &'a FormatOptions,
+    lines: Vec<Line>,
+    cur: Option<Line>,
+    // Indentation for lines that a statement spills onto because of a
+    // line comment in its middle.
+    cont_indent: usize,
+    prev: Option<String>,
+    prev2: Option<String>
+}
+
+impl<'a> Formatter<'a> {
+    fn new(opts: &'a FormatOptions) -> Self {
+        return Formatter {
+            opts,
+            lines: vec![],
+            cur: None,
+            cont_indent: 0,
+            prev: None,
+            prev2: None
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.end_line();
+        while self.lines.last().is_some_and(|l| l.text.is_empty()) {
+            self.lines.pop();
+        }
+        if self.opts.align_field_numbers {
+            self.align();
+        }
+        let mut out = String::new();
+        for line in self.lines.iter() {
+            if !line.text.is_empty() {
+                out.push_str(&" ".repeat(line.indent * self.opts.indent_width));
+                out.push_str(line.text.trim_end());
+            }
+            out.push('\n');
+        }
+        return out
+    }
+
+    fn align(&mut self) {
+        let mut i = 0;
+        while i < self.lines.len() {
+            if self.lines[i].align.is_none() {
+                i += 1;
+                continue
+            }
+            let indent = self.lines[i].indent;
+            let mut end = i;
+            let mut col = 0;
+            while end < self.lines.len() && self.lines[end].indent == indent && (self.lines[end].align.is_some() || self.lines[end].is_comment) {
+                if let Some(c) = self.lines[end].align {
+                    col = col.max(c);
+                }
+                end += 1;
+            }
+            for line in self.lines[i..end].iter_mut() {
+                if let Some(c) = line.align {
+                    line.text.insert_str(c, &" ".repeat(col - c));
+                }
+            }
+            i = end;
+        }
+    }
+
+    fn start_line(&mut self, indent: usize) {
+        self.end_line();
+        self.cur = Some(Line { indent, text: String::new(), align: None, is_comment: false });
+        self.prev = None;
+        self.prev2 = None;
+    }
+
+    fn end_line(&mut self) {
+        if let Some(line) = self.cur.take() {
+            if !line.text.is_empty() {
+                self.lines.push(line);
+            }
+        }
+    }
+
+    fn blank_line(&mut self) {
+        self.end_line();
+        if self.lines.last().is_some_and(|l| !l.text.is_empty()) {
+            self.lines.push(Line { indent: 0, text: String::new(), align: None, is_comment: false });
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        return self.cur.as_ref().is_none_or(|l| l.text.is_empty())
+    }
+
+    fn push(&mut self, text: &str, space: bool) {
+        if self.cur.is_none() {
+            let indent = self.cont_indent;
+            self.start_line(indent);
+        }
+        let line = self.cur.as_mut().unwrap();
+        if space && !line.text.is_empty() {
+            line.text.push(' ');
+        }
+        line.text.push_str(text);
+    }
+
+    fn comment_line(&mut self, c: &Comment, indent: usize) {
+        self.start_line(indent);
+        self.push(c.text(), false);
+        if let Some(line) = self.cur.as_mut() {
+            line.is_comment = true;
+        }
+        self.end_line();
+    }
+
+    /// Writes a token with its comments. `skip_first_gap` is set when the
+    /// blank line in front of the token was already handled by the
+    /// statement that starts with it.
+    fn tok(&mut self, tok: &Tok, space: bool, skip_first_gap: bool) {
+        let mut space = space;
+        if self.at_line_start() {
+            let indent = self.cur.as_ref().map(|l| l.indent).unwrap_or(self.cont_indent);
+            for (i, c) in tok.leading.iter().enumerate() {
+                if (i > 0 || !skip_first_gap) && is_blank_gap(c.leading_whitespace()) {
+                    self.blank_line();
+                }
+                self.comment_line(c, indent);
+            }
+            if !tok.leading.is_empty() && is_blank_gap(&tok.leading_whitespace) {
+                self.blank_line();
+            }
+            if self.cur.is_none() {
+                self.start_line(indent);
+            }
+        } else {
+            for c in tok.leading.iter() {
+                self.push(c.text(), true);
+                if is_line_comment(c) {
+                    self.end_line();
+                }
+                space = !matches!(tok.raw.as_str(), ";" | "," | ")" | "]" | ">" | ":" | ".");
+            }
+        }
+        self.push(&tok.raw, space);
+        self.prev2 = self.prev.take();
+        self.prev = Some(tok.raw.clone());
+        let indent = self.cur.as_ref().map(|l| l.indent).unwrap_or(self.cont_indent);
+        for c in tok.trailing.iter() {
+            // A trailing comment that started on a later line stays on a
+            // line of its own.
+            if c.leading_whitespace().contains('\n') {
+                self.comment_line(c, indent);
+                continue
+            }
+            self.push(c.text(), true);
+            if is_line_comment(c) {
+                self.end_line();
+            }
+        }
+    }
+
+    fn space_before(&self, cur: &Tok) -> bool {
+        let prev = match self.prev.as_deref() {
+            Some(p) => p,
+            None => return false
+        };
+        // A leading dot after a keyword, as in `repeated .foo.Bar`. Without
+        // the space in the source, the keyword was part of the name.
+        if cur.is(".") && matches!(prev, "optional" | "required" | "repeated" | "stream" | "extend") && !cur.leading_whitespace.is_empty() {
+            return true
+        }
+        let cur = cur.raw.as_str();
+        if matches!(cur, ";" | "," | ")" | "]" | "." | ">" | ":" | "/") {
+            return false
+        }
+        if matches!(prev, "(" | "[" | "." | "<" | "/" | "-" | "+") {
+            return false
+        }
+        if cur == "(" && self.prev2.as_deref() == Some("rpc") {
+            return false
+        }
+        if cur == "<" && prev == "map" {
+            return false
+        }
+        return true
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, indent: usize, first: bool) {
+        if stmt.blank_before && !first {
+            self.blank_line();
+        }
+        // Options and message literals are only laid out when they are
+        // whole; the tokens of a broken statement are written as they come.
+        let opens_body = if stmt.body.is_some() { 1 } else { 0 };
+        if !stmt.complete || !balanced(&stmt.head[..stmt.head.len() - opens_body]) {
+            self.start_line(indent);
+            for (i, tok) in stmt.head.iter().enumerate() {
+                let space = self.space_before(tok);
+                self.tok(tok, space, i == 0);
+            }
+        } else {
+            let snapshot = self.lines.len();
+            self.head(&stmt.head, indent, false);
+            let too_long = self.cur.iter().chain(self.lines[snapshot..].iter()).any(|l| l.indent * self.opts.indent_width + l.text.len() > self.opts.max_line_length);
+            if too_long && has_compact_options(&stmt.head) {
+                self.lines.truncate(snapshot);
+                self.cur = None;
+                self.head(&stmt.head, indent, true);
+            }
+        }
+        match &stmt.body {
+            Some((body, Some(close))) if body.is_empty() && close.leading.is_empty() => {
+                self.tok(close, false, false);
+            },
+            Some((body, close)) => {
+                self.end_line();
+                for (i, child) in body.iter().enumerate() {
+                    self.stmt(child, indent + 1, i == 0);
+                }
+                // Without a closing brace the body runs to the end of the
+                // file, which writes the comments after it.
+                if let Some(close) = close {
+                    self.close_comments(close, indent + 1, body.is_empty());
+                    self.start_line(indent);
+                    let mut close = close.clone();
+                    close.leading.clear();
+                    self.tok(&close, false, false);
+                }
+            },
+            None => {}
+        }
+        self.end_line();
+    }
+
+    /// Writes the comments in front of a closing brace or the end of the
+    /// file, which belong to the end of the enclosing block.
+    fn close_comments(&mut self, close: &Tok, indent: usize, first: bool) {
+        for (i, c) in close.leading.iter().enumerate() {
+            if (i > 0 || !first) && is_blank_gap(c.leading_whitespace()) {
+                self.blank_line();
+            }
+            self.comment_line(c, indent);
+        }
+    }
+
+    fn head(&mut self, toks: &[Tok], indent: usize, wrap: bool) {
+        self.start_line(indent);
+        self.cont_indent = indent + 2;
+        let alignable = !matches!(toks[0].raw.as_str(), "option" | "syntax" | "edition");
+        let mut seen_equals = false;
+        let mut i = 0;
+        while i < toks.len() {
+            let tok = &toks[i];
+            if tok.is("[") {
+                self.compact_options(toks, &mut i, indent, wrap);
+                continue
+            }
+            if tok.is("{") && self.prev.as_deref() == Some("=") {
+                self.literal(toks, &mut i, indent);
+                continue
+            }
+            if tok.is("=") && alignable && !seen_equals {
+                seen_equals = true;
+                if let Some(line) = self.cur.as_mut() {
+                    line.align = Some(line.text.len());
+                }
+            }
+            let space = self.space_before(tok);
+            self.tok(tok, space, i == 0);
+            i += 1;
+        }
+    }
+
+    fn compact_options(&mut self, toks: &[Tok], i: &mut usize, indent: usize, wrap: bool) {
+        let end = matching(toks, *i);
+        // Split the options at the commas between them.
+        let mut opts: Vec<(usize, usize)> = vec![];
+        let mut start = *i + 1;
+        let mut depth = 0;
+        for (j, t) in toks.iter().enumerate().take(end).skip(*i + 1) {
+            match t.raw.as_str() {
+                "(" | "[" | "{" | "<" => depth += 1,
+                ")" | "]" | "}" | ">" => depth -= 1,
+                "," if depth == 0 => {
+                    opts.push((start, j));
+                    start = j + 1;
+                },
+                _ => {}
+            }
+        }
+        opts.push((start, end));
+
+        let wrap = wrap || (self.opts.wrap_compact_options && opts.len() > 1);
+        let opt_indent = if wrap { indent + 1 } else { indent };
+        self.tok(&toks[*i], true, false);
+        for (n, (s, e)) in opts.iter().enumerate() {
+            if wrap {
+                self.start_line(opt_indent);
+            }
+            self.value_tokens(&toks[*s..*e], opt_indent);
+            if n + 1 < opts.len() {
+                self.tok(&toks[*e], false, false);
+            }
+        }
+        if wrap {
+            self.start_line(indent);
+        }
+        self.tok(&toks[end], false, false);
+        *i = end + 1;
+    }
+
+    /// Writes an option name, `=` and value, or any other run of tokens
+    /// that may contain message literals.
+    fn value_tokens(&mut self, toks: &[Tok], indent: usize) {
+        let mut i = 0;
+        while i < toks.len() {
+            if (toks[i].is("{") || toks[i].is("<")) && self.prev.as_deref() == Some("=") {
+                self.literal(toks, &mut i, indent);
+                continue
+            }
+            let space = self.space_before(&toks[i]);
+            self.tok(&toks[i], space, false);
+            i += 1;
+        }
+    }
+
+    /// Writes a message literal with one field per line.
+    fn literal(&mut self, toks: &[Tok], i: &mut usize, indent: usize) {
+        let end = matching(toks, *i);
+        let space = self.space_before(&toks[*i]);
+        self.tok(&toks[*i], space, false);
+        if end == *i + 1 && toks[end].leading.is_empty() {
+            self.tok(&toks[end], false, false);
+            *i = end + 1;
+            return
+        }
+        let saved_cont = self.cont_indent;
+        self.cont_indent = indent + 3;
+        // The fields only see the tokens up to the closing brace, so a
+        // malformed one can't run past it.
+        let inner = &toks[..end];
+        let mut j = *i + 1;
+        while j < end {
+            self.start_line(indent + 1);
+            self.literal_field(inner, &mut j, indent + 1);
+        }
+        self.cont_indent = saved_cont;
+        self.close_comments(&toks[end], indent + 1, false);
+        self.start_line(indent);
+        let mut close = toks[end].clone();
+        close.leading.clear();
+        self.tok(&close, false, false);
+        *i = end + 1;
+    }
+
+    fn literal_field(&mut self, toks: &[Tok], j: &mut usize, indent: usize) {
+        // The name: an identifier, or an extension or type URL in brackets.
+        if toks[*j].is("[") {
+            let end = matching(toks, *j);
+            for t in toks[*j..=end].iter() {
+                let space = self.space_before(t);
+                self.tok(t, space, false);
+            }
+            *j = end + 1;
+        } else {
+            self.tok(&toks[*j], false, false);
+            *j += 1;
+        }
+        if toks.get(*j).is_some_and(|t| t.is(":")) {
+            self.tok(&toks[*j], false, false);
+            *j += 1;
+        }
+        self.literal_value(toks, j, indent);
+        if toks.get(*j).is_some_and(|t| t.is(",") || t.is(";")) {
+            self.tok(&toks[*j], false, false);
+            *j += 1;
+        }
+    }
+
+    fn literal_value(&mut self, toks: &[Tok], j: &mut usize, indent: usize) {
+        let tok = match toks.get(*j) {
+            Some(tok) => tok,
+            None => return
+        };
+        if tok.is("{") || tok.is("<") {
+            self.literal(toks, j, indent);
+            return
+        }
+        if tok.is("[") {
+            self.array(toks, j, indent);
+            return
+        }
+        if tok.is("-") || tok.is("+") {
+            let space = self.space_before(tok);
+            self.tok(tok, space, false);
+            *j += 1;
+            if let Some(tok) = toks.get(*j) {
+                self.tok(tok, false, false);
+                *j += 1;
+            }
+            return
+        }
+        if tok.is_string() {
+            while toks.get(*j).is_some_and(Tok::is_string) {
+                let space = self.space_before(&toks[*j]);
+                self.tok(&toks[*j], space, false);
+                *j += 1;
+            }
+            return
+        }
+        let space = self.space_before(tok);
+        self.tok(tok, space, false);
+        *j += 1;
+    }
+
+    fn array(&mut self, toks: &[Tok], j: &mut usize, indent: usize) {
+        let end = matching(toks, *j);
+        let multiline = toks[*j + 1..end].iter().any(|t| t.is("{") || t.is("<"));
+        let space = self.space_before(&toks[*j]);
+        self.tok(&toks[*j], space, false);
+        *j += 1;
+        let inner = &toks[..end];
+        while *j < end {
+            if multiline {
+                self.start_line(indent + 1);
+            }
+            self.literal_value(inner, j, indent + 1);
+            if inner.get(*j).is_some_and(|t| t.is(",")) {
+                self.tok(&toks[*j], false, false);
+                *j += 1;
+            }
+        }
+        if multiline {
+            self.start_line(indent);
+        }
+        self.tok(&toks[end], false, false);
+        *j = end + 1;
+    }
+}
+
+fn has_compact_options(toks: &[Tok]) -> bool {
+    return toks.iter().any(|t| t.is("["))
+}
+
+/// Reports whether every bracket, parenthesis, brace and angle bracket in
+/// `toks` is closed by its own kind, in order.
+fn balanced(toks: &[Tok]) -> bool {
+    let mut open: Vec<&str> = vec![];
+    for tok in toks.iter() {
+        let expected = match tok.raw.as_str() {
+            "(" | "[" | "{" | "<" => {
+                open.push(tok.raw.as_str());
+                continue
+            },
+            ")" => "(",
+            "]" => "[",
+            "}" => "{",
+            ">" => "<",
+            _ => continue
+        };
+        if open.pop() != Some(expected) {
+            return false
+        }
+    }
+    return open.is_empty()
+}
+
+/// Returns the index of the token that closes the bracket, parenthesis,
+/// brace or angle bracket at `start`.
+fn matching(toks: &[Tok], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, tok) in toks.iter().enumerate().skip(start) {
+        match tok.raw.as_str() {
+            "(" | "[" | "{" | "<" => depth += 1,
+            ")" | "]" | "}" | ">" => {
+                depth -= 1;
+                if depth == 0 {
+                    return i
+                }
+            },
+            _ => {}
+        }
+    }
+    return toks.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, parse_partial};
+
+    const SOURCE: &str = "syntax   =  \"proto3\" ;\nimport \"z.proto\";\nimport \"a.proto\";\n// About M.\nmessage M {\n    int32 a = 1;   // trailing\n\n\n\n  string long_name = 22 [deprecated = true, json_name = \"x\"];\n  enum E { A = 0; BB = 1; }\n}\n// Last.\n";
+
+    fn reformat(source: &str, opts: &FormatOptions) -> String {
+        return format(&parse("a.proto", source).unwrap(), opts)
+    }
+
+    #[test]
+    fn canonical_style() {
+        let expected = "syntax = \"proto3\";\nimport \"a.proto\";\nimport \"z.proto\";\n// About M.\nmessage M {\n  int32 a = 1; // trailing\n\n  string long_name = 22 [deprecated = true, json_name = \"x\"];\n  enum E {\n    A = 0;\n    BB = 1;\n  }\n}\n// Last.\n";
+        assert_eq!(reformat(SOURCE, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn options_change_the_style() {
+        let opts = FormatOptions {
+            indent_width: 4,
+            max_line_length: 100,
+            wrap_compact_options: true,
+            align_field_numbers: true,
+            sort_imports: false
+        };
+        let expected = "syntax = \"proto3\";\nimport \"z.proto\";\nimport \"a.proto\";\n// About M.\nmessage M {\n    int32 a = 1; // trailing\n\n    string long_name = 22 [\n        deprecated = true,\n        json_name = \"x\"\n    ];\n    enum E {\n        A  = 0;\n        BB = 1;\n    }\n}\n// Last.\n";
+        assert_eq!(reformat(SOURCE, &opts), expected);
+    }
+
+    #[test]
+    fn long_lines_wrap_their_options() {
+        let opts = FormatOptions { max_line_length: 40, ..FormatOptions::default() };
+        let formatted = reformat("message M { string long_name = 22 [deprecated = true, json_name = \"x\"]; }", &opts);
+        assert_eq!(formatted, "message M {\n  string long_name = 22 [\n    deprecated = true,\n    json_name = \"x\"\n  ];\n}\n");
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let sources = [
+            SOURCE,
+            "// Leading.\nsyntax = \"proto2\";  // trailing\n\npackage  a.b ;\n\n/* detached */\n\nmessage M {\n\toptional int32 x = 1 [default = -inf, (c).d = { e: [1, 2] f <g: \"h\" \"i\"> }];\n  extensions 10 to max;\n  oneof o { group G = 2 { } }\n}\nservice S { rpc R (stream M) returns (M); }\n// Last.\n"
+        ];
+        let styles = [
+            FormatOptions::default(),
+            FormatOptions { indent_width: 4, max_line_length: 20, wrap_compact_options: true, align_field_numbers: true, sort_imports: false }
+        ];
+        for source in sources.iter() {
+            for opts in styles.iter() {
+                let once = reformat(source, opts);
+                assert_eq!(reformat(&once, opts), once);
+            }
+        }
+    }
+
+    #[test]
+    fn partial_trees_end_at_the_end_of_file() {
+        let cases = [
+            ("message M { message N {", "message M {\n  message N {\n"),
+            ("message M { int32 a = 1 [(", "message M {\n  int32 a = 1 [(\n"),
+            ("message M { int32 a = 1 [(x) = {a: 1", "message M {\n  int32 a = 1 [(x) = { a: 1\n"),
+            ("import \"a.proto\";\nimport \"", "import \"\nimport \"a.proto\";\n"),
+            ("option x = {:};", "option x = {\n  :\n};\n")
+        ];
+        for (source, expected) in cases.iter() {
+            let (file, errors) = parse_partial("a.proto", source);
+            assert!(!errors.errors().is_empty());
+            assert_eq!(format(&file, &FormatOptions::default()), *expected);
+        }
+    }
+}
@@ -1,6 +1,10 @@
-use crate::node::{TerminalNode, TokenInfo, CompositeNode, RuneNode, NodeTrait};
-use crate::values::{_ValueNodeTrait};
+use std::any::Any;
+use crate::node::{NodeKind, TerminalNode, TokenInfo, CompositeNode, RuneNode, NodeTrait, TerminalNodeTrait, CompositeNodeTrait};
+use dyn_clone::clone_trait_object;
+use crate::values::{ValueNodeTrait, _ValueNodeTrait};
 use crate::source_pos::{SourcePos, Comment};
+use crate::error::{Error, check_separators, check_not_empty};
+use crate::visit::{Visitor, VisitorMut, walk_opt_mut, walk_separated_mut};
 
 pub type Identifier = String;
 
@@ -13,7 +17,9 @@ pub trait IdentValueNodeTrait: _IdentValueNodeTrait + NodeTrait {
     fn as_ident_value_node_trait(&self) -> Box<dyn _IdentValueNodeTrait>;
 }
 
-#[derive(Clone)]
+clone_trait_object!(IdentValueNodeTrait);
+
+#[derive(Debug, Clone)]
 pub struct IdentNode {
     terminal_node: TerminalNode,
     val: String
@@ -27,9 +33,6 @@ impl IdentNode {
         }
     }
 
-    pub fn as_identifier(self) -> Identifier {
-        return self.val
-    }
 
     pub fn to_keyword(self) -> KeywordNode {
         return self
@@ -38,11 +41,11 @@ impl IdentNode {
 
 impl NodeTrait for IdentNode {
     fn start(&self) -> &SourcePos {
-        return &self.terminal_node.start()
+        return self.terminal_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.terminal_node.end()
+        return self.terminal_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -52,14 +55,65 @@ impl NodeTrait for IdentNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.terminal_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.terminal_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.terminal_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_ident(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_ident_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Ident
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
 impl _ValueNodeTrait<Identifier> for IdentNode {
     fn value(self) -> Identifier {
-        return self.as_identifier()
+        return self.val
+    }
+}
+
+impl ValueNodeTrait<Identifier> for IdentNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<Identifier>> {
+        return Box::new(self.clone())
+    }
+}
+
+impl _IdentValueNodeTrait for IdentNode {
+    fn as_identifier(&self) -> Identifier {
+        return self.val.clone()
+    }
+}
+
+impl IdentValueNodeTrait for IdentNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_ident_value_node_trait(&self) -> Box<dyn _IdentValueNodeTrait> {
+        return Box::new(self.clone())
     }
 }
-#[derive(Clone)]
+
+#[derive(Debug, Clone)]
 pub struct CompoundIdentNode {
     composite_node: CompositeNode,
     leading_dot: Option<RuneNode>,
@@ -69,35 +123,99 @@ pub struct CompoundIdentNode {
 }
 
 impl CompoundIdentNode {
-    fn new(leading_dot: Option<RuneNode>, components: Vec<IdentNode>, dots: Vec<RuneNode>) -> Self {
-        let mut num_children = (components.len()*2) - 1;
+    pub fn new(leading_dot: Option<RuneNode>, components: Vec<IdentNode>, dots: Vec<RuneNode>) -> Result<Self, Error> {
+        let around: Vec<&dyn NodeTrait> = leading_dot.iter().map(|d| d as &dyn NodeTrait).collect();
+        check_not_empty(&components, NodeKind::CompoundIdent, &around)?;
+        check_separators(&components, &dots)?;
+        let mut num_children = components.len() + dots.len();
         if leading_dot.is_some() {
             num_children += 1;
         }
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(num_children);
         let mut b = "".to_string();
-        if leading_dot.is_some() {
-            children.push(Box::new(leading_dot.clone().unwrap()));
-            b.push(leading_dot.clone().unwrap().rune())
+        if let Some(dot) = leading_dot.clone() {
+            b.push(dot.rune());
+            children.push(Box::new(dot));
         }
         for (i, c) in components.iter().enumerate() {
             if i > 0 {
-                let dot = dots.get(i-1);
-                children.push(Box::new(dot.clone().unwrap().clone()));
-                b.push(dot.clone().unwrap().rune());
+                let dot = &dots[i-1];
+                children.push(Box::new(dot.clone()));
+                b.push(dot.rune());
             }
             children.push(Box::new(c.clone()));
             b.push_str(&c.clone().value());
         }
         let mut comp = CompositeNode::new();
         comp.push_children(children);
-        return CompoundIdentNode {
+        return Ok(CompoundIdentNode {
             composite_node: comp,
-            leading_dot: leading_dot,
-            components: components,
-            dots: dots,
+            leading_dot,
+            components,
+            dots,
             val: b
-        }
+        })
+    }
+
+    pub fn leading_dot(&self) -> Option<&RuneNode> {
+        return self.leading_dot.as_ref()
+    }
+
+    pub fn components(&self) -> &[IdentNode] {
+        return &self.components
+    }
+
+    pub fn dots(&self) -> &[RuneNode] {
+        return &self.dots
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_opt_mut(v, self.leading_dot.as_mut());
+        walk_separated_mut(v, &mut self.components, &mut self.dots);
+        // Visiting can't change how many components and dots there are.
+        *self = CompoundIdentNode::new(self.leading_dot.clone(), self.components.clone(), self.dots.clone()).unwrap();
+    }
+}
+
+impl NodeTrait for CompoundIdentNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_compound_ident(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_compound_ident_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::CompoundIdent
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
     }
 }
 
@@ -107,10 +225,30 @@ impl _ValueNodeTrait<String> for CompoundIdentNode {
     }
 }
 
+impl ValueNodeTrait<String> for CompoundIdentNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<String>> {
+        return Box::new(self.clone())
+    }
+}
+
 impl _IdentValueNodeTrait for CompoundIdentNode {
     fn as_identifier(&self) -> Identifier {
         return self.val.clone()
     }
 }
 
+impl IdentValueNodeTrait for CompoundIdentNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_ident_value_node_trait(&self) -> Box<dyn _IdentValueNodeTrait> {
+        return Box::new(self.clone())
+    }
+}
+
 pub type KeywordNode = IdentNode;
\ No newline at end of file
@@ -0,0 +1,949 @@
+//! Interprets the options of a linked file, turning the options protoc
+//! keeps as `uninterpreted_option`s into fields of the options messages.
+//!
+//! Option names are resolved against the options messages of
+//! descriptor.proto, and against extensions of them for custom options
+//! such as `(my.ext).sub.field`. Each option is checked against the type
+//! of the field it sets and encoded, and the encoded fields are read back
+//! into the options message. Custom options end up in `unknown_fields`,
+//! in the order they were written, as in protoc's output. Options that
+//! can't be interpreted are reported and left as `uninterpreted_option`s.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use crate::compile::pos_range;
+use crate::descriptor::*;
+use crate::enums::{EnumElement, EnumNode};
+use crate::error::Error;
+use crate::file::{FileElement, FileNode};
+use crate::identifiers::_IdentValueNodeTrait;
+use crate::linker::{Linker, SymbolKind};
+use crate::message::{ExtendElement, ExtendNode, MessageBody, MessageElement};
+use crate::node::NodeTrait;
+use crate::oneof::OneOfElement;
+use crate::options::{CompactOptionsNode, FieldReferenceNode, OptionNode};
+use crate::parser::parse;
+use crate::service::{MethodElement, ServiceElement, ServiceNode};
+use crate::values::{MessageLiteralNode, ValueNode, _IntValueNodeTrait};
+use crate::wellknown;
+use crate::wire::{Message, WireType, Writer};
+
+const DESCRIPTOR_PROTO: &str = "google/protobuf/descriptor.proto";
+
+/// Interprets every option of `fd`, which must have been linked from
+/// `file` by `linker`. `deps` are the linked descriptors the file can use
+/// extensions and types from. The bundled descriptor.proto defines the
+/// options messages unless `deps` has its own copy.
+pub fn interpret_options(file: &FileNode, fd: &mut FileDescriptorProto, linker: &Linker, deps: &[&FileDescriptorProto]) -> Vec<Error> {
+    // The options of `fd` change as they are interpreted, so the types it
+    // declares are looked up in a copy.
+    let snapshot = fd.clone();
+    let mut pool = Pool::default();
+    pool.add_file(descriptor_proto());
+    for dep in deps.iter() {
+        pool.add_file(dep);
+    }
+    pool.add_file(&snapshot);
+    let mut interpreter = Interpreter {
+        pool,
+        linker,
+        file: file.start().filename().to_string(),
+        errors: vec![]
+    };
+    interpreter.file(file, fd);
+    return interpreter.errors
+}
+
+// The bundled descriptor.proto, linked, for the options messages.
+fn descriptor_proto() -> &'static FileDescriptorProto {
+    static DESCRIPTOR: OnceLock<FileDescriptorProto> = OnceLock::new();
+    return DESCRIPTOR.get_or_init(|| {
+        let source = wellknown::source(DESCRIPTOR_PROTO).unwrap_or_default();
+        let file = parse(DESCRIPTOR_PROTO, source).expect("bundled descriptor.proto must parse");
+        return Linker::new().link(&file).expect("bundled descriptor.proto must link")
+    })
+}
+
+// The messages, enums and extensions of a set of files, by fully
+// qualified name without the leading dot. Messages are kept with whether
+// their file is proto3, which makes repeated scalars packed by default.
+#[derive(Default)]
+struct Pool<'a> {
+    messages: HashMap<String, (&'a DescriptorProto, bool)>,
+    enums: HashMap<String, &'a EnumDescriptorProto>,
+    extensions: HashMap<String, &'a FieldDescriptorProto>
+}
+
+impl<'a> Pool<'a> {
+    fn add_file(&mut self, fd: &'a FileDescriptorProto) {
+        let package = fd.package.clone().unwrap_or_default();
+        let proto3 = fd.syntax.as_deref() == Some("proto3");
+        self.add_messages(&package, &fd.message_type, proto3);
+        self.add_enums(&package, &fd.enum_type);
+        self.add_extensions(&package, &fd.extension);
+    }
+
+    fn add_messages(&mut self, scope: &str, messages: &'a [DescriptorProto], proto3: bool) {
+        for msg in messages.iter() {
+            let full_name = join(scope, msg.name.as_deref().unwrap_or_default());
+            self.add_messages(&full_name, &msg.nested_type, proto3);
+            self.add_enums(&full_name, &msg.enum_type);
+            self.add_extensions(&full_name, &msg.extension);
+            self.messages.insert(full_name, (msg, proto3));
+        }
+    }
+
+    fn add_enums(&mut self, scope: &str, enums: &'a [EnumDescriptorProto]) {
+        for en in enums.iter() {
+            self.enums.insert(join(scope, en.name.as_deref().unwrap_or_default()), en);
+        }
+    }
+
+    fn add_extensions(&mut self, scope: &str, extensions: &'a [FieldDescriptorProto]) {
+        for ext in extensions.iter() {
+            self.extensions.insert(join(scope, ext.name.as_deref().unwrap_or_default()), ext);
+        }
+    }
+}
+
+fn join(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        return name.to_string()
+    }
+    return format!("{}.{}", scope, name)
+}
+
+fn type_name(field: &FieldDescriptorProto) -> &str {
+    let name = field.type_name.as_deref().unwrap_or_default();
+    return name.strip_prefix('.').unwrap_or(name)
+}
+
+// Returns the type of a field as written in source, such as `int32`.
+fn type_label(field: &FieldDescriptorProto) -> String {
+    match field.r#type {
+        Some(t) => return t.name().trim_start_matches("TYPE_").to_ascii_lowercase(),
+        None => return type_name(field).to_string()
+    }
+}
+
+fn is_repeated(field: &FieldDescriptorProto) -> bool {
+    return field.label == Some(FieldLabel::Repeated)
+}
+
+fn is_message(field: &FieldDescriptorProto) -> bool {
+    return matches!(field.r#type, Some(FieldType::Message) | Some(FieldType::Group))
+}
+
+// Returns whether the values of a repeated field are written packed: when
+// `packed` says so, or by default in proto3. The field's own options may
+// not have been interpreted yet.
+fn is_packed(field: &FieldDescriptorProto, proto3: bool) -> bool {
+    match field.r#type {
+        Some(FieldType::String) | Some(FieldType::Bytes) | Some(FieldType::Message) | Some(FieldType::Group) | None => return false,
+        _ => {}
+    }
+    let explicit = field.options.as_ref().and_then(|o| {
+        return o.packed.or_else(|| {
+            let u = o.uninterpreted_option.iter().find(|u| u.name.len() == 1 && !u.name[0].is_extension && u.name[0].name_part == "packed")?;
+            return u.identifier_value.as_deref().map(|v| v == "true")
+        })
+    });
+    return explicit.unwrap_or(proto3)
+}
+
+// Returns the option name as written, such as `(my.ext).sub.field`.
+fn option_name(parts: &[FieldReferenceNode]) -> String {
+    let names: Vec<String> = parts.iter().map(|p| {
+        if p.is_extension() {
+            return format!("({})", p.name().as_identifier())
+        }
+        return p.name().as_identifier()
+    }).collect();
+    return names.join(".")
+}
+
+// A field value, ready to be written after its tag.
+enum Scalar {
+    Varint(u64),
+    Fixed32(u32),
+    Fixed64(u64),
+    Bytes(Vec<u8>),
+    Group(Vec<u8>)
+}
+
+impl Scalar {
+    fn write(&self, w: &mut Writer, number: u32) {
+        match self {
+            Scalar::Varint(v) => {
+                w.tag(number, WireType::Varint);
+                w.varint(*v);
+            },
+            Scalar::Fixed32(v) => {
+                w.tag(number, WireType::Fixed32);
+                w.raw(&v.to_le_bytes());
+            },
+            Scalar::Fixed64(v) => {
+                w.tag(number, WireType::Fixed64);
+                w.raw(&v.to_le_bytes());
+            },
+            Scalar::Bytes(b) => {
+                w.tag(number, WireType::LengthDelimited);
+                w.varint(b.len() as u64);
+                w.raw(b);
+            },
+            Scalar::Group(b) => {
+                w.tag(number, WireType::StartGroup);
+                w.raw(b);
+                w.tag(number, WireType::EndGroup);
+            }
+        }
+    }
+
+    // Writes the value without a tag, as an element of a packed field.
+    fn write_packed(&self, w: &mut Writer) {
+        match self {
+            Scalar::Varint(v) => w.varint(*v),
+            Scalar::Fixed32(v) => w.raw(&v.to_le_bytes()),
+            Scalar::Fixed64(v) => w.raw(&v.to_le_bytes()),
+            Scalar::Bytes(_) | Scalar::Group(_) => {}
+        }
+    }
+}
+
+// The options messages, whose uninterpreted options can be taken out and
+// put back.
+trait Options: Message {
+    fn uninterpreted(&mut self) -> &mut Vec<UninterpretedOption>;
+}
+
+macro_rules! impl_options {
+    ($($t:ty),*) => {
+        $(
+            impl Options for $t {
+                fn uninterpreted(&mut self) -> &mut Vec<UninterpretedOption> {
+                    return &mut self.uninterpreted_option
+                }
+            }
+        )*
+    };
+}
+
+impl_options!(FileOptions, MessageOptions, FieldOptions, OneofOptions, EnumOptions, EnumValueOptions, ServiceOptions, MethodOptions, ExtensionRangeOptions);
+
+// Returns the options of a declaration, leaving out those the compiler
+// doesn't keep: array values, and `default` and `json_name` on fields.
+fn compact_nodes(opts: Option<&CompactOptionsNode>, field: bool) -> Vec<&OptionNode> {
+    let opts = match opts {
+        Some(o) => o.options(),
+        None => return vec![]
+    };
+    return opts.iter().filter(|o| {
+        let parts = o.name().parts();
+        let pseudo = field && parts.len() == 1 && !parts[0].is_extension() && {
+            let name = parts[0].name().as_identifier();
+            name == "default" || name == "json_name"
+        };
+        return !pseudo && !matches!(o.val(), ValueNode::Array(_))
+    }).collect()
+}
+
+fn statement_nodes<'n>(opts: impl Iterator<Item = &'n OptionNode>) -> Vec<&'n OptionNode> {
+    return opts.filter(|o| !matches!(o.val(), ValueNode::Array(_))).collect()
+}
+
+struct Interpreter<'a> {
+    pool: Pool<'a>,
+    linker: &'a Linker,
+    file: String,
+    errors: Vec<Error>
+}
+
+impl<'a> Interpreter<'a> {
+    fn error(&mut self, node: &dyn NodeTrait, message: &str) {
+        self.errors.push(Error::invalid(pos_range(node), message));
+    }
+
+    // Interprets `nodes`, the options written on `decl`, which match the
+    // uninterpreted options of `options` one for one. `message` is the
+    // options message and `scope` where custom option names are resolved
+    // from.
+    fn options<T: Options>(&mut self, decl: &dyn NodeTrait, options: &mut Option<T>, message: &str, nodes: &[&OptionNode], scope: &str) {
+        let count = options.as_mut().map_or(0, |o| o.uninterpreted().len());
+        if count != nodes.len() {
+            // The descriptor wasn't built from this declaration.
+            self.error(decl, &format!("option count mismatch: {} written, {} uninterpreted in the descriptor", nodes.len(), count));
+            return
+        }
+        let opts = match options.as_mut() {
+            Some(o) => o,
+            None => return
+        };
+        let uninterpreted = std::mem::take(opts.uninterpreted());
+        let mut bytes = opts.encode_to_vec();
+        let mut failed = vec![];
+        let mut set = vec![];
+        for (node, u) in nodes.iter().zip(uninterpreted.iter()) {
+            match self.option(message, node, scope, &mut set) {
+                Some(encoded) => bytes.extend(encoded),
+                None => failed.push(u.clone())
+            }
+        }
+        // Values are checked as they are encoded, so the options should
+        // always read back.
+        match T::decode(&bytes) {
+            Ok(decoded) => {
+                *opts = decoded;
+                *opts.uninterpreted() = failed;
+            },
+            Err(e) => {
+                self.error(decl, &format!("could not decode the interpreted {}: {}", message, e.message()));
+                *opts.uninterpreted() = uninterpreted;
+            }
+        }
+    }
+
+    // Returns the encoding of a single option, or `None` if it can't be
+    // interpreted. `set` holds the field number paths of the options
+    // already set on the declaration, with whether the last field is
+    // repeated.
+    fn option(&mut self, message: &str, node: &OptionNode, scope: &str, set: &mut Vec<(Vec<i32>, bool)>) -> Option<Vec<u8>> {
+        let parts = node.name().parts();
+        let name = option_name(parts);
+        if !parts[0].is_extension() && parts[0].name().as_identifier() == "uninterpreted_option" {
+            self.error(&parts[0], "option name \"uninterpreted_option\" is reserved");
+            return None
+        }
+        let mut msg_name = message.to_string();
+        let mut fields = vec![];
+        for (i, part) in parts.iter().enumerate() {
+            let field = self.field(&msg_name, part, scope)?;
+            if i + 1 < parts.len() {
+                if !is_message(field) {
+                    self.error(part, &format!("option \"{}\" has type {}, not a message type", option_name(&parts[..=i]), type_label(field)));
+                    return None
+                }
+                if is_repeated(field) {
+                    self.error(part, &format!("option \"{}\" is a repeated message and must be set with a message literal", option_name(&parts[..=i])));
+                    return None
+                }
+                msg_name = type_name(field).to_string();
+            }
+            fields.push(field);
+        }
+
+        let path: Vec<i32> = fields.iter().map(|f| f.number.unwrap_or_default()).collect();
+        let repeated = is_repeated(fields[fields.len() - 1]);
+        let clash = set.iter().any(|(p, r)| {
+            let n = p.len().min(path.len());
+            return p[..n] == path[..n] && !(p.len() == path.len() && *r && repeated)
+        });
+        if clash {
+            self.error(node.name(), &format!("option \"{}\" is already set", name));
+            return None
+        }
+        set.push((path, repeated));
+
+        let leaf = fields[fields.len() - 1];
+        let mut value = self.scalar(leaf, node.val(), &name, scope)?;
+        let mut number = leaf.number.unwrap_or_default() as u32;
+        for field in fields[..fields.len() - 1].iter().rev() {
+            let mut w = Writer::new();
+            value.write(&mut w, number);
+            value = if field.r#type == Some(FieldType::Group) { Scalar::Group(w.into_bytes()) } else { Scalar::Bytes(w.into_bytes()) };
+            number = field.number.unwrap_or_default() as u32;
+        }
+        let mut w = Writer::new();
+        value.write(&mut w, number);
+        return Some(w.into_bytes())
+    }
+
+    // Returns the field of the options message `message` that one part of
+    // an option name refers to.
+    fn field(&mut self, message: &str, part: &FieldReferenceNode, scope: &str) -> Option<&'a FieldDescriptorProto> {
+        let name = part.name().as_identifier();
+        if part.is_extension() {
+            return self.extension(message, part.name(), &name, scope)
+        }
+        let msg = match self.pool.messages.get(message) {
+            Some((msg, _)) => *msg,
+            None => {
+                self.error(part, &format!("message \"{}\" is not defined", message));
+                return None
+            }
+        };
+        match msg.field.iter().find(|f| f.name.as_deref() == Some(name.as_str())) {
+            Some(field) => return Some(field),
+            None => {
+                self.error(part, &format!("\"{}\" has no field named \"{}\"", message, name));
+                return None
+            }
+        }
+    }
+
+    // Returns the extension of `message` named `name`, as written at
+    // `node`.
+    fn extension(&mut self, message: &str, node: &dyn NodeTrait, name: &str, scope: &str) -> Option<&'a FieldDescriptorProto> {
+        let symbol = match self.linker.lookup(&self.file, scope, name) {
+            Some(symbol) => symbol,
+            None => {
+                self.errors.push(Error::Unresolved { pos_range: pos_range(node), name: name.to_string(), hint: None });
+                return None
+            }
+        };
+        let ext = match self.pool.extensions.get(symbol.full_name()) {
+            Some(ext) if symbol.kind() == SymbolKind::Extension => *ext,
+            _ => {
+                self.error(node, &format!("\"{}\" is not an extension", name));
+                return None
+            }
+        };
+        let extendee = ext.extendee.as_deref().unwrap_or_default();
+        if extendee.strip_prefix('.') != Some(message) {
+            self.error(node, &format!("extension \"{}\" extends \"{}\", not \"{}\"", name, extendee.trim_start_matches('.'), message));
+            return None
+        }
+        return Some(ext)
+    }
+
+    // Checks `val` against the type of `field` and returns it encoded.
+    // `name` is the option or field name used in errors.
+    fn scalar(&mut self, field: &FieldDescriptorProto, val: &ValueNode, name: &str, scope: &str) -> Option<Scalar> {
+        let t = field.r#type?;
+        let label = type_label(field);
+        match t {
+            FieldType::Message | FieldType::Group => {
+                let lit = match val {
+                    ValueNode::Message(lit) => lit,
+                    _ => {
+                        self.error(val, &format!("value must be a message literal for message option \"{}\"", name));
+                        return None
+                    }
+                };
+                let bytes = self.aggregate(type_name(field), lit, scope)?;
+                if t == FieldType::Group {
+                    return Some(Scalar::Group(bytes))
+                }
+                return Some(Scalar::Bytes(bytes))
+            },
+            FieldType::Enum => {
+                let ident = match val {
+                    ValueNode::Identifier(i) => i.as_identifier(),
+                    ValueNode::Bool(b) => b.keyword().as_identifier(),
+                    ValueNode::SpecialFloat(f) => f.keyword().as_identifier(),
+                    _ => {
+                        self.error(val, &format!("value must be an identifier for enum option \"{}\"", name));
+                        return None
+                    }
+                };
+                let en = self.pool.enums.get(type_name(field)).copied();
+                match en.and_then(|en| en.value.iter().find(|v| v.name.as_deref() == Some(ident.as_str()))) {
+                    Some(v) => return Some(Scalar::Varint(v.number.unwrap_or_default() as i64 as u64)),
+                    None => {
+                        self.error(val, &format!("enum \"{}\" has no value named \"{}\" for option \"{}\"", type_name(field), ident, name));
+                        return None
+                    }
+                }
+            },
+            FieldType::Bool => {
+                let ident = match val {
+                    ValueNode::Bool(b) => b.keyword().as_identifier(),
+                    ValueNode::Identifier(i) => i.as_identifier(),
+                    _ => String::new()
+                };
+                match ident.as_str() {
+                    "true" => return Some(Scalar::Varint(1)),
+                    "false" => return Some(Scalar::Varint(0)),
+                    _ => {
+                        self.error(val, &format!("value must be \"true\" or \"false\" for bool option \"{}\"", name));
+                        return None
+                    }
+                }
+            },
+            FieldType::String | FieldType::Bytes => {
+                let bytes = match val.as_string_value() {
+                    Some(s) => s.as_bytes(),
+                    None => {
+                        self.error(val, &format!("value must be a string for {} option \"{}\"", label, name));
+                        return None
+                    }
+                };
+                if t == FieldType::String && std::str::from_utf8(&bytes).is_err() {
+                    self.error(val, &format!("value must be valid UTF-8 for string option \"{}\"", name));
+                    return None
+                }
+                return Some(Scalar::Bytes(bytes))
+            },
+            FieldType::Float | FieldType::Double => {
+                let v = match val {
+                    ValueNode::PositiveUint(i) => i.as_uint64().0 as f64,
+                    ValueNode::NegativeInt(i) => i.as_int64().0 as f64,
+                    _ => match val.as_float_value() {
+                        Some(f) => f.as_float(),
+                        None => {
+                            self.error(val, &format!("value must be a number for {} option \"{}\"", label, name));
+                            return None
+                        }
+                    }
+                };
+                // protoc reads `-nan` as a plain NaN.
+                let v = if v.is_nan() { f64::NAN } else { v };
+                if t == FieldType::Float {
+                    return Some(Scalar::Fixed32((v as f32).to_bits()))
+                }
+                return Some(Scalar::Fixed64(v.to_bits()))
+            },
+            _ => {}
+        }
+
+        let int = match val.as_int_value() {
+            Some(i) => i,
+            None => {
+                self.error(val, &format!("value must be an integer for {} option \"{}\"", label, name));
+                return None
+            }
+        };
+        let (signed, signed_ok) = int.as_int64();
+        let (unsigned, unsigned_ok) = int.as_uint64();
+        let negative = matches!(val, ValueNode::NegativeInt(_));
+        let scalar = match t {
+            FieldType::Int32 | FieldType::Sint32 | FieldType::Sfixed32 if signed_ok && signed >= i32::MIN as i64 && signed <= i32::MAX as i64 => {
+                let n = signed as i32;
+                match t {
+                    FieldType::Sint32 => Scalar::Varint(((n << 1) ^ (n >> 31)) as u32 as u64),
+                    FieldType::Sfixed32 => Scalar::Fixed32(n as u32),
+                    _ => Scalar::Varint(n as i64 as u64)
+                }
+            },
+            FieldType::Int64 | FieldType::Sint64 | FieldType::Sfixed64 if signed_ok => {
+                match t {
+                    FieldType::Sint64 => Scalar::Varint(((signed << 1) ^ (signed >> 63)) as u64),
+                    FieldType::Sfixed64 => Scalar::Fixed64(signed as u64),
+                    _ => Scalar::Varint(signed as u64)
+                }
+            },
+            FieldType::Uint32 | FieldType::Fixed32 if !negative && unsigned_ok && unsigned <= u32::MAX as u64 => {
+                match t {
+                    FieldType::Fixed32 => Scalar::Fixed32(unsigned as u32),
+                    _ => Scalar::Varint(unsigned)
+                }
+            },
+            FieldType::Uint64 | FieldType::Fixed64 if !negative && unsigned_ok => {
+                match t {
+                    FieldType::Fixed64 => Scalar::Fixed64(unsigned),
+                    _ => Scalar::Varint(unsigned)
+                }
+            },
+            _ => {
+                self.error(val, &format!("value out of range for {} option \"{}\"", label, name));
+                return None
+            }
+        };
+        return Some(scalar)
+    }
+
+    // Returns the encoding of a message literal of type `message`. Fields
+    // are written in field number order, as protoc writes the message it
+    // parses the literal into.
+    fn aggregate(&mut self, message: &str, lit: &MessageLiteralNode, scope: &str) -> Option<Vec<u8>> {
+        let (msg, proto3) = match self.pool.messages.get(message) {
+            Some(m) => *m,
+            None => {
+                self.error(lit, &format!("message \"{}\" is not defined", message));
+                return None
+            }
+        };
+        let mut values: BTreeMap<i32, (&FieldDescriptorProto, Vec<Scalar>)> = BTreeMap::new();
+        let mut ok = true;
+        for elem in lit.elements().iter() {
+            let name = elem.name().name().as_identifier();
+            if elem.name().is_any_type_reference() {
+                ok &= self.any(message, msg, elem.name(), elem.val(), scope, &mut values);
+                continue
+            }
+            let field = if elem.name().is_extension() {
+                self.extension(message, elem.name().name(), &name, scope)
+            } else {
+                // Groups are written with the name of their type.
+                let field = msg.field.iter().find(|f| {
+                    return f.name.as_deref() == Some(name.as_str())
+                        || (f.r#type == Some(FieldType::Group) && type_name(f).rsplit('.').next() == Some(name.as_str()))
+                });
+                if field.is_none() {
+                    self.error(elem.name(), &format!("\"{}\" has no field named \"{}\"", message, name));
+                }
+                field
+            };
+            let field = match field {
+                Some(f) => f,
+                None => {
+                    ok = false;
+                    continue
+                }
+            };
+            let entry = values.entry(field.number.unwrap_or_default()).or_insert((field, vec![]));
+            if !is_repeated(field) && !entry.1.is_empty() {
+                self.error(elem.name(), &format!("field \"{}\" is already set", name));
+                ok = false;
+                continue
+            }
+            let elements: Vec<&ValueNode> = match elem.val() {
+                ValueNode::Array(a) if is_repeated(field) => a.elements().iter().collect(),
+                ValueNode::Array(a) => {
+                    self.error(a, &format!("field \"{}\" is not repeated, so its value can't be a list", name));
+                    ok = false;
+                    continue
+                },
+                v => vec![v]
+            };
+            for v in elements {
+                match self.scalar(field, v, &name, scope) {
+                    Some(s) => values.entry(field.number.unwrap_or_default()).or_insert((field, vec![])).1.push(s),
+                    None => ok = false
+                }
+            }
+        }
+        for field in msg.field.iter() {
+            if field.label == Some(FieldLabel::Required) && !values.contains_key(&field.number.unwrap_or_default()) {
+                self.error(lit, &format!("message \"{}\" is missing required field \"{}\"", message, field.name.as_deref().unwrap_or_default()));
+                ok = false;
+            }
+        }
+        if !ok {
+            return None
+        }
+
+        let mut w = Writer::new();
+        for (number, (field, scalars)) in values.iter() {
+            let number = *number as u32;
+            if is_repeated(field) && is_packed(field, proto3) {
+                let mut packed = Writer::new();
+                for s in scalars.iter() {
+                    s.write_packed(&mut packed);
+                }
+                Scalar::Bytes(packed.into_bytes()).write(&mut w, number);
+            } else {
+                for s in scalars.iter() {
+                    s.write(&mut w, number);
+                }
+            }
+        }
+        return Some(w.into_bytes())
+    }
+
+    // Sets the fields of a `google.protobuf.Any` from an expanded literal
+    // such as `[type.googleapis.com/foo.Bar] { ... }`.
+    fn any(&mut self, message: &str, msg: &'a DescriptorProto, name: &FieldReferenceNode, val: &ValueNode, scope: &str, values: &mut BTreeMap<i32, (&'a FieldDescriptorProto, Vec<Scalar>)>) -> bool {
+        let prefix = name.url_prefix().map(|p| p.as_identifier()).unwrap_or_default();
+        let type_name = name.name().as_identifier();
+        if message != "google.protobuf.Any" {
+            self.error(name, &format!("\"{}\" is not google.protobuf.Any, so it can't hold \"{}/{}\"", message, prefix, type_name));
+            return false
+        }
+        let symbol = match self.linker.symbol(&type_name) {
+            Some(s) if s.kind() == SymbolKind::Message => s.full_name().to_string(),
+            _ => {
+                self.error(name.name(), &format!("\"{}\" is not a message type", type_name));
+                return false
+            }
+        };
+        let lit = match val {
+            ValueNode::Message(lit) => lit,
+            _ => {
+                self.error(val, &format!("value must be a message literal for \"{}/{}\"", prefix, type_name));
+                return false
+            }
+        };
+        let type_url = msg.field.iter().find(|f| f.number == Some(1));
+        let value = msg.field.iter().find(|f| f.number == Some(2));
+        let (type_url, value) = match (type_url, value) {
+            (Some(t), Some(v)) => (t, v),
+            _ => return false
+        };
+        if values.contains_key(&1) || values.contains_key(&2) {
+            self.error(name, "google.protobuf.Any is already set");
+            return false
+        }
+        let bytes = match self.aggregate(&symbol, lit, scope) {
+            Some(b) => b,
+            None => return false
+        };
+        values.insert(1, (type_url, vec![Scalar::Bytes(format!("{}/{}", prefix, type_name).into_bytes())]));
+        values.insert(2, (value, vec![Scalar::Bytes(bytes)]));
+        return true
+    }
+
+    fn file(&mut self, file: &FileNode, fd: &mut FileDescriptorProto) {
+        let package = fd.package.clone().unwrap_or_default();
+        let nodes = statement_nodes(file.decls().iter().filter_map(|d| match d {
+            FileElement::Option(o) => Some(o),
+            _ => None
+        }));
+        self.options(file, &mut fd.options, "google.protobuf.FileOptions", &nodes, &package);
+        let (mut mi, mut ei, mut xi, mut si) = (0, 0, 0, 0);
+        for decl in file.decls().iter() {
+            match decl {
+                FileElement::Message(m) => {
+                    if let Some(msg) = fd.message_type.get_mut(mi) {
+                        self.message(m, m.body(), msg, &package);
+                    }
+                    mi += 1;
+                },
+                FileElement::Enum(e) => {
+                    if let Some(en) = fd.enum_type.get_mut(ei) {
+                        self.enum_type(e, en, &package);
+                    }
+                    ei += 1;
+                },
+                FileElement::Extend(e) => self.extend(e, &package, &mut fd.extension, &mut xi, &mut fd.message_type, &mut mi),
+                FileElement::Service(s) => {
+                    if let Some(service) = fd.service.get_mut(si) {
+                        self.service(s, service, &package);
+                    }
+                    si += 1;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn field_options(&mut self, decl: &dyn NodeTrait, opts: Option<&CompactOptionsNode>, field: Option<&mut FieldDescriptorProto>, scope: &str) {
+        if let Some(field) = field {
+            self.options(decl, &mut field.options, "google.protobuf.FieldOptions", &compact_nodes(opts, true), scope);
+        }
+    }
+
+    fn message(&mut self, decl: &dyn NodeTrait, body: &MessageBody, msg: &mut DescriptorProto, scope: &str) {
+        let full_name = join(scope, msg.name.as_deref().unwrap_or_default());
+        let nodes = statement_nodes(body.decls().iter().filter_map(|d| match d {
+            MessageElement::Option(o) => Some(o),
+            _ => None
+        }));
+        self.options(decl, &mut msg.options, "google.protobuf.MessageOptions", &nodes, scope);
+        let (mut fi, mut ni, mut ei, mut xi, mut ri, mut oi) = (0, 0, 0, 0, 0, 0);
+        for decl in body.decls().iter() {
+            match decl {
+                MessageElement::Field(f) => {
+                    self.field_options(f, f.options(), msg.field.get_mut(fi), &full_name);
+                    fi += 1;
+                },
+                MessageElement::MapField(f) => {
+                    self.field_options(f, f.options(), msg.field.get_mut(fi), &full_name);
+                    fi += 1;
+                    ni += 1;
+                },
+                MessageElement::Group(g) => {
+                    self.field_options(g, g.options(), msg.field.get_mut(fi), &full_name);
+                    if let Some(nested) = msg.nested_type.get_mut(ni) {
+                        self.message(g, g.body(), nested, &full_name);
+                    }
+                    fi += 1;
+                    ni += 1;
+                },
+                MessageElement::OneOf(o) => {
+                    let nodes = statement_nodes(o.decls().iter().filter_map(|d| match d {
+                        OneOfElement::Option(o) => Some(o),
+                        _ => None
+                    }));
+                    if let Some(oneof) = msg.oneof_decl.get_mut(oi) {
+                        self.options(o, &mut oneof.options, "google.protobuf.OneofOptions", &nodes, &full_name);
+                    }
+                    oi += 1;
+                    for decl in o.decls().iter() {
+                        match decl {
+                            OneOfElement::Field(f) => {
+                                self.field_options(f, f.options(), msg.field.get_mut(fi), &full_name);
+                                fi += 1;
+                            },
+                            OneOfElement::Group(g) => {
+                                self.field_options(g, g.options(), msg.field.get_mut(fi), &full_name);
+                                if let Some(nested) = msg.nested_type.get_mut(ni) {
+                                    self.message(g, g.body(), nested, &full_name);
+                                }
+                                fi += 1;
+                                ni += 1;
+                            },
+                            _ => {}
+                        }
+                    }
+                },
+                MessageElement::Message(m) => {
+                    if let Some(nested) = msg.nested_type.get_mut(ni) {
+                        self.message(m, m.body(), nested, &full_name);
+                    }
+                    ni += 1;
+                },
+                MessageElement::Enum(e) => {
+                    if let Some(en) = msg.enum_type.get_mut(ei) {
+                        self.enum_type(e, en, &full_name);
+                    }
+                    ei += 1;
+                },
+                MessageElement::Extend(e) => self.extend(e, &full_name, &mut msg.extension, &mut xi, &mut msg.nested_type, &mut ni),
+                MessageElement::ExtensionRange(r) => {
+                    // The ranges of one declaration share its options, so
+                    // they are interpreted once and copied.
+                    let count = r.ranges().len();
+                    if let Some(range) = msg.extension_range.get_mut(ri) {
+                        let nodes = compact_nodes(r.options(), false);
+                        self.options(r, &mut range.options, "google.protobuf.ExtensionRangeOptions", &nodes, scope);
+                        let options = range.options.clone();
+                        for range in msg.extension_range.iter_mut().skip(ri + 1).take(count.saturating_sub(1)) {
+                            range.options = options.clone();
+                        }
+                    }
+                    ri += count;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn extend(&mut self, e: &ExtendNode, scope: &str, extensions: &mut [FieldDescriptorProto], xi: &mut usize, nested: &mut [DescriptorProto], ni: &mut usize) {
+        for decl in e.decls().iter() {
+            match decl {
+                ExtendElement::Field(f) => {
+                    self.field_options(f, f.options(), extensions.get_mut(*xi), scope);
+                    *xi += 1;
+                },
+                ExtendElement::Group(g) => {
+                    self.field_options(g, g.options(), extensions.get_mut(*xi), scope);
+                    if let Some(msg) = nested.get_mut(*ni) {
+                        self.message(g, g.body(), msg, scope);
+                    }
+                    *xi += 1;
+                    *ni += 1;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn enum_type(&mut self, e: &EnumNode, en: &mut EnumDescriptorProto, scope: &str) {
+        let nodes = statement_nodes(e.decls().iter().filter_map(|d| match d {
+            EnumElement::Option(o) => Some(o),
+            _ => None
+        }));
+        self.options(e, &mut en.options, "google.protobuf.EnumOptions", &nodes, scope);
+        let mut vi = 0;
+        for decl in e.decls().iter() {
+            if let EnumElement::Value(v) = decl {
+                if let Some(value) = en.value.get_mut(vi) {
+                    // Enum values are declared next to their enum, so their
+                    // options are resolved from the same scope.
+                    self.options(v, &mut value.options, "google.protobuf.EnumValueOptions", &compact_nodes(v.options(), false), scope);
+                }
+                vi += 1;
+            }
+        }
+    }
+
+    fn service(&mut self, s: &ServiceNode, service: &mut ServiceDescriptorProto, scope: &str) {
+        let full_name = join(scope, service.name.as_deref().unwrap_or_default());
+        let nodes = statement_nodes(s.decls().iter().filter_map(|d| match d {
+            ServiceElement::Option(o) => Some(o),
+            _ => None
+        }));
+        self.options(s, &mut service.options, "google.protobuf.ServiceOptions", &nodes, scope);
+        let mut mi = 0;
+        for decl in s.decls().iter() {
+            if let ServiceElement::RPC(r) = decl {
+                let nodes = statement_nodes(r.decls().iter().filter_map(|d| match d {
+                    MethodElement::Option(o) => Some(o),
+                    _ => None
+                }));
+                if let Some(method) = service.method.get_mut(mi) {
+                    self.options(r, &mut method.options, "google.protobuf.MethodOptions", &nodes, &full_name);
+                }
+                mi += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessor::MemoryAccessor;
+    use crate::compiler::Compiler;
+
+    const EXT_PROTO: &str = "syntax = \"proto2\";\npackage my;\nimport \"google/protobuf/descriptor.proto\";\nmessage Sub { optional int32 field = 1; repeated Sub child = 2; }\nextend google.protobuf.FileOptions { optional Sub ext = 50000; optional sint32 num = 50001; }\n";
+
+    fn compile(main: &str) -> Result<FileDescriptorProto, Vec<Error>> {
+        let mut accessor = MemoryAccessor::new();
+        accessor.insert("ext.proto", EXT_PROTO).insert("main.proto", main);
+        let mut c = Compiler::new(Box::new(accessor));
+        return c.compile("main.proto").map(|mut files| files.pop().unwrap())
+    }
+
+    #[test]
+    fn custom_options() {
+        let fd = compile("syntax = \"proto3\";\npackage my.app;\nimport \"ext.proto\";\noption java_package = \"a.b\";\noption (my.ext).field = 5;\noption (num) = -2;\noption (ext).child = { field: 6 };\n").unwrap();
+        let opts = fd.options.unwrap();
+        assert_eq!(opts.java_package.as_deref(), Some("a.b"));
+        assert!(opts.uninterpreted_option.is_empty());
+
+        let mut expected = Writer::new();
+        expected.string(1, &Some("a.b".to_string()));
+        let mut sub = Writer::new();
+        sub.tag(1, WireType::Varint);
+        sub.varint(5);
+        Scalar::Bytes(sub.into_bytes()).write(&mut expected, 50000);
+        Scalar::Varint(3).write(&mut expected, 50001);
+        let mut child = Writer::new();
+        child.tag(1, WireType::Varint);
+        child.varint(6);
+        let mut sub = Writer::new();
+        Scalar::Bytes(child.into_bytes()).write(&mut sub, 2);
+        Scalar::Bytes(sub.into_bytes()).write(&mut expected, 50000);
+        assert_eq!(opts.encode_to_vec(), expected.into_bytes());
+    }
+
+    #[test]
+    fn errors() {
+        let errors = compile("syntax = \"proto3\";\nimport \"ext.proto\";\noption (my.num) = 3000000000;\noption (my.ext).nope = 1;\noption (my.missing) = 1;\noption java_package = 1;\n").unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.message()).collect();
+        assert_eq!(messages, vec![
+            "value out of range for sint32 option \"(my.num)\"",
+            "\"my.Sub\" has no field named \"nope\"",
+            "\"my.missing\" is not defined",
+            "value must be a string for string option \"java_package\""
+        ]);
+    }
+
+    #[test]
+    fn failed_options_stay_uninterpreted() {
+        let mut linker = Linker::new();
+        let source = wellknown::source(DESCRIPTOR_PROTO).unwrap();
+        let descriptor = parse(DESCRIPTOR_PROTO, source).unwrap();
+        let dep = linker.link(&descriptor).unwrap();
+        let ext = parse("ext.proto", EXT_PROTO).unwrap();
+        let ext_fd = linker.link(&ext).unwrap();
+        let file = parse("main.proto", "import \"ext.proto\";\noption java_package = \"a.b\";\noption (my.num) = \"x\";\n").unwrap();
+        let mut fd = linker.link(&file).unwrap();
+        let errors = interpret_options(&file, &mut fd, &linker, &[&dep, &ext_fd]);
+        assert_eq!(errors.len(), 1);
+        let opts = fd.options.unwrap();
+        assert_eq!(opts.java_package.as_deref(), Some("a.b"));
+        assert_eq!(opts.uninterpreted_option.len(), 1);
+        assert_eq!(opts.uninterpreted_option[0].string_value.as_deref(), Some(&b"x"[..]));
+    }
+
+    #[test]
+    fn descriptors_that_do_not_match_the_source() {
+        let mut linker = Linker::new();
+        let source = wellknown::source(DESCRIPTOR_PROTO).unwrap();
+        let descriptor = parse(DESCRIPTOR_PROTO, source).unwrap();
+        let dep = linker.link(&descriptor).unwrap();
+        let file = parse("main.proto", "option java_package = \"a.b\";\nmessage M { option deprecated = true; }\n").unwrap();
+        let mut fd = linker.link(&file).unwrap();
+        fd.message_type[0].options = None;
+        // A varint that never ends.
+        fd.options.as_mut().unwrap().unknown_fields = vec![0x80];
+        let errors: Vec<String> = interpret_options(&file, &mut fd, &linker, &[&dep]).iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec![
+            "main.proto:1:1: could not decode the interpreted google.protobuf.FileOptions: invalid protobuf data at byte 4: unexpected end of data",
+            "main.proto:2:1: option count mismatch: 1 written, 0 uninterpreted in the descriptor"
+        ]);
+        assert_eq!(fd.options.unwrap().uninterpreted_option.len(), 1);
+    }
+}
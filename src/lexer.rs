@@ -0,0 +1,816 @@
+use crate::node::TokenInfo;
+use crate::source_pos::{SourcePos, PosRange, Comment};
+use crate::error::Error;
+
+/// The kind of a lexical token, along with its interpreted value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    Int(u64),
+    Float(f64),
+    /// The unescaped bytes of a string literal. Escapes such as `\xff`
+    /// may produce bytes that are not valid UTF-8.
+    String(Vec<u8>),
+    Rune(char),
+    Eof
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    kind: TokenKind,
+    info: TokenInfo
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, info: TokenInfo) -> Self {
+        return Token {
+            kind,
+            info
+        }
+    }
+
+    pub fn kind(&self) -> &TokenKind {
+        return &self.kind
+    }
+
+    pub fn info(&self) -> &TokenInfo {
+        return &self.info
+    }
+
+    pub fn into_info(self) -> TokenInfo {
+        return self.info
+    }
+
+    /// Returns true if this token is the given punctuation rune.
+    pub fn is_rune(&self, c: char) -> bool {
+        return self.kind == TokenKind::Rune(c)
+    }
+
+    /// Returns true if this token is an identifier spelled exactly `kw`.
+    pub fn is_ident(&self, kw: &str) -> bool {
+        match &self.kind {
+            TokenKind::Ident(s) => return s == kw,
+            _ => return false
+        }
+    }
+}
+
+/// Splits proto source into tokens. The last token is always
+/// `TokenKind::Eof`, which carries any comments and whitespace that
+/// follow the final real token.
+///
+/// Comments are attributed the same way protoc attributes them: a comment
+/// on the same line as the previous token, or a block of comments directly
+/// below it that is followed by a blank line, trails the previous token.
+/// Everything else leads the next token.
+pub fn lex(filename: &str, source: &str) -> Result<Vec<Token>, Error> {
+    let (tokens, mut errors) = lex_partial(filename, source);
+    if errors.is_empty() {
+        return Ok(tokens)
+    }
+    return Err(errors.remove(0))
+}
+
+/// Splits proto source into tokens, carrying on past errors the way
+/// protoc's tokenizer does. A malformed literal still becomes a token, and
+/// a character that can't start a token is skipped. Every error found is
+/// returned along with the tokens, in source order.
+pub fn lex_partial(filename: &str, source: &str) -> (Vec<Token>, Vec<Error>) {
+    let mut lexer = Lexer::new(filename, source);
+    let mut tokens: Vec<Token> = vec![];
+    loop {
+        let (gap, next) = lexer.next_with_comments(tokens.last());
+        if let Some(prev) = tokens.last_mut() {
+            prev.info.set_trailing_comments(gap.trailing);
+        }
+        let mut info = next.info;
+        info.set_leading_comments(gap.leading);
+        info.set_leading_whitespace(&gap.whitespace);
+        let eof = next.kind == TokenKind::Eof;
+        tokens.push(Token::new(next.kind, info));
+        if eof {
+            return (tokens, lexer.errors)
+        }
+    }
+}
+
+/// The comments and whitespace found between two tokens.
+struct Gap {
+    trailing: Vec<Comment>,
+    leading: Vec<Comment>,
+    whitespace: String
+}
+
+/// Groups comments between two tokens the way protoc's `CommentCollector`
+/// does. Only the indices of the comments are tracked since, unlike protoc,
+/// no comment is ever dropped: whatever does not trail the previous token
+/// leads the next one.
+struct CommentCollector {
+    comments: Vec<Comment>,
+    buffer_start: Option<usize>,
+    buffer_is_line: bool,
+    can_attach_to_prev: bool,
+    num_trailing: usize,
+    num_groups: usize
+}
+
+impl CommentCollector {
+    fn new() -> Self {
+        return CommentCollector {
+            comments: vec![],
+            buffer_start: None,
+            buffer_is_line: false,
+            can_attach_to_prev: true,
+            num_trailing: 0,
+            num_groups: 0
+        }
+    }
+
+    fn push_line_comment(&mut self, c: Comment) {
+        if self.buffer_start.is_some() && !self.buffer_is_line {
+            self.flush();
+        }
+        if self.buffer_start.is_none() {
+            self.buffer_start = Some(self.comments.len());
+        }
+        self.buffer_is_line = true;
+        self.comments.push(c);
+    }
+
+    fn push_block_comment(&mut self, c: Comment) {
+        self.flush();
+        self.buffer_start = Some(self.comments.len());
+        self.buffer_is_line = false;
+        self.comments.push(c);
+    }
+
+    fn flush(&mut self) {
+        if self.buffer_start.take().is_some() {
+            if self.can_attach_to_prev {
+                self.num_trailing = self.comments.len();
+                self.can_attach_to_prev = false;
+            }
+            self.num_groups += 1;
+        }
+    }
+
+    fn detach_from_prev(&mut self) {
+        self.can_attach_to_prev = false;
+    }
+
+    /// Drops the buffered comment without attributing it to the previous
+    /// token.
+    fn clear_buffer(&mut self) {
+        self.buffer_start = None;
+    }
+
+    fn maybe_detach_comment(&mut self) {
+        let mut count = self.num_groups;
+        if self.buffer_start.is_some() {
+            count += 1;
+        }
+        if count == 1 {
+            self.num_trailing = 0;
+            self.can_attach_to_prev = false;
+            self.flush();
+        }
+    }
+
+    fn into_gap(mut self, whitespace: String) -> Gap {
+        let leading = self.comments.split_off(self.num_trailing);
+        return Gap {
+            trailing: self.comments,
+            leading,
+            whitespace
+        }
+    }
+}
+
+enum CommentStart {
+    Line,
+    Block,
+    SlashNotComment,
+    None
+}
+
+struct Lexer<'a> {
+    filename: String,
+    src: &'a str,
+    offset: usize,
+    line: i32,
+    col: i32,
+    ws_start: usize,
+    errors: Vec<Error>
+}
+
+impl<'a> Lexer<'a> {
+    fn new(filename: &str, src: &'a str) -> Self {
+        return Lexer {
+            filename: filename.to_string(),
+            src,
+            offset: 0,
+            line: 1,
+            col: 1,
+            ws_start: 0,
+            errors: vec![]
+        }
+    }
+
+    fn pos(&self) -> SourcePos {
+        let mut p = SourcePos::new(&self.filename);
+        p.set_line(self.line).set_col(self.col).set_offset(self.offset as i32);
+        return p
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.src[self.offset..].chars().next()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        return self.src[self.offset..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        // Columns are counted the way protoc counts them: in bytes, with a
+        // tab advancing to the next multiple of 8.
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else if c == '\t' {
+            self.col += 8 - (self.col - 1) % 8;
+        } else {
+            self.col += c.len_utf8() as i32;
+        }
+        return Some(c)
+    }
+
+    fn try_consume(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            return true
+        }
+        return false
+    }
+
+    fn consume_whitespace_no_newline(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == ' ' || c == '\t' || c == '\r' || c == '\x0b' || c == '\x0c' || (c == '\u{feff}' && self.offset == 0) {
+                self.bump();
+            } else {
+                return
+            }
+        }
+    }
+
+    fn consume_whitespace(&mut self) {
+        loop {
+            self.consume_whitespace_no_newline();
+            if !self.try_consume('\n') {
+                return
+            }
+        }
+    }
+
+    fn error(&mut self, start: SourcePos, message: &str) {
+        let e = Error::syntax(PosRange { start, end: self.pos() }, message);
+        self.errors.push(e);
+    }
+
+    fn overflow(&mut self, start: SourcePos) {
+        let literal = self.src[start.offset() as usize..self.offset].to_string();
+        self.errors.push(Error::NumericOverflow { pos_range: PosRange { start, end: self.pos() }, literal });
+    }
+
+    fn try_consume_comment_start(&self) -> CommentStart {
+        if self.peek() != Some('/') {
+            return CommentStart::None
+        }
+        match self.peek_at(1) {
+            Some('/') => return CommentStart::Line,
+            Some('*') => return CommentStart::Block,
+            _ => return CommentStart::SlashNotComment
+        }
+    }
+
+    /// Builds a comment out of everything between `start` and the current
+    /// position, taking the whitespace that precedes it along.
+    fn finish_comment(&mut self, start: SourcePos) -> Comment {
+        let ws = &self.src[self.ws_start..start.offset() as usize];
+        let text = &self.src[start.offset() as usize..self.offset];
+        let mut c = Comment::new(PosRange { start, end: self.pos() });
+        c.set_leading_whitespace(ws).set_text(text);
+        self.ws_start = self.offset;
+        return c
+    }
+
+    /// Consumes a `//` comment up to, but not including, the newline.
+    fn consume_line_comment(&mut self) -> Comment {
+        let start = self.pos();
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break
+            }
+            self.bump();
+        }
+        return self.finish_comment(start)
+    }
+
+    fn consume_block_comment(&mut self) -> Comment {
+        let start = self.pos();
+        self.bump();
+        self.bump();
+        loop {
+            match self.bump() {
+                Some('*') if self.peek() == Some('/') => {
+                    self.bump();
+                    return self.finish_comment(start)
+                },
+                Some(_) => {},
+                None => {
+                    self.error(start.clone(), "block comment never terminates, unexpected EOF");
+                    return self.finish_comment(start)
+                }
+            }
+        }
+    }
+
+    /// Skips any comments and whitespace without attributing them, for
+    /// when protoc would give up on deciding which token they belong to.
+    fn skip_unattributed(&mut self, collector: &mut CommentCollector) {
+        loop {
+            self.consume_whitespace();
+            match self.try_consume_comment_start() {
+                CommentStart::Line => {
+                    let c = self.consume_line_comment();
+                    collector.comments.push(c);
+                },
+                CommentStart::Block => {
+                    let c = self.consume_block_comment();
+                    collector.comments.push(c);
+                },
+                _ => return
+            }
+        }
+    }
+
+    /// Reads the comments following `prev` and the token after them,
+    /// mirroring protoc's `Tokenizer::NextWithComments`.
+    fn next_with_comments(&mut self, prev: Option<&Token>) -> (Gap, Token) {
+        let mut collector = CommentCollector::new();
+        let prev_line = self.line;
+        let mut trailing_comment_end_line = -1;
+
+        match prev {
+            None => {
+                collector.detach_from_prev();
+            },
+            Some(_) => {
+                self.consume_whitespace_no_newline();
+                match self.try_consume_comment_start() {
+                    CommentStart::Line => {
+                        trailing_comment_end_line = self.line;
+                        let c = self.consume_line_comment();
+                        collector.push_line_comment(c);
+                        self.try_consume('\n');
+                        collector.flush();
+                    },
+                    CommentStart::Block => {
+                        let c = self.consume_block_comment();
+                        collector.push_block_comment(c);
+                        trailing_comment_end_line = self.line;
+                        self.consume_whitespace_no_newline();
+                        if !self.try_consume('\n') {
+                            collector.clear_buffer();
+                            self.skip_unattributed(&mut collector);
+                            return self.finish_gap(collector, None, None)
+                        }
+                        collector.flush();
+                    },
+                    CommentStart::SlashNotComment => {
+                        return self.finish_gap(collector, None, None)
+                    },
+                    CommentStart::None => {
+                        if !self.try_consume('\n') {
+                            self.skip_unattributed(&mut collector);
+                            return self.finish_gap(collector, None, None)
+                        }
+                    }
+                }
+            }
+        }
+
+        loop {
+            self.consume_whitespace_no_newline();
+            match self.try_consume_comment_start() {
+                CommentStart::Line => {
+                    let c = self.consume_line_comment();
+                    collector.push_line_comment(c);
+                    self.try_consume('\n');
+                },
+                CommentStart::Block => {
+                    let c = self.consume_block_comment();
+                    collector.push_block_comment(c);
+                    self.consume_whitespace_no_newline();
+                    self.try_consume('\n');
+                },
+                CommentStart::SlashNotComment => {
+                    return self.finish_gap(collector, None, None)
+                },
+                CommentStart::None => {
+                    if self.try_consume('\n') {
+                        collector.flush();
+                        collector.detach_from_prev();
+                    } else {
+                        return self.finish_gap(collector, Some(prev_line), Some(trailing_comment_end_line))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lexes the next token and settles the attribution of the comments
+    /// collected before it. `prev_line` and `trailing_end_line` are only
+    /// given when protoc would consider detaching a lone comment.
+    fn finish_gap(&mut self, mut collector: CommentCollector, prev_line: Option<i32>, trailing_end_line: Option<i32>) -> (Gap, Token) {
+        // A character that can't start a token is skipped along with the
+        // whitespace and comments after it, and kept in the whitespace.
+        let tok = loop {
+            match self.next_token() {
+                Some(tok) => break tok,
+                None => self.skip_unattributed(&mut collector)
+            }
+        };
+        let whitespace = self.src[self.ws_start..tok.info.pos_range().start.offset() as usize].to_string();
+        self.ws_start = self.offset;
+        if let Some(prev_line) = prev_line {
+            let end_of_scope = matches!(tok.kind, TokenKind::Eof | TokenKind::Rune('}') | TokenKind::Rune(']') | TokenKind::Rune(')'));
+            if end_of_scope {
+                collector.flush();
+            }
+            let line = tok.info.pos_range().start.line();
+            if tok.kind != TokenKind::Eof && (prev_line == line || trailing_end_line == Some(line)) {
+                collector.maybe_detach_comment();
+            }
+        }
+        return (collector.into_gap(whitespace), tok)
+    }
+
+    /// Lexes the next token, or returns `None` after skipping a character
+    /// that can't start one.
+    fn next_token(&mut self) -> Option<Token> {
+        let start = self.pos();
+        let c = match self.peek() {
+            None => return Some(self.make_token(TokenKind::Eof, start)),
+            Some(c) => c
+        };
+        if c.is_ascii_alphabetic() || c == '_' {
+            while let Some(c) = self.peek() {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    self.bump();
+                } else {
+                    break
+                }
+            }
+            let ident = self.src[start.offset() as usize..self.offset].to_string();
+            return Some(self.make_token(TokenKind::Ident(ident), start))
+        }
+        if c.is_ascii_digit() || (c == '.' && self.peek_at(1).is_some_and(|d| d.is_ascii_digit())) {
+            let kind = self.consume_number(start.clone());
+            return Some(self.make_token(kind, start))
+        }
+        if c == '"' || c == '\'' {
+            let kind = self.consume_string(start.clone(), c);
+            return Some(self.make_token(kind, start))
+        }
+        if c.is_ascii_graphic() {
+            self.bump();
+            return Some(self.make_token(TokenKind::Rune(c), start))
+        }
+        self.bump();
+        self.error(start, &format!("invalid character {:?}", c));
+        return None
+    }
+
+    fn make_token(&self, kind: TokenKind, start: SourcePos) -> Token {
+        let raw = &self.src[start.offset() as usize..self.offset];
+        let mut info = TokenInfo::new(PosRange { start, end: self.pos() });
+        info.set_raw_text(raw);
+        return Token::new(kind, info)
+    }
+
+    fn consume_digits(&mut self, radix: u32) {
+        while let Some(c) = self.peek() {
+            if c.is_digit(radix) {
+                self.bump();
+            } else {
+                return
+            }
+        }
+    }
+
+    /// Consumes a numeric literal. A malformed one is reported and read as
+    /// zero, so it can still stand where a number is expected.
+    fn consume_number(&mut self, start: SourcePos) -> TokenKind {
+        let mut is_float = false;
+        if self.peek() == Some('0') && matches!(self.peek_at(1), Some('x') | Some('X')) {
+            self.bump();
+            self.bump();
+            self.consume_digits(16);
+            let raw = &self.src[start.offset() as usize + 2..self.offset];
+            if raw.is_empty() {
+                self.error(start, "invalid hexadecimal integer literal");
+                return TokenKind::Int(0)
+            }
+            let value = u64::from_str_radix(raw, 16);
+            if !self.check_number_end(&start) {
+                return TokenKind::Int(0)
+            }
+            match value {
+                Ok(v) => return TokenKind::Int(v),
+                Err(_) => {
+                    self.overflow(start);
+                    return TokenKind::Int(0)
+                }
+            }
+        }
+        let is_octal = self.peek() == Some('0');
+        self.consume_digits(10);
+        if self.peek() == Some('.') {
+            is_float = true;
+            self.bump();
+            self.consume_digits(10);
+        }
+        if matches!(self.peek(), Some('e') | Some('E')) {
+            let sign_or_digit = self.peek_at(1);
+            let digit_after_sign = self.peek_at(2);
+            let has_exponent = match sign_or_digit {
+                Some(d) if d.is_ascii_digit() => true,
+                Some('+') | Some('-') => digit_after_sign.is_some_and(|d| d.is_ascii_digit()),
+                _ => false
+            };
+            if !has_exponent {
+                self.bump();
+                self.error(start, "invalid float literal: exponent has no digits");
+                return TokenKind::Float(0.0)
+            }
+            is_float = true;
+            self.bump();
+            if matches!(self.peek(), Some('+') | Some('-')) {
+                self.bump();
+            }
+            self.consume_digits(10);
+        }
+        let raw = &self.src[start.offset() as usize..self.offset];
+        let is_octal = is_octal && raw.len() > 1 && !is_float;
+        let octal = if is_octal { Some((u64::from_str_radix(&raw[1..], 8), raw[1..].chars().all(|c| c.is_digit(8)))) } else { None };
+        let float = raw.parse::<f64>();
+        let int = raw.parse::<u64>();
+        if !self.check_number_end(&start) {
+            return if is_float { TokenKind::Float(0.0) } else { TokenKind::Int(0) }
+        }
+        if is_float {
+            match float {
+                Ok(f) => return TokenKind::Float(f),
+                Err(_) => {
+                    self.error(start, "invalid float literal");
+                    return TokenKind::Float(0.0)
+                }
+            }
+        }
+        match octal {
+            Some((Ok(v), _)) => return TokenKind::Int(v),
+            Some((Err(_), true)) => {
+                self.overflow(start);
+                return TokenKind::Int(0)
+            },
+            Some((Err(_), false)) => {
+                self.error(start, "numbers starting with leading zero must be in octal");
+                return TokenKind::Int(0)
+            },
+            None => {}
+        }
+        match int {
+            Ok(v) => return TokenKind::Int(v),
+            // Decimal values too big for uint64 are still usable as floats.
+            Err(_) => return TokenKind::Float(float.unwrap_or(f64::INFINITY))
+        }
+    }
+
+    /// Checks that a number isn't run together with what follows it,
+    /// returning false after reporting it if it is.
+    fn check_number_end(&mut self, start: &SourcePos) -> bool {
+        if let Some(c) = self.peek() {
+            if c == '.' {
+                self.bump();
+                self.error(start.clone(), "invalid numeric literal: unexpected '.'");
+                return false
+            }
+            if c.is_ascii_alphanumeric() || c == '_' {
+                self.bump();
+                self.error(start.clone(), "need space between number and identifier");
+                return false
+            }
+        }
+        return true
+    }
+
+    /// Consumes a string literal. An unterminated string ends at the end
+    /// of its line, and a bad escape or character is reported and left
+    /// out.
+    fn consume_string(&mut self, start: SourcePos, quote: char) -> TokenKind {
+        self.bump();
+        let mut buf: Vec<u8> = vec![];
+        loop {
+            let esc_start = self.pos();
+            // The newline ending an unterminated string is left for the
+            // next token.
+            let c = match self.peek() {
+                None => {
+                    self.error(start, "unexpected EOF in string literal");
+                    return TokenKind::String(buf)
+                },
+                Some('\n') => {
+                    self.error(start, "string literals cannot cross line boundaries");
+                    return TokenKind::String(buf)
+                },
+                Some(c) => c
+            };
+            self.bump();
+            match c {
+                c if c == quote => return TokenKind::String(buf),
+                '\\' => self.consume_escape(esc_start, &mut buf),
+                '\0' => self.error(esc_start, "null character not allowed in string literal"),
+                c => {
+                    let mut tmp = [0u8; 4];
+                    buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+                }
+            }
+        }
+    }
+
+    fn consume_escape(&mut self, start: SourcePos, buf: &mut Vec<u8>) {
+        // The end of the line or file is left for the string to report.
+        let c = match self.peek() {
+            None | Some('\n') => return,
+            Some(_) => self.bump().unwrap_or_default()
+        };
+        match c {
+            'a' => buf.push(0x07),
+            'b' => buf.push(0x08),
+            'f' => buf.push(0x0c),
+            'n' => buf.push(b'\n'),
+            'r' => buf.push(b'\r'),
+            't' => buf.push(b'\t'),
+            'v' => buf.push(0x0b),
+            '\\' => buf.push(b'\\'),
+            '\'' => buf.push(b'\''),
+            '"' => buf.push(b'"'),
+            '?' => buf.push(b'?'),
+            '0'..='7' => {
+                let mut v = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match self.peek().and_then(|d| d.to_digit(8)) {
+                        Some(d) => {
+                            self.bump();
+                            v = v*8 + d;
+                        },
+                        None => break
+                    }
+                }
+                if v > 0xff {
+                    self.error(start, "octal escape is out of range");
+                    return
+                }
+                buf.push(v as u8);
+            },
+            'x' | 'X' => {
+                let mut v = 0u32;
+                let mut n = 0;
+                while n < 2 {
+                    match self.peek().and_then(|d| d.to_digit(16)) {
+                        Some(d) => {
+                            self.bump();
+                            v = v*16 + d;
+                            n += 1;
+                        },
+                        None => break
+                    }
+                }
+                if n == 0 {
+                    self.error(start, "invalid hex escape");
+                    return
+                }
+                buf.push(v as u8);
+            },
+            'u' | 'U' => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let mut v = 0u32;
+                for _ in 0..len {
+                    match self.peek().and_then(|d| d.to_digit(16)) {
+                        Some(d) => {
+                            self.bump();
+                            v = v.wrapping_mul(16).wrapping_add(d);
+                        },
+                        None => {
+                            self.error(start, "invalid unicode escape");
+                            return
+                        }
+                    }
+                }
+                match char::from_u32(v) {
+                    Some(ch) => {
+                        let mut tmp = [0u8; 4];
+                        buf.extend_from_slice(ch.encode_utf8(&mut tmp).as_bytes());
+                    },
+                    None => self.error(start, "unicode escape is out of range")
+                }
+            },
+            _ => self.error(start, &format!("invalid escape sequence \\{}", c))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns the leading and trailing comments of the `n`th token spelled
+    // `raw`.
+    fn comments(tokens: &[Token], raw: &str, n: usize) -> (Vec<String>, Vec<String>) {
+        let tok = tokens.iter().filter(|t| t.info().raw_text() == raw).nth(n).unwrap();
+        let leading = tok.info().leading_comments().iter().map(|c| c.text().to_string()).collect();
+        let trailing = tok.info().trailing_comments().iter().map(|c| c.text().to_string()).collect();
+        return (leading, trailing)
+    }
+
+    #[test]
+    fn token_kinds() {
+        let tokens = lex("a.proto", "foo.bar 0x1F 017 1.5e3 \"a\\x41\\n\" {").unwrap();
+        let kinds: Vec<&TokenKind> = tokens.iter().map(|t| t.kind()).collect();
+        assert_eq!(kinds, vec![
+            &TokenKind::Ident("foo".to_string()),
+            &TokenKind::Rune('.'),
+            &TokenKind::Ident("bar".to_string()),
+            &TokenKind::Int(31),
+            &TokenKind::Int(15),
+            &TokenKind::Float(1500.0),
+            &TokenKind::String(b"aA\n".to_vec()),
+            &TokenKind::Rune('{'),
+            &TokenKind::Eof
+        ]);
+    }
+
+    #[test]
+    fn tokens_keep_their_whitespace_and_position() {
+        let tokens = lex("a.proto", "a\n   b").unwrap();
+        let b = tokens[1].info();
+        assert_eq!(b.leading_whitespace(), "\n   ");
+        assert_eq!((b.pos_range().start.line(), b.pos_range().start.col()), (2, 4));
+    }
+
+    #[test]
+    fn comment_on_the_same_line_trails() {
+        let tokens = lex("a.proto", "int32 a = 1; // about a\nint32 b = 2;\n").unwrap();
+        assert_eq!(comments(&tokens, ";", 0), (vec![], vec!["// about a".to_string()]));
+        assert_eq!(comments(&tokens, "int32", 1), (vec![], vec![]));
+    }
+
+    #[test]
+    fn comment_on_the_next_line_trails_when_followed_by_blank_line() {
+        let tokens = lex("a.proto", "int32 a = 1;\n// about a\n\nint32 b = 2;\n").unwrap();
+        assert_eq!(comments(&tokens, ";", 0), (vec![], vec!["// about a".to_string()]));
+        assert_eq!(comments(&tokens, "int32", 1), (vec![], vec![]));
+    }
+
+    #[test]
+    fn comment_on_the_next_line_leads_the_next_token() {
+        let tokens = lex("a.proto", "int32 a = 1;\n// about b\nint32 b = 2;\n").unwrap();
+        assert_eq!(comments(&tokens, ";", 0), (vec![], vec![]));
+        assert_eq!(comments(&tokens, "int32", 1), (vec!["// about b".to_string()], vec![]));
+    }
+
+    #[test]
+    fn detached_comment_leads_the_next_token() {
+        let tokens = lex("a.proto", "int32 a = 1;\n\n/* detached */\n\n// about b\nint32 b = 2;\n").unwrap();
+        assert_eq!(comments(&tokens, ";", 0), (vec![], vec![]));
+        let leading = vec!["/* detached */".to_string(), "// about b".to_string()];
+        assert_eq!(comments(&tokens, "int32", 1), (leading, vec![]));
+    }
+
+    #[test]
+    fn errors_do_not_stop_lexing() {
+        let (tokens, errors) = lex_partial("a.proto", "x = 0x; \u{1} y = \"a\\qb\"; z = 1e;\n");
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "a.proto:1:5: invalid hexadecimal integer literal",
+            "a.proto:1:9: invalid character '\\u{1}'",
+            "a.proto:1:17: invalid escape sequence \\q",
+            "a.proto:1:27: invalid float literal: exponent has no digits"
+        ]);
+        let kinds: Vec<&TokenKind> = tokens.iter().map(|t| t.kind()).collect();
+        assert_eq!(kinds[6], &TokenKind::String(b"ab".to_vec()));
+        assert_eq!(kinds.last(), Some(&&TokenKind::Eof));
+    }
+}
@@ -0,0 +1,36 @@
+//! An abstract syntax tree for protocol buffer source files.
+//!
+//! Every node keeps the positions, whitespace and comments of the tokens
+//! it was built from so that tools can report precise locations and
+//! reproduce the original source.
+
+#![allow(clippy::needless_return)]
+
+pub mod error;
+pub mod diagnostic;
+pub mod emit;
+pub mod node;
+pub mod source_pos;
+pub mod values;
+pub mod identifiers;
+pub mod options;
+pub mod enums;
+pub mod message;
+pub mod field;
+pub mod oneof;
+pub mod service;
+pub mod ranges;
+pub mod lexer;
+pub mod file;
+pub mod parser;
+pub mod printer;
+pub mod format;
+pub mod visit;
+pub mod descriptor;
+pub mod compile;
+pub mod wire;
+pub mod linker;
+pub mod accessor;
+pub mod compiler;
+pub mod wellknown;
+pub mod interpret;
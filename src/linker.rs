@@ -0,0 +1,722 @@
+//! Links files into a table of fully qualified names and resolves the type
+//! names each file refers to.
+//!
+//! Relative names are resolved as protoc does: the scope the name is used
+//! in is searched first, then each enclosing scope out to the root. Only
+//! the first part of a dotted name is looked up that way; the rest must be
+//! found inside whatever the first part matched. Enum values are declared
+//! next to their enum rather than inside it.
+
+use std::collections::{HashMap, HashSet};
+use crate::compile::{compile_partial, map_entry_name, pos_range, scalar_type};
+use crate::descriptor::*;
+use crate::enums::{EnumElement, EnumNode};
+use crate::error::Error;
+use crate::field::FieldNode;
+use crate::file::{FileElement, FileNode};
+use crate::identifiers::{IdentNode, IdentValueNodeTrait, _IdentValueNodeTrait};
+use crate::message::{ExtendElement, ExtendNode, MessageBody, MessageElement};
+use crate::node::NodeTrait;
+use crate::oneof::OneOfElement;
+use crate::options::OptionNode;
+use crate::service::{ServiceElement, ServiceNode};
+use crate::source_pos::PosRange;
+use crate::values::{IntValueNodeTrait, ValueNode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Package,
+    Message,
+    Enum,
+    EnumValue,
+    Field,
+    Extension,
+    OneOf,
+    Service,
+    Method
+}
+
+impl SymbolKind {
+    /// Returns whether other symbols can be declared inside this one.
+    pub fn is_aggregate(&self) -> bool {
+        return matches!(self, SymbolKind::Package | SymbolKind::Message | SymbolKind::Enum | SymbolKind::Service)
+    }
+
+    pub fn is_type(&self) -> bool {
+        return matches!(self, SymbolKind::Message | SymbolKind::Enum)
+    }
+}
+
+/// A declaration in the symbol table. A package declared by several files
+/// is kept once, at its first declaration.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    full_name: String,
+    kind: SymbolKind,
+    pos_range: PosRange
+}
+
+impl Symbol {
+    /// Returns the fully qualified name, without a leading dot.
+    pub fn full_name(&self) -> &str {
+        return &self.full_name
+    }
+
+    pub fn kind(&self) -> SymbolKind {
+        return self.kind
+    }
+
+    /// Returns the range of the declared name.
+    pub fn pos_range(&self) -> &PosRange {
+        return &self.pos_range
+    }
+
+    pub fn file(&self) -> &str {
+        return self.pos_range.start.filename()
+    }
+}
+
+/// A type name in the source and the declaration it was resolved to.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pos_range: PosRange,
+    name: String,
+    target: String
+}
+
+impl Reference {
+    pub fn pos_range(&self) -> &PosRange {
+        return &self.pos_range
+    }
+
+    /// Returns the name as written.
+    pub fn name(&self) -> &str {
+        return &self.name
+    }
+
+    /// Returns the fully qualified name of the declaration.
+    pub fn target(&self) -> &str {
+        return &self.target
+    }
+}
+
+// Why a name could not be resolved.
+enum Miss {
+    NotFound,
+    NotImported(String),
+    Hidden(String)
+}
+
+/// Links files one at a time, each after the files it imports, keeping the
+/// symbols of all of them.
+#[derive(Debug, Clone, Default)]
+pub struct Linker {
+    symbols: HashMap<String, Symbol>,
+    // The files that declare each package, including the packages that
+    // enclose it.
+    packages: HashMap<String, HashSet<String>>,
+    // The `import public` targets of each linked file.
+    public_imports: HashMap<String, Vec<String>>,
+    // The files whose symbols each linked file can use: itself, its
+    // imports and what those import publicly.
+    visible: HashMap<String, HashSet<String>>,
+    references: Vec<Reference>,
+    by_pos: HashMap<(String, i32), usize>,
+    // The extension ranges of each message, with exclusive ends.
+    extension_ranges: HashMap<String, Vec<(i32, i32)>>,
+    // The value names of each enum.
+    enum_values: HashMap<String, Vec<String>>
+}
+
+struct FileLinker<'a> {
+    linker: &'a mut Linker,
+    file: String,
+    errors: Vec<Error>
+}
+
+fn join(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        return name.to_string()
+    }
+    return format!("{}.{}", scope, name)
+}
+
+fn parent(scope: &str) -> &str {
+    match scope.rfind('.') {
+        Some(i) => return &scope[..i],
+        None => return ""
+    }
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        return Default::default()
+    }
+
+    /// Compiles `file`, adds its declarations to the symbol table and
+    /// returns its descriptor with every type name fully qualified. The
+    /// files it imports must have been linked already. The symbols of a
+    /// file are kept even when it has errors.
+    pub fn link(&mut self, file: &FileNode) -> Result<FileDescriptorProto, Vec<Error>> {
+        let (mut fd, errors) = compile_partial(file);
+        let mut fl = FileLinker {
+            linker: self,
+            file: file.start().filename().to_string(),
+            errors
+        };
+        fl.imports(file);
+        fl.linker.add_ranges(&fd);
+        fl.declare_file(file);
+        fl.resolve_file(file);
+        fl.linker.link_descriptor(&fl.file, &mut fd);
+        if fl.errors.is_empty() {
+            return Ok(fd)
+        }
+        return Err(fl.errors)
+    }
+
+    /// Returns the symbol with the given fully qualified name, with or
+    /// without a leading dot.
+    pub fn symbol(&self, full_name: &str) -> Option<&Symbol> {
+        return self.symbols.get(full_name.strip_prefix('.').unwrap_or(full_name))
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        return self.symbols.values()
+    }
+
+    /// Returns every type name resolved so far, in the order the files were
+    /// linked.
+    pub fn references(&self) -> &[Reference] {
+        return &self.references
+    }
+
+    /// Returns the declaration a type name in the source refers to.
+    pub fn declaration_of(&self, node: &dyn NodeTrait) -> Option<&Symbol> {
+        let key = (node.start().filename().to_string(), node.start().offset());
+        let reference = &self.references[*self.by_pos.get(&key)?];
+        return self.symbols.get(&reference.target)
+    }
+
+    /// Resolves `name` as used in `file` inside `scope`, the fully qualified
+    /// name of the enclosing message, service or package.
+    pub fn lookup(&self, file: &str, scope: &str, name: &str) -> Option<&Symbol> {
+        return self.resolve(file, scope, name, false).ok()
+    }
+
+    fn is_visible(&self, file: &str, symbol: &Symbol) -> bool {
+        let visible = match self.visible.get(file) {
+            Some(v) => v,
+            None => return false
+        };
+        if symbol.kind == SymbolKind::Package {
+            return self.packages[&symbol.full_name].iter().any(|f| visible.contains(f))
+        }
+        return visible.contains(symbol.file())
+    }
+
+    fn find(&self, file: &str, full_name: &str, not_imported: &mut Option<String>) -> Option<&Symbol> {
+        let symbol = self.symbols.get(full_name)?;
+        if self.is_visible(file, symbol) {
+            return Some(symbol)
+        }
+        if not_imported.is_none() {
+            *not_imported = Some(symbol.file().to_string());
+        }
+        return None
+    }
+
+    fn resolve(&self, file: &str, scope: &str, name: &str, types_only: bool) -> Result<&Symbol, Miss> {
+        let mut not_imported = None;
+        let mut hidden = None;
+        if let Some(full_name) = name.strip_prefix('.') {
+            if let Some(symbol) = self.find(file, full_name, &mut not_imported) {
+                return Ok(symbol)
+            }
+        } else {
+            let first = name.split('.').next().unwrap_or(name);
+            let mut scope = scope;
+            loop {
+                if let Some(symbol) = self.find(file, &join(scope, first), &mut not_imported) {
+                    if first.len() < name.len() {
+                        if symbol.kind.is_aggregate() {
+                            let full_name = join(scope, name);
+                            if let Some(symbol) = self.find(file, &full_name, &mut not_imported) {
+                                return Ok(symbol)
+                            }
+                            hidden = Some(full_name);
+                            break
+                        }
+                    } else if !types_only || symbol.kind.is_type() {
+                        return Ok(symbol)
+                    }
+                }
+                if scope.is_empty() {
+                    break
+                }
+                scope = parent(scope);
+            }
+        }
+        if let Some(f) = not_imported {
+            return Err(Miss::NotImported(f))
+        }
+        if let Some(full_name) = hidden {
+            return Err(Miss::Hidden(full_name))
+        }
+        return Err(Miss::NotFound)
+    }
+
+    // Keeps the extension ranges of the messages of a descriptor and the
+    // values of its enums, which extensions and defaults are checked
+    // against.
+    fn add_ranges(&mut self, fd: &FileDescriptorProto) {
+        let package = fd.package.clone().unwrap_or_default();
+        self.add_message_ranges(&package, &fd.message_type);
+        self.add_enum_values(&package, &fd.enum_type);
+    }
+
+    fn add_message_ranges(&mut self, scope: &str, messages: &[DescriptorProto]) {
+        for msg in messages.iter() {
+            let full_name = join(scope, msg.name.as_deref().unwrap_or_default());
+            let ranges = msg.extension_range.iter().map(|r| (r.start.unwrap_or_default(), r.end.unwrap_or_default())).collect();
+            self.extension_ranges.insert(full_name.clone(), ranges);
+            self.add_message_ranges(&full_name, &msg.nested_type);
+            self.add_enum_values(&full_name, &msg.enum_type);
+        }
+    }
+
+    fn add_enum_values(&mut self, scope: &str, enums: &[EnumDescriptorProto]) {
+        for en in enums.iter() {
+            let values = en.value.iter().filter_map(|v| v.name.clone()).collect();
+            self.enum_values.insert(join(scope, en.name.as_deref().unwrap_or_default()), values);
+        }
+    }
+
+    // Rewrites the type names of a descriptor as fully qualified names and
+    // sets the types of fields that name messages and enums. Names that
+    // don't resolve are left as they are; the errors for them are reported
+    // from the source.
+    fn link_descriptor(&self, file: &str, fd: &mut FileDescriptorProto) {
+        let package = fd.package.clone().unwrap_or_default();
+        self.link_messages(file, &package, &mut fd.message_type);
+        self.link_fields(file, &package, &mut fd.extension);
+        for service in fd.service.iter_mut() {
+            let scope = join(&package, service.name.as_deref().unwrap_or_default());
+            for method in service.method.iter_mut() {
+                for name in method.input_type.iter_mut().chain(method.output_type.iter_mut()) {
+                    if let Ok(symbol) = self.resolve(file, &scope, name, false) {
+                        if symbol.kind == SymbolKind::Message {
+                            *name = format!(".{}", symbol.full_name);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn link_messages(&self, file: &str, scope: &str, messages: &mut [DescriptorProto]) {
+        for msg in messages.iter_mut() {
+            let scope = join(scope, msg.name.as_deref().unwrap_or_default());
+            self.link_fields(file, &scope, &mut msg.field);
+            self.link_fields(file, &scope, &mut msg.extension);
+            self.link_messages(file, &scope, &mut msg.nested_type);
+        }
+    }
+
+    fn link_fields(&self, file: &str, scope: &str, fields: &mut [FieldDescriptorProto]) {
+        for field in fields.iter_mut() {
+            if let Some(name) = &field.type_name {
+                if let Ok(symbol) = self.resolve(file, scope, name, true) {
+                    if field.r#type.is_none() && symbol.kind == SymbolKind::Enum {
+                        field.r#type = Some(FieldType::Enum);
+                    } else if field.r#type.is_none() && symbol.kind == SymbolKind::Message {
+                        field.r#type = Some(FieldType::Message);
+                    }
+                    if symbol.kind.is_type() {
+                        field.type_name = Some(format!(".{}", symbol.full_name));
+                    }
+                }
+            }
+            if let Some(name) = &field.extendee {
+                if let Ok(symbol) = self.resolve(file, scope, name, false) {
+                    if symbol.kind == SymbolKind::Message {
+                        field.extendee = Some(format!(".{}", symbol.full_name));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FileLinker<'_> {
+    // Records what the file imports and works out which files it can see.
+    fn imports(&mut self, file: &FileNode) {
+        let mut visible = HashSet::from([self.file.clone()]);
+        let mut public = vec![];
+        for decl in file.decls().iter() {
+            if let FileElement::Import(i) = decl {
+                let dep = i.name().as_string();
+                if !self.linker.visible.contains_key(&dep) {
+                    self.errors.push(Error::invalid(pos_range(i.name()), &format!("import \"{}\" was not found or had errors", dep)));
+                    continue
+                }
+                if i.is_public() {
+                    public.push(dep.clone());
+                }
+                let mut pending = vec![dep];
+                while let Some(f) = pending.pop() {
+                    if visible.insert(f.clone()) {
+                        pending.extend(self.linker.public_imports.get(&f).into_iter().flatten().cloned());
+                    }
+                }
+            }
+        }
+        self.linker.public_imports.insert(self.file.clone(), public);
+        self.linker.visible.insert(self.file.clone(), visible);
+    }
+
+    fn declare(&mut self, full_name: String, kind: SymbolKind, node: &dyn NodeTrait) {
+        if kind == SymbolKind::Package {
+            self.linker.packages.entry(full_name.clone()).or_default().insert(self.file.clone());
+        }
+        let pos_range = pos_range(node);
+        match self.linker.symbols.get(&full_name) {
+            Some(prev) if prev.kind == SymbolKind::Package && kind == SymbolKind::Package => {},
+            Some(prev) => {
+                let previous = Box::new(prev.pos_range.clone());
+                self.errors.push(Error::Duplicate { pos_range, name: full_name, previous });
+            },
+            None => {
+                self.linker.symbols.insert(full_name.clone(), Symbol { full_name, kind, pos_range });
+            }
+        }
+    }
+
+    fn declare_file(&mut self, file: &FileNode) {
+        let mut package = String::new();
+        for decl in file.decls().iter() {
+            if let FileElement::Package(p) = decl {
+                package = p.name().as_identifier();
+                for (i, _) in package.match_indices('.') {
+                    self.declare(package[..i].to_string(), SymbolKind::Package, p.name());
+                }
+                self.declare(package.clone(), SymbolKind::Package, p.name());
+            }
+        }
+        for decl in file.decls().iter() {
+            match decl {
+                FileElement::Message(m) => self.declare_message(&package, m.name(), m.body()),
+                FileElement::Enum(e) => self.declare_enum(&package, e),
+                FileElement::Extend(e) => self.declare_extend(&package, e),
+                FileElement::Service(s) => self.declare_service(&package, s),
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_message(&mut self, scope: &str, name: &IdentNode, body: &MessageBody) {
+        let scope = join(scope, &name.as_identifier());
+        self.declare(scope.clone(), SymbolKind::Message, name);
+        for decl in body.decls().iter() {
+            match decl {
+                MessageElement::Field(f) => self.declare(join(&scope, &f.name().as_identifier()), SymbolKind::Field, f.name()),
+                MessageElement::MapField(f) => {
+                    let name = f.name().as_identifier();
+                    self.declare(join(&scope, &name), SymbolKind::Field, f.name());
+                    self.declare(join(&scope, &map_entry_name(&name)), SymbolKind::Message, f.name());
+                },
+                MessageElement::Group(g) => {
+                    self.declare(join(&scope, &g.name().as_identifier().to_ascii_lowercase()), SymbolKind::Field, g.name());
+                    self.declare_message(&scope, g.name(), g.body());
+                },
+                MessageElement::OneOf(o) => {
+                    self.declare(join(&scope, &o.name().as_identifier()), SymbolKind::OneOf, o.name());
+                    for decl in o.decls().iter() {
+                        match decl {
+                            OneOfElement::Field(f) => self.declare(join(&scope, &f.name().as_identifier()), SymbolKind::Field, f.name()),
+                            OneOfElement::Group(g) => {
+                                self.declare(join(&scope, &g.name().as_identifier().to_ascii_lowercase()), SymbolKind::Field, g.name());
+                                self.declare_message(&scope, g.name(), g.body());
+                            },
+                            _ => {}
+                        }
+                    }
+                },
+                MessageElement::Message(m) => self.declare_message(&scope, m.name(), m.body()),
+                MessageElement::Enum(e) => self.declare_enum(&scope, e),
+                MessageElement::Extend(e) => self.declare_extend(&scope, e),
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_enum(&mut self, scope: &str, e: &EnumNode) {
+        self.declare(join(scope, &e.name().as_identifier()), SymbolKind::Enum, e.name());
+        for decl in e.decls().iter() {
+            if let EnumElement::Value(v) = decl {
+                self.declare(join(scope, &v.name().as_identifier()), SymbolKind::EnumValue, v.name());
+            }
+        }
+    }
+
+    fn declare_extend(&mut self, scope: &str, e: &ExtendNode) {
+        for decl in e.decls().iter() {
+            match decl {
+                ExtendElement::Field(f) => self.declare(join(scope, &f.name().as_identifier()), SymbolKind::Extension, f.name()),
+                ExtendElement::Group(g) => {
+                    self.declare(join(scope, &g.name().as_identifier().to_ascii_lowercase()), SymbolKind::Extension, g.name());
+                    self.declare_message(scope, g.name(), g.body());
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_service(&mut self, scope: &str, s: &ServiceNode) {
+        let scope = join(scope, &s.name().as_identifier());
+        self.declare(scope.clone(), SymbolKind::Service, s.name());
+        for decl in s.decls().iter() {
+            if let ServiceElement::RPC(r) = decl {
+                self.declare(join(&scope, &r.name().as_identifier()), SymbolKind::Method, r.name());
+            }
+        }
+    }
+
+    // Resolves a type name in the source, reporting it if it can't be
+    // resolved or is the wrong kind of symbol. Field types may be messages
+    // or enums; everything else must be a message. Returns the fully
+    // qualified name of the type.
+    fn reference(&mut self, scope: &str, node: &dyn IdentValueNodeTrait, field_type: bool) -> Option<String> {
+        let name = node.as_identifier();
+        if field_type && scalar_type(&name).is_some() {
+            return None
+        }
+        let pos_range = pos_range(node);
+        let symbol = match self.linker.resolve(&self.file, scope, &name, field_type) {
+            Ok(symbol) => symbol,
+            Err(Miss::NotFound) => {
+                self.errors.push(Error::Unresolved { pos_range, name, hint: None });
+                return None
+            },
+            Err(Miss::NotImported(f)) => {
+                let hint = format!("it is declared in \"{}\", which is not imported", f);
+                self.errors.push(Error::Unresolved { pos_range, name, hint: Some(hint.into()) });
+                return None
+            },
+            Err(Miss::Hidden(resolved_to)) => {
+                self.errors.push(Error::Ambiguous { pos_range, name, resolved_to: resolved_to.into() });
+                return None
+            }
+        };
+        if field_type && !symbol.kind.is_type() {
+            self.errors.push(Error::invalid(pos_range, &format!("\"{}\" is not a type", name)));
+            return None
+        }
+        if !field_type && symbol.kind != SymbolKind::Message {
+            self.errors.push(Error::invalid(pos_range, &format!("\"{}\" is not a message type", name)));
+            return None
+        }
+        let target = symbol.full_name.clone();
+        let key = (self.file.clone(), pos_range.start.offset());
+        self.linker.by_pos.insert(key, self.linker.references.len());
+        self.linker.references.push(Reference { pos_range, name, target: target.clone() });
+        return Some(target)
+    }
+
+    // Resolves the type of a field and checks its options against it: the
+    // default of an enum field must be the name of one of its values,
+    // message fields can't have one, and only enum fields can be packed.
+    fn field(&mut self, scope: &str, f: &FieldNode) {
+        let target = match self.reference(scope, f.field_type(), true) {
+            Some(target) => target,
+            None => return
+        };
+        let is_message = self.linker.symbols[&target].kind == SymbolKind::Message;
+        if let Some(packed) = field_option(f, "packed") {
+            // The compiler has already reported packed fields that aren't
+            // repeated.
+            if is_message && f.label().is_some_and(|l| l.as_identifier() == "repeated") {
+                self.errors.push(Error::invalid(pos_range(packed.name()), "[packed = true] can only be specified for repeated primitive fields"));
+            }
+        }
+        let val = match field_option(f, "default") {
+            Some(o) => o.val(),
+            None => return
+        };
+        if is_message {
+            self.errors.push(Error::invalid(pos_range(val), "messages can't have default values"));
+            return
+        }
+        let name = match val {
+            ValueNode::Identifier(i) => i.as_identifier(),
+            ValueNode::Bool(b) => b.keyword().as_identifier(),
+            ValueNode::SpecialFloat(f) => f.keyword().as_identifier(),
+            _ => {
+                self.errors.push(Error::invalid(pos_range(val), "default value for an enum field must be an identifier"));
+                return
+            }
+        };
+        let values = self.linker.enum_values.get(&target);
+        if !values.is_some_and(|v| v.contains(&name)) {
+            self.errors.push(Error::invalid(pos_range(val), &format!("enum \"{}\" has no value named \"{}\"", target, name)));
+        }
+    }
+
+    // Checks that the extendee declares `number` as an extension number.
+    fn extension_number(&mut self, extendee: &str, tag: &dyn IntValueNodeTrait) {
+        let (number, ok) = tag.as_int64();
+        let ranges = self.linker.extension_ranges.get(extendee);
+        if !ok || ranges.is_some_and(|r| r.iter().any(|(start, end)| (*start as i64) <= number && number < (*end as i64))) {
+            return
+        }
+        self.errors.push(Error::invalid(pos_range(tag), &format!("\"{}\" does not declare {} as an extension number", extendee, number)));
+    }
+
+    fn resolve_file(&mut self, file: &FileNode) {
+        let mut package = String::new();
+        for decl in file.decls().iter() {
+            if let FileElement::Package(p) = decl {
+                package = p.name().as_identifier();
+            }
+        }
+        for decl in file.decls().iter() {
+            match decl {
+                FileElement::Message(m) => self.resolve_message(&join(&package, &m.name().as_identifier()), m.body()),
+                FileElement::Extend(e) => self.resolve_extend(&package, e),
+                FileElement::Service(s) => {
+                    let scope = join(&package, &s.name().as_identifier());
+                    for decl in s.decls().iter() {
+                        if let ServiceElement::RPC(r) = decl {
+                            self.reference(&scope, r.input().message_type(), false);
+                            self.reference(&scope, r.output().message_type(), false);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve_message(&mut self, scope: &str, body: &MessageBody) {
+        for decl in body.decls().iter() {
+            match decl {
+                MessageElement::Field(f) => self.field(scope, f),
+                MessageElement::MapField(f) => {
+                    self.reference(scope, f.map_type().value_type(), true);
+                },
+                MessageElement::Group(g) => self.resolve_message(&join(scope, &g.name().as_identifier()), g.body()),
+                MessageElement::OneOf(o) => {
+                    for decl in o.decls().iter() {
+                        match decl {
+                            OneOfElement::Field(f) => self.field(scope, f),
+                            OneOfElement::Group(g) => self.resolve_message(&join(scope, &g.name().as_identifier()), g.body()),
+                            _ => {}
+                        }
+                    }
+                },
+                MessageElement::Message(m) => self.resolve_message(&join(scope, &m.name().as_identifier()), m.body()),
+                MessageElement::Extend(e) => self.resolve_extend(scope, e),
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve_extend(&mut self, scope: &str, e: &ExtendNode) {
+        let extendee = self.reference(scope, e.extendee(), false);
+        for decl in e.decls().iter() {
+            match decl {
+                ExtendElement::Field(f) => {
+                    if let Some(extendee) = &extendee {
+                        self.extension_number(extendee, f.tag());
+                    }
+                    self.field(scope, f);
+                },
+                ExtendElement::Group(g) => {
+                    if let Some(extendee) = &extendee {
+                        self.extension_number(extendee, g.tag());
+                    }
+                    self.resolve_message(&join(scope, &g.name().as_identifier()), g.body());
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+// Returns the compact option of a field that sets the built-in option
+// `name`, if there is one.
+fn field_option<'a>(f: &'a FieldNode, name: &str) -> Option<&'a OptionNode> {
+    let opts = f.options()?;
+    return opts.options().iter().find(|o| {
+        let parts = o.name().parts();
+        return parts.len() == 1 && !parts[0].is_extension() && parts[0].name().as_identifier() == name
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn errors(source: &str) -> Vec<String> {
+        let file = parse("a.proto", source).unwrap();
+        return Linker::new().link(&file).err().unwrap_or_default().iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn resolves_relative_names() {
+        let file = parse("a.proto", "package p;\nmessage A { message B {} }\nmessage C { optional A.B b = 1; optional .p.A a = 2; }\nservice S { rpc R (A) returns (A.B); }\n").unwrap();
+        let mut linker = Linker::new();
+        let fd = linker.link(&file).unwrap();
+        let types: Vec<Option<&str>> = fd.message_type[1].field.iter().map(|f| f.type_name.as_deref()).collect();
+        assert_eq!(types, vec![Some(".p.A.B"), Some(".p.A")]);
+        assert_eq!(fd.message_type[1].field[0].r#type, Some(FieldType::Message));
+        assert_eq!(fd.service[0].method[0].output_type.as_deref(), Some(".p.A.B"));
+        assert_eq!(linker.lookup("a.proto", "p.C", "A.B").map(|s| s.full_name()), Some("p.A.B"));
+        assert_eq!(linker.symbol("p.A.B").map(|s| s.kind()), Some(SymbolKind::Message));
+    }
+
+    #[test]
+    fn duplicate_and_missing_names() {
+        let source = "package p;\nmessage A {}\nenum A { X = 0; }\nmessage B { optional Missing m = 1; }\nenum E { Y = 0; }\nenum F { Y = 0; }\n";
+        assert_eq!(errors(source), vec![
+            "a.proto:3:6: \"p.A\" is already defined",
+            "a.proto:6:10: \"p.Y\" is already defined",
+            "a.proto:4:22: \"Missing\" is not defined"
+        ]);
+    }
+
+    #[test]
+    fn ambiguous_names() {
+        let source = "package foo.bar;\nmessage Outer {}\nmessage M { optional bar.Outer.Nope x = 1; }\n";
+        assert_eq!(errors(source), vec!["a.proto:3:22: \"bar.Outer.Nope\" resolves to \"foo.bar.Outer.Nope\", which is not defined"]);
+    }
+
+    #[test]
+    fn enum_defaults() {
+        let source = "enum E { A = 0; }\nmessage M {\n  optional E e = 1 [default = B];\n  optional M m = 2 [default = A];\n  optional E ok = 3 [default = A];\n  optional E n = 4 [default = 1];\n  optional M s = 5 [default = \"x\"];\n}\n";
+        assert_eq!(errors(source), vec![
+            "a.proto:3:31: enum \"E\" has no value named \"B\"",
+            "a.proto:4:31: messages can't have default values",
+            "a.proto:6:31: default value for an enum field must be an identifier",
+            "a.proto:7:31: messages can't have default values"
+        ]);
+    }
+
+    #[test]
+    fn packed_message_fields() {
+        let source = "enum E { A = 0; }\nmessage M {\n  repeated E e = 1 [packed = true];\n  repeated M m = 2 [packed = true];\n}\n";
+        assert_eq!(errors(source), vec![
+            "a.proto:4:21: [packed = true] can only be specified for repeated primitive fields"
+        ]);
+    }
+
+    #[test]
+    fn extension_numbers() {
+        let source = "message M { extensions 100 to 199; }\nmessage N {}\nextend M { optional int32 x = 5; optional int32 y = 150; }\nextend N { optional int32 z = 1; }\n";
+        assert_eq!(errors(source), vec![
+            "a.proto:3:31: \"M\" does not declare 5 as an extension number",
+            "a.proto:4:31: \"N\" does not declare 1 as an extension number"
+        ]);
+    }
+}
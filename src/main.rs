@@ -1,41 +1,188 @@
-mod node;
-use node::{CompositeNode, TerminalNode, TokenInfo};
-use node::NodeTrait;
-use node::TerminalNodeTrait;
-use node::RuneNode;
-mod source_pos;
-use source_pos::PosRange;
-use source_pos::Comment;
-use source_pos::SourcePos;
-use std::char;
-mod values;
-use values::{SignedFloatLiteralNode, FloatLiteralNode};
-mod identifiers;
-use identifiers::{IdentNode};
-mod options;
-use options::{OptionNode};
-mod enums;
-use enums::EnumNode;
-mod message;
-use message::MessageNode;
+#![allow(clippy::needless_return)]
 
-fn main() {
-    let s = SourcePos::new("test.yaml");
-    let mut e = s.clone();
-    e.set_line(5).set_col(10).set_offset(10);
-    let p = PosRange {
-        start: s.clone(),
-        end: e.clone()
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::process;
+
+use protoreflect::lexer;
+use protoreflect::parser;
+use protoreflect::printer;
+use protoreflect::format::{self, FormatOptions};
+use protoreflect::node::NodeTrait;
+use protoreflect::error::Error;
+use protoreflect::diagnostic::{Diagnostic, Renderer};
+use protoreflect::emit;
+
+fn usage() -> String {
+    return format!("protoreflect {}\n\nUSAGE:\n    protoreflect <COMMAND> [ARGS]\n\nCOMMANDS:\n    lex <FILE>      Print the tokens of a proto file\n    parse [OPTIONS] <FILE>\n                    Check a proto file for syntax errors\n    print <FILE>    Parse a proto file and print it back out unchanged\n    fmt [OPTIONS] <FILE>\n                    Print a proto file in canonical style\n    help            Print this message\n\nPARSE OPTIONS:\n    --error-format <FORMAT>  human (default), json, sarif or protoc\n\nFMT OPTIONS:\n    --indent-width <N>       Spaces per indentation level (default 2)\n    --max-line-length <N>    Wrap compact options past this width (default 100)\n    --wrap-compact-options   Put each compact option on its own line\n    --align-field-numbers    Line up the numbers of consecutive fields\n    --no-sort-imports        Keep imports in their original order", env!("CARGO_PKG_VERSION"))
+}
+
+fn read_source(path: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(s) => return s,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+// Prints the errors with the source lines they point at, in color when
+// stderr is a terminal and NO_COLOR is not set, and exits.
+fn report(errors: &[Error], source: &str) -> ! {
+    let renderer = if env::var_os("NO_COLOR").is_none() && io::stderr().is_terminal() {
+        Renderer::colored()
+    } else {
+        Renderer::plain()
+    };
+    eprint!("{}", renderer.render_errors(errors, source));
+    process::exit(1);
+}
+
+fn lex(path: &str) {
+    let source = read_source(path);
+    let (tokens, errors) = lexer::lex_partial(path, &source);
+    if !errors.is_empty() {
+        report(&errors, &source);
+    }
+    for tok in tokens.iter() {
+        let info = tok.info();
+        let node = info.as_terminal_node();
+        println!("{} {:?} {:?}", node.start(), tok.kind(), info.raw_text());
+        for c in info.leading_comments() {
+            println!("    leading {:?}", c.text());
+        }
+        for c in info.trailing_comments() {
+            println!("    trailing {:?}", c.text());
+        }
+    }
+}
+
+fn parse(args: &[String]) {
+    let mut error_format = "human".to_string();
+    let mut path = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--error-format" => {
+                i += 1;
+                match args.get(i).map(|s| s.as_str()) {
+                    Some(f @ "human") | Some(f @ "json") | Some(f @ "sarif") | Some(f @ "protoc") => error_format = f.to_string(),
+                    _ => {
+                        eprintln!("--error-format expects human, json, sarif or protoc\n\n{}", usage());
+                        process::exit(2);
+                    }
+                }
+            },
+            arg if arg.starts_with("--") => {
+                eprintln!("unknown option: {}\n\n{}", arg, usage());
+                process::exit(2);
+            },
+            arg => path = Some(arg.to_string())
+        }
+        i += 1;
+    }
+    let path = match path {
+        Some(p) => p,
+        None => {
+            eprintln!("parse: missing file\n\n{}", usage());
+            process::exit(2);
+        }
     };
-    let mut t = TerminalNode::new(p.clone());
-    let mut c = Comment::new(p.clone());
-    t.push_trailing_comment(c);
-    let mut ti = TokenInfo::new(p.clone());
-    let mut r = RuneNode::new(char::from_u32(0x2764).unwrap(), ti.clone());
-    let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(r.clone()), Box::new(t)];
-    let mut cn = CompositeNode::new();
-    cn.push_children(children);
-    let fln = FloatLiteralNode::new(1 as f64, ti.clone());
-    let float_n = SignedFloatLiteralNode::new(r, Box::new(fln));
-    println!("{:?}", float_n);
-}
\ No newline at end of file
+    let source = read_source(&path);
+    let errs = match parser::parse(&path, &source) {
+        Ok(_) => return,
+        Err(errs) => errs
+    };
+    let diags: Vec<Diagnostic> = errs.errors().iter().map(Diagnostic::from).collect();
+    match error_format.as_str() {
+        "json" => print!("{}", emit::to_ndjson(&diags)),
+        "sarif" => print!("{}", emit::to_sarif(&diags)),
+        "protoc" => eprint!("{}", emit::to_protoc(&diags)),
+        _ => report(errs.errors(), &source)
+    }
+    process::exit(1);
+}
+
+fn print(path: &str) {
+    let source = read_source(path);
+    match parser::parse(path, &source) {
+        Ok(file) => print!("{}", printer::print(&file)),
+        Err(errs) => report(errs.errors(), &source)
+    }
+}
+
+fn parse_number(flag: &str, val: Option<&String>) -> usize {
+    match val.map(|v| v.parse::<usize>()) {
+        Some(Ok(n)) => return n,
+        _ => {
+            eprintln!("{} expects a number\n\n{}", flag, usage());
+            process::exit(2);
+        }
+    }
+}
+
+fn fmt(args: &[String]) {
+    let mut opts = FormatOptions::default();
+    let mut path = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--indent-width" => {
+                i += 1;
+                opts.indent_width = parse_number("--indent-width", args.get(i));
+            },
+            "--max-line-length" => {
+                i += 1;
+                opts.max_line_length = parse_number("--max-line-length", args.get(i));
+            },
+            "--wrap-compact-options" => opts.wrap_compact_options = true,
+            "--align-field-numbers" => opts.align_field_numbers = true,
+            "--no-sort-imports" => opts.sort_imports = false,
+            arg if arg.starts_with("--") => {
+                eprintln!("unknown option: {}\n\n{}", arg, usage());
+                process::exit(2);
+            },
+            arg => path = Some(arg.to_string())
+        }
+        i += 1;
+    }
+    let path = match path {
+        Some(p) => p,
+        None => {
+            eprintln!("fmt: missing file\n\n{}", usage());
+            process::exit(2);
+        }
+    };
+    let source = read_source(&path);
+    match parser::parse(&path, &source) {
+        Ok(file) => print!("{}", format::format(&file, &opts)),
+        Err(errs) => report(errs.errors(), &source)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match (args.first().map(|s| s.as_str()), args.get(1)) {
+        (Some("lex"), Some(path)) => {
+            lex(path);
+        },
+        (Some("parse"), Some(_)) => {
+            parse(&args[1..]);
+        },
+        (Some("print"), Some(path)) => {
+            print(path);
+        },
+        (Some("fmt"), Some(_)) => {
+            fmt(&args[1..]);
+        },
+        (None, _) | (Some("help"), _) | (Some("-h"), _) | (Some("--help"), _) => {
+            println!("{}", usage());
+        },
+        (Some(cmd), _) => {
+            eprintln!("unknown command or missing argument: {}\n\n{}", cmd, usage());
+            process::exit(2);
+        }
+    }
+}
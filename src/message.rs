@@ -1,10 +1,19 @@
-use crate::node::{NodeTrait, CompositeNode, RuneNode};
+use std::any::Any;
+use crate::node::{NodeKind, NodeTrait, CompositeNode, RuneNode, EmptyDeclNode, ErrorNode, TerminalNodeTrait, CompositeNodeTrait, node_enum};
 use crate::identifiers::{KeywordNode, IdentNode, IdentValueNodeTrait};
+use crate::source_pos::{SourcePos, Comment};
+use crate::field::{FieldNode, GroupNode, MapFieldNode};
+use crate::oneof::OneOfNode;
+use crate::enums::EnumNode;
+use crate::options::OptionNode;
+use crate::ranges::{ExtensionRangeNode, ReservedNode};
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut};
 
 pub trait MessageDeclNodeTrait {
     fn message_name(&self) -> Box<dyn NodeTrait>;
 }
 
+#[derive(Clone)]
 pub struct MessageNode {
     composite_node: CompositeNode,
     keyword: KeywordNode,
@@ -13,21 +22,19 @@ pub struct MessageNode {
 }
 
 impl MessageNode {
-    pub fn file_element(&self) {}
-    pub fn msg_element(&self) {}
-    pub fn new(keyword: KeywordNode, name: IdentNode, open_brace: RuneNode, decls: Vec<Box<dyn MessageElementTrait>>, close_brace: RuneNode) -> Self {
+    pub fn new(keyword: KeywordNode, name: IdentNode, open_brace: RuneNode, decls: Vec<MessageElement>, close_brace: RuneNode) -> Self {
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(4 + decls.len());
         children.push(Box::new(keyword.clone()));
         children.push(Box::new(name.clone()));
         children.push(Box::new(open_brace.clone()));
-        for (_, decl) in decls.iter().enumerate() {
+        for decl in decls.iter() {
             children.push(decl.as_node_trait());
         }
         children.push(Box::new(close_brace.clone()));
         let mut comp = CompositeNode::new();
         comp.push_children(children);
         let mut msg_body = MessageBody::new();
-        msg_body.populate_body(open_brace.clone(), decls, close_brace.clone());
+        msg_body.populate_body(open_brace, decls, close_brace);
         return MessageNode {
             composite_node: comp,
             keyword,
@@ -35,6 +42,26 @@ impl MessageNode {
             message_body: msg_body
         }
     }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn body(&self) -> &MessageBody {
+        return &self.message_body
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.name);
+        self.message_body.walk_children_mut(v);
+        let body = &self.message_body;
+        *self = MessageNode::new(self.keyword.clone(), self.name.clone(), body.open_brace().unwrap().clone(), body.decls().to_vec(), body.close_brace().unwrap().clone());
+    }
 }
 
 impl MessageDeclNodeTrait for MessageNode {
@@ -43,9 +70,72 @@ impl MessageDeclNodeTrait for MessageNode {
     }
 }
 
+impl NodeTrait for MessageNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_message(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_message_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Message
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A declaration that may appear in the body of a message or group.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum MessageElement {
+    Field(FieldNode),
+    MapField(MapFieldNode),
+    Group(GroupNode),
+    OneOf(OneOfNode),
+    Message(MessageNode),
+    Enum(EnumNode),
+    Extend(ExtendNode),
+    Option(OptionNode),
+    ExtensionRange(ExtensionRangeNode),
+    Reserved(ReservedNode),
+    Empty(EmptyDeclNode),
+    Error(ErrorNode)
+}
+
+node_enum!(MessageElement { Field, MapField, Group, OneOf, Message, Enum, Extend, Option, ExtensionRange, Reserved, Empty, Error });
+
+#[derive(Clone, Default)]
 pub struct MessageBody {
     open_brace: Option<RuneNode>,
-    decls: Vec<Box<dyn MessageElementTrait>>,
+    decls: Vec<MessageElement>,
     close_brace: Option<RuneNode>
 }
 
@@ -58,78 +148,202 @@ impl MessageBody {
         }
     }
 
-    pub fn populate_body(&mut self, open_brace: RuneNode, decls: Vec<Box<dyn MessageElementTrait>>, close_brace: RuneNode) -> &mut Self {
+    pub fn populate_body(&mut self, open_brace: RuneNode, decls: Vec<MessageElement>, close_brace: RuneNode) -> &mut Self {
         self.open_brace = Some(open_brace);
         self.close_brace = Some(close_brace);
         self.decls = decls;
         return self
     }
-}
 
-pub trait _MessageElementTrait {
-    fn msg_element(&self) {} 
-}
+    pub fn open_brace(&self) -> Option<&RuneNode> {
+        return self.open_brace.as_ref()
+    }
+
+    pub fn decls(&self) -> &[MessageElement] {
+        return &self.decls
+    }
 
-pub trait MessageElementTrait: _MessageElementTrait + NodeTrait {
-    fn as_node_trait(&self) -> Box<dyn NodeTrait>;
-    fn as_message_element_trait(&self) -> Box<dyn _MessageElementTrait>;
+    pub fn close_brace(&self) -> Option<&RuneNode> {
+        return self.close_brace.as_ref()
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_opt_mut(v, self.open_brace.as_mut());
+        for decl in self.decls.iter_mut() {
+            walk_mut(v, decl.as_node_mut());
+        }
+        walk_opt_mut(v, self.close_brace.as_mut());
+    }
 }
 
+#[derive(Clone)]
 pub struct ExtendNode {
     composite_node: CompositeNode,
     keyword: KeywordNode,
     extendee: Box<dyn IdentValueNodeTrait>,
     open_brace: RuneNode,
-    decls: Vec<Box<dyn ExtendElementTrait>>,
+    decls: Vec<ExtendElement>,
     close_brace: RuneNode
 }
 
 impl ExtendNode {
-    pub fn file_element(&self) {}
-    pub fn msg_element(&self) {}
-    pub fn new(keyword: KeywordNode, extendee: Box<dyn IdentValueNodeTrait>, open_brace: RuneNode, decls: Vec<Box<dyn ExtendElementTrait>>, close_brace: RuneNode) -> Self {
+    pub fn new(keyword: KeywordNode, extendee: Box<dyn IdentValueNodeTrait>, open_brace: RuneNode, mut decls: Vec<ExtendElement>, close_brace: RuneNode) -> Self {
+        for decl in decls.iter_mut() {
+            decl.set_extendee(extendee.clone());
+        }
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(4 + decls.len());
-        children.push(Box::new(keyword));
+        children.push(Box::new(keyword.clone()));
         children.push(extendee.as_node_trait());
-        children.push(Box::new(open_brace));
-        for (_, decl) in decls.iter().enumerate() {
+        children.push(Box::new(open_brace.clone()));
+        for decl in decls.iter() {
             children.push(decl.as_node_trait());
         }
-        children.push(Box::new(close_brace));
+        children.push(Box::new(close_brace.clone()));
 
         let mut comp = CompositeNode::new();
         comp.push_children(children);
 
-        let ret = ExtendNode {
+        return ExtendNode {
             composite_node: comp,
             keyword,
             extendee,
             open_brace,
             decls,
             close_brace
-        };
-        for (_, decl) in decls.iter().enumerate() {
-            match *decl {
-                FieldNode => {
-                    decl.extendee = ret
-                },
-                GroupNode => {
-                    decl.extendee = ret
-                }
-                default => {
-                    continue
-                }
-            }
         }
-        return ret
     }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn extendee(&self) -> &dyn IdentValueNodeTrait {
+        return self.extendee.as_ref()
+    }
+
+    pub fn open_brace(&self) -> &RuneNode {
+        return &self.open_brace
+    }
+
+    pub fn decls(&self) -> &[ExtendElement] {
+        return &self.decls
+    }
+
+    pub fn close_brace(&self) -> &RuneNode {
+        return &self.close_brace
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, self.extendee.as_mut());
+        walk_mut(v, &mut self.open_brace);
+        for decl in self.decls.iter_mut() {
+            walk_mut(v, decl.as_node_mut());
+        }
+        walk_mut(v, &mut self.close_brace);
+        *self = ExtendNode::new(self.keyword.clone(), self.extendee.clone(), self.open_brace.clone(), self.decls.clone(), self.close_brace.clone());
+    }
+}
+
+impl NodeTrait for ExtendNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_extend(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_extend_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Extend
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A declaration that may appear in the body of an `extend` block.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum ExtendElement {
+    Field(FieldNode),
+    Group(GroupNode),
+    Empty(EmptyDeclNode),
+    Error(ErrorNode)
 }
 
-pub trait _ExtendElementTrait {
-    fn extend_element(&self) {}
+node_enum!(ExtendElement { Field, Group, Empty, Error });
+
+impl ExtendElement {
+    /// Records the message being extended. Fields and groups keep it so
+    /// they can be told apart from normal message fields.
+    fn set_extendee(&mut self, extendee: Box<dyn IdentValueNodeTrait>) {
+        match self {
+            ExtendElement::Field(f) => f.set_extendee(extendee),
+            ExtendElement::Group(g) => g.set_extendee(extendee),
+            ExtendElement::Empty(_) | ExtendElement::Error(_) => {}
+        }
+    }
 }
 
-pub trait ExtendElementTrait: _ExtendElementTrait + NodeTrait {
-    fn as_node_trait(&self) -> Box<dyn NodeTrait>;
-    fn as_extend_element_trait(&self) -> Box<dyn _ExtendElementTrait>;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileElement;
+    use crate::node::NodeKind;
+    use crate::parser::parse;
+
+    #[test]
+    fn body_elements() {
+        let source = "message M {\n  int32 a = 1;\n  map<string, M> m = 2;\n  oneof o { int32 b = 3; }\n  reserved 4;\n  extensions 5;\n  option deprecated = true;\n  ;\n  group G = 6 {}\n}\nextend M { int32 c = 5; }\n";
+        let file = parse("a.proto", source).unwrap();
+        let m = match &file.decls()[0] {
+            FileElement::Message(m) => m,
+            _ => panic!("expected a message")
+        };
+        let kinds: Vec<NodeKind> = m.body().decls().iter().map(|d| d.kind()).collect();
+        assert_eq!(kinds, vec![
+            NodeKind::Field,
+            NodeKind::MapField,
+            NodeKind::OneOf,
+            NodeKind::Reserved,
+            NodeKind::ExtensionRange,
+            NodeKind::Option,
+            NodeKind::EmptyDecl,
+            NodeKind::Group
+        ]);
+        match &m.body().decls()[0] {
+            MessageElement::Field(f) => assert_eq!(f.start().line(), 2),
+            _ => panic!("expected a field")
+        }
+        match &file.decls()[1] {
+            FileElement::Extend(e) => assert!(matches!(e.decls(), [ExtendElement::Field(_)])),
+            _ => panic!("expected an extend block")
+        }
+    }
 }
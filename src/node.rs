@@ -1,23 +1,52 @@
+use std::any::Any;
 use crate::source_pos::SourcePos;
 use crate::source_pos::Comment;
 use crate::source_pos::PosRange;
 use crate::values::ValueNodeTrait;
 use crate::values::_ValueNodeTrait;
-#[macro_use]
 use dyn_clone::{self, clone_trait_object, DynClone};
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_all_mut};
+use crate::error::{Error, check_not_empty};
 
 pub trait NodeTrait: DynClone {
     fn start(&self) -> &SourcePos;
     fn end(&self) -> &SourcePos;
     fn leading_comments(&self) -> Vec<Comment>;
     fn trailing_comments(&self) -> Vec<Comment>;
+    /// Returns the token behind this node if it is a leaf of the tree.
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait>;
+    /// Returns the children of this node if it is not a leaf of the tree.
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait>;
+    /// Calls the `visit_*` method of `v` for this node's type.
+    fn accept(&self, v: &mut dyn Visitor);
+    /// Calls the `visit_*_mut` method of `v` for this node's type.
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut);
+    /// Returns which type of node this is.
+    fn kind(&self) -> NodeKind;
+    /// Returns the node as `Any`, so that it can be downcast to its
+    /// concrete type. Prefer [`downcast_ref`](#method.downcast_ref).
+    fn as_any(&self) -> &dyn Any;
 }
 
 clone_trait_object!(NodeTrait);
 
+impl<'a> dyn NodeTrait + 'a {
+    /// Returns true if the node is a `T`.
+    pub fn is<T: NodeTrait + 'static>(&self) -> bool {
+        return self.as_any().is::<T>()
+    }
+
+    /// Returns the node as a `T`, or None if it is some other type of
+    /// node.
+    pub fn downcast_ref<T: NodeTrait + 'static>(&self) -> Option<&T> {
+        return self.as_any().downcast_ref::<T>()
+    }
+}
+
 impl std::fmt::Debug for dyn NodeTrait {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("NodeTrait")
+            .field("kind", &self.kind())
             .field("start", &self.start())
             .field("end", &self.end())
             .field("leading_comments", &self.leading_comments())
@@ -26,6 +55,129 @@ impl std::fmt::Debug for dyn NodeTrait {
     }
 }
 
+/// The concrete type of a node, for code that only has a
+/// `dyn NodeTrait`. Keywords are `Ident`s, and a
+/// [`ValueNode`](crate::values::ValueNode) reports the kind of the node it
+/// holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    File,
+    Syntax,
+    Edition,
+    Package,
+    Import,
+    Option,
+    OptionName,
+    FieldReference,
+    CompactOptions,
+    Message,
+    Extend,
+    Field,
+    Group,
+    MapType,
+    MapField,
+    OneOf,
+    Enum,
+    EnumValue,
+    Reserved,
+    ExtensionRange,
+    Range,
+    Service,
+    RPC,
+    RPCType,
+    EmptyDecl,
+    Error,
+    Ident,
+    CompoundIdent,
+    StringLiteral,
+    CompoundStringLiteral,
+    UintLiteral,
+    PositiveUintLiteral,
+    NegativeIntLiteral,
+    FloatLiteral,
+    SpecialFloatLiteral,
+    SignedFloatLiteral,
+    BoolLiteral,
+    ArrayLiteral,
+    MessageLiteral,
+    MessageField,
+    Rune,
+    Terminal,
+    Composite
+}
+
+/// Implements [`NodeTrait`] for an enum whose variants each hold one node,
+/// by handing every call to that node. The enum also gets `as_node`,
+/// `as_node_mut` and `as_node_trait` methods.
+macro_rules! node_enum {
+    ($name:ident { $($variant:ident),* }) => {
+        impl $name {
+            /// Returns the node held in the variant.
+            pub fn as_node(&self) -> &dyn $crate::node::NodeTrait {
+                match self {
+                    $($name::$variant(n) => return n),*
+                }
+            }
+
+            pub fn as_node_mut(&mut self) -> &mut dyn $crate::node::NodeTrait {
+                match self {
+                    $($name::$variant(n) => return n),*
+                }
+            }
+
+            pub fn as_node_trait(&self) -> Box<dyn $crate::node::NodeTrait> {
+                match self {
+                    $($name::$variant(n) => return Box::new(n.clone())),*
+                }
+            }
+        }
+
+        impl $crate::node::NodeTrait for $name {
+            fn start(&self) -> &$crate::source_pos::SourcePos {
+                return self.as_node().start()
+            }
+
+            fn end(&self) -> &$crate::source_pos::SourcePos {
+                return self.as_node().end()
+            }
+
+            fn leading_comments(&self) -> Vec<$crate::source_pos::Comment> {
+                return self.as_node().leading_comments()
+            }
+
+            fn trailing_comments(&self) -> Vec<$crate::source_pos::Comment> {
+                return self.as_node().trailing_comments()
+            }
+
+            fn as_terminal(&self) -> Option<&dyn $crate::node::TerminalNodeTrait> {
+                return self.as_node().as_terminal()
+            }
+
+            fn as_composite(&self) -> Option<&dyn $crate::node::CompositeNodeTrait> {
+                return self.as_node().as_composite()
+            }
+
+            fn accept(&self, v: &mut dyn $crate::visit::Visitor) {
+                self.as_node().accept(v);
+            }
+
+            fn accept_mut(&mut self, v: &mut dyn $crate::visit::VisitorMut) {
+                self.as_node_mut().accept_mut(v);
+            }
+
+            fn kind(&self) -> $crate::node::NodeKind {
+                return self.as_node().kind()
+            }
+
+            fn as_any(&self) -> &dyn std::any::Any {
+                return self.as_node().as_any()
+            }
+        }
+    };
+}
+
+pub(crate) use node_enum;
+
 pub trait TerminalNodeTrait: NodeTrait {
     fn pop_leading_comment(&mut self) -> Option<Comment>;
     fn push_trailing_comment(&mut self, c: Comment);
@@ -49,7 +201,7 @@ impl TerminalNode {
             trailing_comments: vec![],
             leading_whitespace: "".to_string(),
             raw: "".to_string(),
-            pos_range: pos_range
+            pos_range
         }
     }
 
@@ -108,9 +260,33 @@ impl NodeTrait for TerminalNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.trailing_comments.clone()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return Some(self)
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return None
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_terminal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_terminal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Terminal
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct TokenInfo {
     pos_range: PosRange,
     raw_text: String,
@@ -130,6 +306,26 @@ impl TokenInfo {
         }
     }
 
+    pub fn pos_range(&self) -> &PosRange {
+        return &self.pos_range
+    }
+
+    pub fn raw_text(&self) -> &str {
+        return &self.raw_text
+    }
+
+    pub fn leading_comments(&self) -> &[Comment] {
+        return &self.leading_comments
+    }
+
+    pub fn leading_whitespace(&self) -> &str {
+        return &self.leading_whitespace
+    }
+
+    pub fn trailing_comments(&self) -> &[Comment] {
+        return &self.trailing_comments
+    }
+
     pub fn set_raw_text(&mut self, raw: &str) -> &mut Self {
         self.raw_text = raw.to_string();
         self
@@ -162,7 +358,7 @@ impl TokenInfo {
 }
 
 pub trait CompositeNodeTrait: NodeTrait {
-    fn children(&self) -> Vec<Box<dyn NodeTrait>>;
+    fn children(&self) -> &[Box<dyn NodeTrait>];
 }
 
 #[derive(Debug, Clone)]
@@ -171,18 +367,18 @@ pub struct CompositeNode {
 }
 
 impl CompositeNodeTrait for CompositeNode {
-    fn children(&self) -> Vec<Box<dyn NodeTrait>> {
-        self.children.clone()
+    fn children(&self) -> &[Box<dyn NodeTrait>] {
+        &self.children
     }
 }
 
 impl NodeTrait for CompositeNode {
     fn start(&self) -> &SourcePos {
-        return &self.children[0].start()
+        return self.children[0].start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.children[self.children.len() - 1].end()
+        return self.children[self.children.len() - 1].end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -192,6 +388,36 @@ impl NodeTrait for CompositeNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.children[self.children.len() - 1].trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return None
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return Some(self)
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_composite(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_composite_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Composite
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+impl Default for CompositeNode {
+    fn default() -> Self {
+        return CompositeNode::new()
+    }
 }
 
 impl CompositeNode {
@@ -208,6 +434,12 @@ impl CompositeNode {
     pub fn push_children(&mut self, mut c: Vec<Box<dyn NodeTrait>>) {
         self.children.append(&mut c);
     }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        for c in self.children.iter_mut() {
+            walk_mut(v, c.as_mut());
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -247,11 +479,11 @@ impl _ValueNodeTrait<char> for RuneNode {
 
 impl NodeTrait for RuneNode {
     fn start(&self) -> &SourcePos {
-        return &self.terminal_node.start()
+        return self.terminal_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.terminal_node.end()
+        return self.terminal_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -261,8 +493,33 @@ impl NodeTrait for RuneNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.terminal_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.terminal_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.terminal_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_rune(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_rune_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Rune
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct EmptyDeclNode {
     composite_node: CompositeNode,
     semicolon: RuneNode
@@ -275,15 +532,187 @@ impl EmptyDeclNode {
         };
         return EmptyDeclNode {
             composite_node: c,
-            semicolon: semicolon
+            semicolon
+        }
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.semicolon);
+        *self = EmptyDeclNode::new(self.semicolon.clone());
+    }
+}
+
+impl NodeTrait for EmptyDeclNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_empty_decl(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_empty_decl_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::EmptyDecl
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// The tokens skipped by the parser while recovering from a syntax error.
+/// They are kept in the tree so that it still covers the whole file.
+#[derive(Debug, Clone)]
+pub struct ErrorNode {
+    composite_node: CompositeNode,
+    tokens: Vec<TerminalNode>
+}
+
+impl ErrorNode {
+    pub fn new(tokens: Vec<TerminalNode>) -> Result<Self, Error> {
+        check_not_empty(&tokens, NodeKind::Error, &[])?;
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(tokens.len());
+        for tok in tokens.iter() {
+            children.push(Box::new(tok.clone()));
         }
+        return Ok(ErrorNode {
+            composite_node: CompositeNode { children },
+            tokens
+        })
+    }
+
+    pub fn tokens(&self) -> &[TerminalNode] {
+        return &self.tokens
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_all_mut(v, &mut self.tokens);
+        // Visiting can't remove tokens.
+        *self = ErrorNode::new(self.tokens.clone()).unwrap();
+    }
+}
+
+impl NodeTrait for ErrorNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
     }
 
-    pub fn file_element() {}
-    pub fn msg_element() {}
-    pub fn extend_element() {}
-    pub fn one_of_element() {}
-    pub fn enum_element() {}
-    pub fn service_element() {}
-    pub fn method_element() {}
-}
\ No newline at end of file
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_error(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_error_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Error
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileNode;
+    use crate::message::MessageNode;
+    use crate::parser::parse;
+    use crate::visit::{walk, Visitor};
+
+    #[derive(Default)]
+    struct Kinds {
+        kinds: Vec<NodeKind>,
+        messages: Vec<String>
+    }
+
+    impl Visitor for Kinds {
+        fn pre_visit(&mut self, node: &dyn NodeTrait) -> bool {
+            self.kinds.push(node.kind());
+            if let Some(m) = node.downcast_ref::<MessageNode>() {
+                self.messages.push(m.name().as_terminal().unwrap().raw_text().to_string());
+            }
+            return true
+        }
+    }
+
+    #[test]
+    fn kinds_of_visited_nodes() {
+        let file = parse("a.proto", "syntax = \"proto3\";\nmessage M { int32 x = 1 [(a) = -1]; message N {} }\n").unwrap();
+        let mut v = Kinds::default();
+        walk(&mut v, &file);
+        use NodeKind::*;
+        assert_eq!(v.kinds, vec![
+            File,
+            Syntax, Ident, Rune, StringLiteral, Rune,
+            Message, Ident, Ident, Rune,
+            Field, Ident, Ident, Rune, UintLiteral,
+            CompactOptions, Rune, Option, OptionName, FieldReference, Rune, Ident, Rune, Rune, NegativeIntLiteral, Rune, UintLiteral, Rune,
+            Rune,
+            Message, Ident, Ident, Rune, Rune,
+            Rune,
+            Terminal
+        ]);
+        assert_eq!(v.messages, vec!["M", "N"]);
+    }
+
+    #[test]
+    fn downcasting_boxed_nodes() {
+        let file = parse("a.proto", "message M {}\n").unwrap();
+        let node: Box<dyn NodeTrait> = Box::new(file);
+        assert!(node.is::<FileNode>());
+        assert!(!node.is::<MessageNode>());
+        assert_eq!(node.downcast_ref::<FileNode>().unwrap().decls().len(), 1);
+        assert!(node.downcast_ref::<RuneNode>().is_none());
+    }
+}
@@ -0,0 +1,131 @@
+use std::any::Any;
+use crate::node::{NodeKind, CompositeNode, RuneNode, EmptyDeclNode, ErrorNode, NodeTrait, TerminalNodeTrait, CompositeNodeTrait, node_enum};
+use crate::identifiers::{IdentNode, KeywordNode};
+use crate::source_pos::{SourcePos, Comment};
+use crate::field::{FieldNode, GroupNode};
+use crate::options::OptionNode;
+use crate::visit::{Visitor, VisitorMut, walk_mut};
+
+/// A `oneof` declaration, which groups fields of which at most one may be
+/// set at a time.
+#[derive(Clone)]
+pub struct OneOfNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    name: IdentNode,
+    open_brace: RuneNode,
+    decls: Vec<OneOfElement>,
+    close_brace: RuneNode
+}
+
+impl OneOfNode {
+    pub fn new(keyword: KeywordNode, name: IdentNode, open_brace: RuneNode, decls: Vec<OneOfElement>, close_brace: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(4 + decls.len());
+        children.push(Box::new(keyword.clone()));
+        children.push(Box::new(name.clone()));
+        children.push(Box::new(open_brace.clone()));
+        for decl in decls.iter() {
+            children.push(decl.as_node_trait());
+        }
+        children.push(Box::new(close_brace.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return OneOfNode {
+            composite_node: comp,
+            keyword,
+            name,
+            open_brace,
+            decls,
+            close_brace
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn open_brace(&self) -> &RuneNode {
+        return &self.open_brace
+    }
+
+    pub fn decls(&self) -> &[OneOfElement] {
+        return &self.decls
+    }
+
+    pub fn close_brace(&self) -> &RuneNode {
+        return &self.close_brace
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.open_brace);
+        for decl in self.decls.iter_mut() {
+            walk_mut(v, decl.as_node_mut());
+        }
+        walk_mut(v, &mut self.close_brace);
+        *self = OneOfNode::new(self.keyword.clone(), self.name.clone(), self.open_brace.clone(), self.decls.clone(), self.close_brace.clone());
+    }
+}
+
+impl NodeTrait for OneOfNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_one_of(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_one_of_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::OneOf
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A declaration that may appear in the body of a oneof.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum OneOfElement {
+    Option(OptionNode),
+    Field(FieldNode),
+    Group(GroupNode),
+    Empty(EmptyDeclNode),
+    Error(ErrorNode)
+}
+
+node_enum!(OneOfElement { Option, Field, Group, Empty, Error });
+
@@ -1,68 +1,97 @@
-use crate::node::{NodeTrait};
-use crate::node::{CompositeNode, RuneNode};
-use crate::identifiers::{KeywordNode, IdentNode};
-use crate::values::{ValueNodeTrait, _ValueNodeTrait};
+use std::any::Any;
+use crate::node::{NodeKind, NodeTrait};
+use crate::node::{CompositeNode, RuneNode, TerminalNodeTrait, CompositeNodeTrait};
+use crate::identifiers::{KeywordNode, IdentNode, IdentValueNodeTrait};
+use crate::values::{ValueNode, _ValueNodeTrait};
 use crate::source_pos::{SourcePos, Comment};
+use crate::error::{Error, check_separators, check_not_empty};
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut, walk_separated_mut};
 
-pub trait OptionDeclNode<T> {
+pub trait OptionDeclNode {
     fn get_name(&self) -> Box<dyn NodeTrait>;
-    fn get_value(&self) -> Box<dyn _ValueNodeTrait<T>>;
+    fn get_value(&self) -> &ValueNode;
 }
 
 #[derive(Clone)]
-pub struct OptionNode<T> {
+pub struct OptionNode {
     composite_node: CompositeNode,
     keyword: Option<KeywordNode>,
     name: OptionNameNode,
     equals: RuneNode,
-    val: Box<dyn ValueNodeTrait<T>>,
+    val: ValueNode,
     semicolon: Option<RuneNode>
 }
 
-impl<T> OptionNode<T> {
-    pub fn file_element() {}
-    pub fn msg_element() {}
-    pub fn one_of_element() {}
-    pub fn enum_element() {}
-    pub fn service_element() {}
-    pub fn method_element() {}
+impl OptionNode {
 
-    pub fn new(keyword: KeywordNode, name: OptionNameNode, equals: RuneNode, val: Box<dyn ValueNodeTrait<T>>, semicolon: RuneNode) -> Self {
-        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(keyword.clone()), Box::new(equals.clone()), val.as_node_trait(), Box::new(semicolon.clone())];
+    pub fn new(keyword: KeywordNode, name: OptionNameNode, equals: RuneNode, val: ValueNode, semicolon: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(keyword.clone()), Box::new(name.clone()), Box::new(equals.clone()), val.as_node_trait(), Box::new(semicolon.clone())];
         let mut comp = CompositeNode::new();
         comp.push_children(children);
         return OptionNode {
             composite_node: comp,
-            name: name,
+            name,
             keyword: Some(keyword),
-            equals: equals,
-            val: val,
+            equals,
+            val,
             semicolon: Some(semicolon)
         }
     }
 
-    pub fn new_compact(name: OptionNameNode, equals: RuneNode, val: Box<dyn ValueNodeTrait<T>>) -> Self {
-        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(equals.clone()), val.as_node_trait()];
+    pub fn new_compact(name: OptionNameNode, equals: RuneNode, val: ValueNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(name.clone()), Box::new(equals.clone()), val.as_node_trait()];
         let mut comp = CompositeNode::new();
         comp.push_children(children);
         return OptionNode {
             composite_node: comp,
-            name: name,
-            equals: equals,
-            val: val,
+            name,
+            equals,
+            val,
             keyword: None,
             semicolon: None
         }
     }
+
+    pub fn keyword(&self) -> Option<&KeywordNode> {
+        return self.keyword.as_ref()
+    }
+
+    pub fn name(&self) -> &OptionNameNode {
+        return &self.name
+    }
+
+    pub fn equals(&self) -> &RuneNode {
+        return &self.equals
+    }
+
+    pub fn val(&self) -> &ValueNode {
+        return &self.val
+    }
+
+    pub fn semicolon(&self) -> Option<&RuneNode> {
+        return self.semicolon.as_ref()
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_opt_mut(v, self.keyword.as_mut());
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.equals);
+        walk_mut(v, self.val.as_node_mut());
+        walk_opt_mut(v, self.semicolon.as_mut());
+        *self = match (self.keyword.clone(), self.semicolon.clone()) {
+            (Some(keyword), Some(semicolon)) => OptionNode::new(keyword, self.name.clone(), self.equals.clone(), self.val.clone(), semicolon),
+            _ => OptionNode::new_compact(self.name.clone(), self.equals.clone(), self.val.clone())
+        };
+    }
 }
 
-impl<T: Clone> NodeTrait for OptionNode<T> {
+impl NodeTrait for OptionNode {
     fn start(&self) -> &SourcePos {
-        return &self.composite_node.start()
+        return self.composite_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.composite_node.end()
+        return self.composite_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -72,15 +101,39 @@ impl<T: Clone> NodeTrait for OptionNode<T> {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.composite_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_option(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_option_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Option
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
-impl<T> OptionDeclNode<T> for OptionNode<T> {
+impl OptionDeclNode for OptionNode {
     fn get_name(&self) -> Box<dyn NodeTrait> {
         return Box::new(self.name.clone())
     }
 
-    fn get_value(&self) -> Box<dyn _ValueNodeTrait<T>> {
-        return self.val.as_value_node_trait()
+    fn get_value(&self) -> &ValueNode {
+        return &self.val
     }
 }
 
@@ -92,31 +145,47 @@ pub struct OptionNameNode {
 }
 
 impl OptionNameNode {
-    pub fn new(parts: Vec<FieldReferenceNode>, dots: Vec<RuneNode>) -> Self {
-        let mut children: Vec<Box<NodeTrait>> = Vec::with_capacity((parts.len()*2)-1);
+    pub fn new(parts: Vec<FieldReferenceNode>, dots: Vec<RuneNode>) -> Result<Self, Error> {
+        check_not_empty(&parts, NodeKind::OptionName, &[])?;
+        check_separators(&parts, &dots)?;
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(parts.len() + dots.len());
         for (i, part) in parts.iter().enumerate() {
             if i > 0 {
-                children.push(Box::new(dots.get(i-1).unwrap().clone()));
+                children.push(Box::new(dots[i-1].clone()));
             }
             children.push(Box::new(part.clone()));
         }
         let mut comp = CompositeNode::new();
         comp.push_children(children);
-        return OptionNameNode {
+        return Ok(OptionNameNode {
             composite_node: comp,
             parts,
             dots
-        }
+        })
+    }
+
+    pub fn parts(&self) -> &[FieldReferenceNode] {
+        return &self.parts
+    }
+
+    pub fn dots(&self) -> &[RuneNode] {
+        return &self.dots
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_separated_mut(v, &mut self.parts, &mut self.dots);
+        // Visiting can't change how many parts and dots there are.
+        *self = OptionNameNode::new(self.parts.clone(), self.dots.clone()).unwrap();
     }
 }
 
 impl NodeTrait for OptionNameNode {
     fn start(&self) -> &SourcePos {
-        return &self.composite_node.start()
+        return self.composite_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.composite_node.end()
+        return self.composite_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -126,53 +195,153 @@ impl NodeTrait for OptionNameNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.composite_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_option_name(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_option_name_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::OptionName
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
+/// A reference to a field in an option name or a message literal. It is
+/// either a plain name, an extension name in parentheses or brackets, or,
+/// inside a message literal, an `Any` type URL such as
+/// `[type.googleapis.com/foo.Bar]`.
 #[derive(Clone)]
 pub struct FieldReferenceNode {
     composite_node: CompositeNode,
     open: Option<RuneNode>,
-    name: IdentNode,
+    url_prefix: Option<Box<dyn IdentValueNodeTrait>>,
+    slash: Option<RuneNode>,
+    name: Box<dyn IdentValueNodeTrait>,
     close: Option<RuneNode>
 }
 
 impl FieldReferenceNode {
     pub fn new(name: IdentNode) -> Self {
-        let mut children: Vec<Box<NodeTrait>> = vec![];
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(name.clone())];
         let mut comp = CompositeNode::new();
         comp.push_children(children);
         return FieldReferenceNode {
             composite_node: comp,
-            name,
+            name: Box::new(name),
             open: None,
+            url_prefix: None,
+            slash: None,
             close: None
         }
     }
 
-    pub fn new_extrension(open_sym: RuneNode, name: IdentNode, close_sym: RuneNode) -> Self {
-        let mut children: Vec<Box<dyn NodeTrait>> = vec![Box::new(name.clone()), Box::new(open_sym.clone()), Box::new(close_sym.clone())];
+    pub fn new_extension(open_sym: RuneNode, name: Box<dyn IdentValueNodeTrait>, close_sym: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(open_sym.clone()), name.as_node_trait(), Box::new(close_sym.clone())];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return FieldReferenceNode {
+            composite_node: comp,
+            open: Some(open_sym),
+            url_prefix: None,
+            slash: None,
+            name,
+            close: Some(close_sym)
+        }
+    }
+
+    pub fn new_any_type_ref(open_sym: RuneNode, url_prefix: Box<dyn IdentValueNodeTrait>, slash: RuneNode, name: Box<dyn IdentValueNodeTrait>, close_sym: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(open_sym.clone()), url_prefix.as_node_trait(), Box::new(slash.clone()), name.as_node_trait(), Box::new(close_sym.clone())];
         let mut comp = CompositeNode::new();
         comp.push_children(children);
         return FieldReferenceNode {
             composite_node: comp,
             open: Some(open_sym),
+            url_prefix: Some(url_prefix),
+            slash: Some(slash),
             name,
             close: Some(close_sym)
         }
     }
 
     pub fn is_extension(&self) -> bool {
-        return self.open.is_some()
+        return self.open.is_some() && self.url_prefix.is_none()
+    }
+
+    pub fn is_any_type_reference(&self) -> bool {
+        return self.url_prefix.is_some()
+    }
+
+    pub fn open(&self) -> Option<&RuneNode> {
+        return self.open.as_ref()
+    }
+
+    pub fn url_prefix(&self) -> Option<&dyn IdentValueNodeTrait> {
+        return self.url_prefix.as_deref()
+    }
+
+    pub fn slash(&self) -> Option<&RuneNode> {
+        return self.slash.as_ref()
+    }
+
+    pub fn name(&self) -> &dyn IdentValueNodeTrait {
+        return self.name.as_ref()
+    }
+
+    pub fn close(&self) -> Option<&RuneNode> {
+        return self.close.as_ref()
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_opt_mut(v, self.open.as_mut());
+        if let Some(url_prefix) = self.url_prefix.as_mut() {
+            walk_mut(v, url_prefix.as_mut());
+        }
+        walk_opt_mut(v, self.slash.as_mut());
+        walk_mut(v, self.name.as_mut());
+        walk_opt_mut(v, self.close.as_mut());
+        // A plain name is stored boxed, so the node can't go back through
+        // the constructors.
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(5);
+        if let Some(open) = self.open.clone() {
+            children.push(Box::new(open));
+        }
+        if let Some(url_prefix) = self.url_prefix.as_ref() {
+            children.push(url_prefix.as_node_trait());
+        }
+        if let Some(slash) = self.slash.clone() {
+            children.push(Box::new(slash));
+        }
+        children.push(self.name.as_node_trait());
+        if let Some(close) = self.close.clone() {
+            children.push(Box::new(close));
+        }
+        self.composite_node = CompositeNode::new();
+        self.composite_node.push_children(children);
     }
 }
 
 impl NodeTrait for FieldReferenceNode {
     fn start(&self) -> &SourcePos {
-        return &self.composite_node.start()
+        return self.composite_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.composite_node.end()
+        return self.composite_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -182,34 +351,67 @@ impl NodeTrait for FieldReferenceNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.composite_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_field_reference(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_field_reference_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::FieldReference
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
 impl _ValueNodeTrait<String> for FieldReferenceNode {
     fn value(self) -> String {
-        if self.open.is_some() {
-            return self.open.unwrap().rune().to_string() + &self.name.as_identifier() + &self.close.unwrap().rune().to_string()
-        } else {
-            return self.name.as_identifier()
+        match (self.open, self.close) {
+            (Some(open), Some(close)) => {
+                let mut name = self.name.as_identifier();
+                if let Some(prefix) = self.url_prefix {
+                    name = prefix.as_identifier() + "/" + &name;
+                }
+                return open.rune().to_string() + &name + &close.rune().to_string()
+            },
+            _ => {
+                return self.name.as_identifier()
+            }
         }
     }
 }
 
 #[derive(Clone)]
-pub struct CompactOptionsNode<T> {
+pub struct CompactOptionsNode {
     composite_node: CompositeNode,
     open_bracket: RuneNode,
-    options: Vec<OptionNode<T>>,
+    options: Vec<OptionNode>,
     commas: Vec<RuneNode>,
     close_bracket: RuneNode
 }
 
-impl<T: Clone + 'static> CompactOptionsNode<T> {
-    pub fn new(open_bracket: RuneNode, opts: Vec<OptionNode<T>>, commas: Vec<RuneNode>, close_bracket: RuneNode) -> Self {
+impl CompactOptionsNode {
+    pub fn new(open_bracket: RuneNode, opts: Vec<OptionNode>, commas: Vec<RuneNode>, close_bracket: RuneNode) -> Result<Self, Error> {
+        check_not_empty(&opts, NodeKind::CompactOptions, &[&open_bracket, &close_bracket])?;
+        check_separators(&opts, &commas)?;
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity((opts.len()*2)+1);
         children.push(Box::new(open_bracket.clone()));
         for (i, opt) in opts.iter().enumerate() {
             if i > 0 {
-                children.push(Box::new(commas.get(i -1).unwrap().clone()));
+                children.push(Box::new(commas[i-1].clone()));
             }
             children.push(Box::new(opt.clone()));
         }
@@ -217,27 +419,51 @@ impl<T: Clone + 'static> CompactOptionsNode<T> {
         let mut comp = CompositeNode::new();
         comp.push_children(children);
 
-        return CompactOptionsNode {
+        return Ok(CompactOptionsNode {
             composite_node: comp,
             open_bracket,
             options: opts,
             commas,
             close_bracket
-        }
+        })
     }
 
-    pub fn get_elements(&self) -> Vec<OptionNode<T>> {
+    pub fn get_elements(&self) -> Vec<OptionNode> {
         return self.options.clone()
     }
+
+    pub fn options(&self) -> &[OptionNode] {
+        return &self.options
+    }
+
+    pub fn open_bracket(&self) -> &RuneNode {
+        return &self.open_bracket
+    }
+
+    pub fn commas(&self) -> &[RuneNode] {
+        return &self.commas
+    }
+
+    pub fn close_bracket(&self) -> &RuneNode {
+        return &self.close_bracket
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.open_bracket);
+        walk_separated_mut(v, &mut self.options, &mut self.commas);
+        walk_mut(v, &mut self.close_bracket);
+        // Visiting can't change how many options and commas there are.
+        *self = CompactOptionsNode::new(self.open_bracket.clone(), self.options.clone(), self.commas.clone(), self.close_bracket.clone()).unwrap();
+    }
 }
 
-impl<T: Clone> NodeTrait for CompactOptionsNode<T> {
+impl NodeTrait for CompactOptionsNode {
     fn start(&self) -> &SourcePos {
-        return &self.composite_node.start()
+        return self.composite_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.composite_node.end()
+        return self.composite_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -247,4 +473,28 @@ impl<T: Clone> NodeTrait for CompactOptionsNode<T> {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.composite_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_compact_options(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_compact_options_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::CompactOptions
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
@@ -0,0 +1,1126 @@
+use crate::lexer::{self, Token, TokenKind};
+use crate::error::Error;
+use crate::node::{NodeTrait, RuneNode, EmptyDeclNode, ErrorNode};
+use crate::identifiers::{IdentNode, KeywordNode, CompoundIdentNode, IdentValueNodeTrait, _IdentValueNodeTrait};
+use crate::field::{FieldNode, GroupNode, MapFieldNode, MapTypeNode};
+use crate::oneof::{OneOfNode, OneOfElement};
+use crate::ranges::{RangeNode, ReservedNode, ExtensionRangeNode};
+use crate::service::{ServiceNode, ServiceElement, RPCNode, RPCTypeNode, MethodElement};
+use crate::values::{ValueNode, IntValueNodeTrait, StringValueNodeTrait, StringLiteralNode, CompoundStringLiteralNode, UintLiteralNode, PositiveUintLiteralNode, NegativeIntLiteralNode, FloatLiteralNode, SpecialFloatLiteralNode, SignedFloatLiteralNode, BoolLiteralNode, ArrayLiteralNode, MessageLiteralNode, MessageFieldNode};
+use crate::options::{OptionNode, OptionNameNode, FieldReferenceNode, CompactOptionsNode};
+use crate::enums::{EnumNode, EnumValueNode, EnumElement};
+use crate::message::{MessageNode, MessageElement, ExtendNode, ExtendElement};
+use crate::file::{FileNode, FileElement, SyntaxNode, EditionNode, PackageNode, ImportNode};
+use crate::source_pos::PosRange;
+
+/// All of the errors reported while parsing a file.
+#[derive(Debug, Clone)]
+pub struct ParseErrors {
+    errors: Vec<Error>
+}
+
+impl ParseErrors {
+    pub fn new(errors: Vec<Error>) -> Self {
+        return ParseErrors {
+            errors
+        }
+    }
+
+    pub fn errors(&self) -> &[Error] {
+        return &self.errors
+    }
+}
+
+impl std::fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", e)?;
+        }
+        return Ok(())
+    }
+}
+
+impl std::error::Error for ParseErrors {}
+
+/// Parses the given proto source into an AST.
+///
+/// # Arguments
+///
+/// * `filename` - The name recorded in every `SourcePos` of the tree
+/// * `source` - The contents of the file
+pub fn parse(filename: &str, source: &str) -> Result<FileNode, ParseErrors> {
+    let (file, errs) = parse_partial(filename, source);
+    if errs.errors().is_empty() {
+        return Ok(file)
+    }
+    return Err(errs)
+}
+
+/// Parses the given proto source, carrying on past syntax errors.
+///
+/// A declaration that fails to parse is skipped up to the next `;` or `}`
+/// and kept in the tree as an [`ErrorNode`], so the tree still covers the
+/// whole file. Malformed tokens are handled as [`lexer::lex_partial`]
+/// does. Every error found is returned along with the tree, in source
+/// order.
+pub fn parse_partial(filename: &str, source: &str) -> (FileNode, ParseErrors) {
+    let (tokens, mut errors) = lexer::lex_partial(filename, source);
+    let mut p = Parser::new(tokens);
+    let file = p.parse_file();
+    errors.extend(p.errors);
+    errors.sort_by_key(|e| e.pos_range().map(|r| r.start.offset()));
+    return (file, ParseErrors::new(errors))
+}
+
+type ParseResult<T> = Result<T, Error>;
+
+/// A field, group or map field, before it is placed in the body that
+/// contains it.
+#[allow(clippy::large_enum_variant)]
+enum FieldDecl {
+    Field(FieldNode),
+    Group(GroupNode),
+    Map(MapFieldNode)
+}
+
+impl FieldDecl {
+    fn into_message_element(self) -> MessageElement {
+        match self {
+            FieldDecl::Field(f) => return MessageElement::Field(f),
+            FieldDecl::Group(g) => return MessageElement::Group(g),
+            FieldDecl::Map(m) => return MessageElement::MapField(m)
+        }
+    }
+
+    /// Map fields are not allowed in extend blocks, so callers must rule
+    /// them out first.
+    fn into_extend_element(self) -> ExtendElement {
+        match self {
+            FieldDecl::Field(f) => return ExtendElement::Field(f),
+            FieldDecl::Group(g) => return ExtendElement::Group(g),
+            FieldDecl::Map(_) => unreachable!("map fields cannot be extend elements")
+        }
+    }
+
+    /// Map fields are not allowed in oneofs, so callers must rule them
+    /// out first.
+    fn into_one_of_element(self) -> OneOfElement {
+        match self {
+            FieldDecl::Field(f) => return OneOfElement::Field(f),
+            FieldDecl::Group(g) => return OneOfElement::Group(g),
+            FieldDecl::Map(_) => unreachable!("map fields cannot be oneof elements")
+        }
+    }
+}
+
+fn describe(tok: &Token) -> String {
+    match tok.kind() {
+        TokenKind::Ident(s) => return format!("identifier \"{}\"", s),
+        TokenKind::Int(_) => return "int literal".to_string(),
+        TokenKind::Float(_) => return "float literal".to_string(),
+        TokenKind::String(_) => return "string literal".to_string(),
+        TokenKind::Rune(c) => return format!("'{}'", c),
+        TokenKind::Eof => return "end of file".to_string()
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    seen_package: bool,
+    errors: Vec<Error>
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        return Parser {
+            tokens,
+            pos: 0,
+            seen_package: false,
+            errors: vec![]
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        return &self.tokens[self.pos]
+    }
+
+    fn peek_at(&self, n: usize) -> &Token {
+        let i = std::cmp::min(self.pos + n, self.tokens.len() - 1);
+        return &self.tokens[i]
+    }
+
+    /// Consumes the current token. The trailing EOF token is never
+    /// consumed, so peeking past the end keeps returning it.
+    fn next(&mut self) -> Token {
+        let tok = self.tokens[self.pos].clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        return tok
+    }
+
+    fn error_at(&self, tok: &Token, message: &str) -> Error {
+        return Error::syntax(tok.info().pos_range().clone(), message)
+    }
+
+    fn unexpected(&self, expecting: &str) -> Error {
+        let tok = self.peek();
+        return self.error_at(tok, &format!("syntax error: unexpected {}, expecting {}", describe(tok), expecting))
+    }
+
+    fn rune(&mut self) -> RuneNode {
+        let tok = self.next();
+        let c = match tok.kind() {
+            TokenKind::Rune(c) => *c,
+            _ => unreachable!("rune() called on a non-rune token")
+        };
+        return RuneNode::new(c, tok.into_info())
+    }
+
+    fn expect_rune(&mut self, c: char) -> ParseResult<RuneNode> {
+        if !self.peek().is_rune(c) {
+            return Err(self.unexpected(&format!("'{}'", c)))
+        }
+        return Ok(self.rune())
+    }
+
+    fn ident(&mut self) -> IdentNode {
+        let tok = self.next();
+        let s = match tok.kind() {
+            TokenKind::Ident(s) => s.clone(),
+            _ => unreachable!("ident() called on a non-identifier token")
+        };
+        return IdentNode::new(&s, tok.into_info())
+    }
+
+    fn expect_ident(&mut self) -> ParseResult<IdentNode> {
+        if let TokenKind::Ident(_) = self.peek().kind() {
+            return Ok(self.ident())
+        }
+        return Err(self.unexpected("identifier"))
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> ParseResult<KeywordNode> {
+        if !self.peek().is_ident(kw) {
+            return Err(self.unexpected(&format!("\"{}\"", kw)))
+        }
+        return Ok(self.ident().to_keyword())
+    }
+
+    fn is_ident(&self) -> bool {
+        return matches!(self.peek().kind(), TokenKind::Ident(_))
+    }
+
+    fn at_eof(&self) -> bool {
+        return *self.peek().kind() == TokenKind::Eof
+    }
+
+    /// Records `err` and skips to the end of the declaration that started
+    /// at token `start`: past the next `;` or past the `}` that closes a
+    /// block opened in the declaration. A `}` that closes the enclosing
+    /// body is left for the caller. The declaration's tokens become an
+    /// error node.
+    fn recover(&mut self, start: usize, err: Error, in_body: bool) -> ErrorNode {
+        self.errors.push(err);
+        let mut depth: usize = 0;
+        for tok in self.tokens[start..self.pos].iter() {
+            if tok.is_rune('{') {
+                depth += 1;
+            } else if tok.is_rune('}') {
+                depth = depth.saturating_sub(1);
+            }
+        }
+        while !self.at_eof() {
+            if in_body && depth == 0 && self.peek().is_rune('}') {
+                break
+            }
+            let tok = self.next();
+            if tok.is_rune('{') {
+                depth += 1;
+            } else if tok.is_rune('}') {
+                if depth <= 1 {
+                    break
+                }
+                depth -= 1;
+            } else if tok.is_rune(';') && depth == 0 {
+                break
+            }
+        }
+        let tokens = self.tokens[start..self.pos].iter().map(|t| t.info().as_terminal_node()).collect();
+        // Declarations never start at the end of the file or at a '}' that
+        // closes the body, so at least one token was skipped.
+        return ErrorNode::new(tokens).unwrap()
+    }
+
+    /// Parses the declarations of a body up to its closing brace. One that
+    /// fails to parse is replaced by an error node and parsing goes on with
+    /// the next. Running out of input ends the body with an error.
+    fn parse_body<T>(&mut self, parse_element: fn(&mut Self) -> ParseResult<T>, error: fn(ErrorNode) -> T) -> ParseResult<Vec<T>> {
+        let mut decls = vec![];
+        while !self.peek().is_rune('}') {
+            let start = self.pos;
+            match parse_element(self) {
+                Ok(decl) => decls.push(decl),
+                Err(e) if self.at_eof() => return Err(e),
+                Err(e) => decls.push(error(self.recover(start, e, true)))
+            }
+        }
+        return Ok(decls)
+    }
+
+    fn parse_file(&mut self) -> FileNode {
+        let mut syntax = None;
+        let mut edition = None;
+        let mut decls: Vec<FileElement> = vec![];
+        let start = self.pos;
+        if self.peek().is_ident("syntax") && self.peek_at(1).is_rune('=') {
+            match self.parse_syntax() {
+                Ok(s) => syntax = Some(s),
+                Err(e) => decls.push(FileElement::Error(self.recover(start, e, false)))
+            }
+        } else if self.peek().is_ident("edition") && self.peek_at(1).is_rune('=') {
+            match self.parse_edition() {
+                Ok(e) => edition = Some(e),
+                Err(e) => decls.push(FileElement::Error(self.recover(start, e, false)))
+            }
+        }
+        while !self.at_eof() {
+            let start = self.pos;
+            match self.parse_file_element() {
+                Ok(decl) => decls.push(decl),
+                Err(e) => decls.push(FileElement::Error(self.recover(start, e, false)))
+            }
+        }
+        let eof = self.next().into_info().as_terminal_node();
+        if let Some(edition) = edition {
+            return FileNode::new_with_edition(edition, decls, eof)
+        }
+        return FileNode::new(syntax, decls, eof)
+    }
+
+    fn parse_syntax(&mut self) -> ParseResult<SyntaxNode> {
+        let keyword = self.expect_keyword("syntax")?;
+        let equals = self.expect_rune('=')?;
+        let start = self.peek().clone();
+        let syntax = self.parse_string_literal()?;
+        let val = syntax.as_string();
+        if val != "proto2" && val != "proto3" {
+            return Err(self.error_at(&start, &format!("syntax value must be \"proto2\" or \"proto3\", got \"{}\"", val)))
+        }
+        let semicolon = self.expect_rune(';')?;
+        return Ok(SyntaxNode::new(keyword, equals, syntax, semicolon))
+    }
+
+    fn parse_edition(&mut self) -> ParseResult<EditionNode> {
+        let keyword = self.expect_keyword("edition")?;
+        let equals = self.expect_rune('=')?;
+        let start = self.peek().clone();
+        let edition = self.parse_string_literal()?;
+        let val = edition.as_string();
+        if val != "2023" && val != "2024" {
+            return Err(self.error_at(&start, &format!("edition value must be \"2023\" or \"2024\", got \"{}\"", val)))
+        }
+        let semicolon = self.expect_rune(';')?;
+        return Ok(EditionNode::new(keyword, equals, edition, semicolon))
+    }
+
+    fn parse_package(&mut self) -> ParseResult<PackageNode> {
+        let start = self.peek().clone();
+        let keyword = self.expect_keyword("package")?;
+        if self.seen_package {
+            return Err(self.error_at(&start, "multiple package declarations"))
+        }
+        self.seen_package = true;
+        if self.peek().is_rune('.') {
+            return Err(self.unexpected("package name"))
+        }
+        let name = self.parse_type_name()?;
+        let semicolon = self.expect_rune(';')?;
+        return Ok(PackageNode::new(keyword, name, semicolon))
+    }
+
+    fn parse_import(&mut self) -> ParseResult<ImportNode> {
+        let keyword = self.expect_keyword("import")?;
+        let mut public = None;
+        let mut weak = None;
+        if self.peek().is_ident("public") {
+            public = Some(self.ident().to_keyword());
+        } else if self.peek().is_ident("weak") {
+            weak = Some(self.ident().to_keyword());
+        }
+        let name = self.parse_string_literal()?;
+        let semicolon = self.expect_rune(';')?;
+        return Ok(ImportNode::new(keyword, public, weak, name, semicolon))
+    }
+
+    fn parse_file_element(&mut self) -> ParseResult<FileElement> {
+        let tok = self.peek();
+        if tok.is_rune(';') {
+            return Ok(FileElement::Empty(EmptyDeclNode::new(self.rune())))
+        }
+        if tok.is_ident("package") {
+            return Ok(FileElement::Package(self.parse_package()?))
+        }
+        if tok.is_ident("import") {
+            return Ok(FileElement::Import(self.parse_import()?))
+        }
+        if tok.is_ident("message") {
+            return Ok(FileElement::Message(self.parse_message()?))
+        }
+        if tok.is_ident("enum") {
+            return Ok(FileElement::Enum(self.parse_enum()?))
+        }
+        if tok.is_ident("extend") {
+            return Ok(FileElement::Extend(self.parse_extend()?))
+        }
+        if tok.is_ident("service") {
+            return Ok(FileElement::Service(self.parse_service()?))
+        }
+        if tok.is_ident("option") {
+            return Ok(FileElement::Option(self.parse_option_decl()?))
+        }
+        return Err(self.unexpected("\"package\", \"import\", \"message\", \"enum\", \"extend\", \"service\", \"option\" or ';'"))
+    }
+
+    fn parse_message(&mut self) -> ParseResult<MessageNode> {
+        let keyword = self.expect_keyword("message")?;
+        let name = self.expect_ident()?;
+        let open_brace = self.expect_rune('{')?;
+        let decls = self.parse_body(Parser::parse_message_element, MessageElement::Error)?;
+        let close_brace = self.rune();
+        return Ok(MessageNode::new(keyword, name, open_brace, decls, close_brace))
+    }
+
+    fn parse_message_element(&mut self) -> ParseResult<MessageElement> {
+        let tok = self.peek();
+        if tok.is_rune(';') {
+            return Ok(MessageElement::Empty(EmptyDeclNode::new(self.rune())))
+        }
+        if tok.is_ident("message") {
+            return Ok(MessageElement::Message(self.parse_message()?))
+        }
+        if tok.is_ident("enum") {
+            return Ok(MessageElement::Enum(self.parse_enum()?))
+        }
+        if tok.is_ident("extend") {
+            return Ok(MessageElement::Extend(self.parse_extend()?))
+        }
+        if tok.is_ident("option") {
+            return Ok(MessageElement::Option(self.parse_option_decl()?))
+        }
+        if tok.is_ident("oneof") && self.peek_at(2).is_rune('{') {
+            return Ok(MessageElement::OneOf(self.parse_one_of()?))
+        }
+        if tok.is_ident("reserved") {
+            return Ok(MessageElement::Reserved(self.parse_reserved()?))
+        }
+        if tok.is_ident("extensions") {
+            return Ok(MessageElement::ExtensionRange(self.parse_extension_range()?))
+        }
+        if self.is_ident() || tok.is_rune('.') {
+            return Ok(self.parse_field_decl()?.into_message_element())
+        }
+        return Err(self.unexpected("field, \"message\", \"enum\", \"extend\", \"oneof\", \"reserved\", \"extensions\", \"option\", ';' or '}'"))
+    }
+
+    fn parse_one_of(&mut self) -> ParseResult<OneOfNode> {
+        let keyword = self.expect_keyword("oneof")?;
+        let name = self.expect_ident()?;
+        let open_brace = self.expect_rune('{')?;
+        let decls = self.parse_body(Parser::parse_one_of_element, OneOfElement::Error)?;
+        let close_brace = self.rune();
+        return Ok(OneOfNode::new(keyword, name, open_brace, decls, close_brace))
+    }
+
+    fn parse_one_of_element(&mut self) -> ParseResult<OneOfElement> {
+        let tok = self.peek().clone();
+        if tok.is_rune(';') {
+            return Ok(OneOfElement::Empty(EmptyDeclNode::new(self.rune())))
+        }
+        if tok.is_ident("option") {
+            return Ok(OneOfElement::Option(self.parse_option_decl()?))
+        }
+        if tok.is_ident("map") && self.peek_at(1).is_rune('<') {
+            return Err(self.error_at(&tok, "map fields are not allowed in oneofs"))
+        }
+        if tok.is_ident("optional") || tok.is_ident("required") || tok.is_ident("repeated") {
+            return Err(self.error_at(&tok, "fields in oneofs must not have labels"))
+        }
+        if self.is_ident() || tok.is_rune('.') {
+            return Ok(self.parse_field_decl()?.into_one_of_element())
+        }
+        return Err(self.unexpected("field, \"option\", ';' or '}'"))
+    }
+
+    fn parse_optional_compact_options(&mut self) -> ParseResult<Option<CompactOptionsNode>> {
+        if self.peek().is_rune('[') {
+            return Ok(Some(self.parse_compact_options()?))
+        }
+        return Ok(None)
+    }
+
+    /// Parses a field, group or map field declaration.
+    fn parse_field_decl(&mut self) -> ParseResult<FieldDecl> {
+        let tok = self.peek();
+        if tok.is_ident("map") && self.peek_at(1).is_rune('<') {
+            return self.parse_map_field()
+        }
+        let mut label = None;
+        if tok.is_ident("optional") || tok.is_ident("required") || tok.is_ident("repeated") {
+            label = Some(self.ident().to_keyword());
+        }
+        // "group" is only a keyword when followed by the group's name;
+        // otherwise it names a message type called "group".
+        if self.peek().is_ident("group") && matches!(self.peek_at(1).kind(), TokenKind::Ident(_)) {
+            return self.parse_group(label)
+        }
+        let fld_type = self.parse_type_name()?;
+        let name = self.expect_ident()?;
+        let equals = self.expect_rune('=')?;
+        let tag = self.parse_int_value()?;
+        let opts = self.parse_optional_compact_options()?;
+        let semicolon = self.expect_rune(';')?;
+        return Ok(FieldDecl::Field(FieldNode::new(label, fld_type, name, equals, tag, opts, semicolon)))
+    }
+
+    fn parse_group(&mut self, label: Option<KeywordNode>) -> ParseResult<FieldDecl> {
+        let keyword = self.expect_keyword("group")?;
+        let name_tok = self.peek().clone();
+        let name = self.expect_ident()?;
+        if !name.as_identifier().starts_with(|c: char| c.is_ascii_uppercase()) {
+            return Err(self.error_at(&name_tok, "group names must start with a capital letter"))
+        }
+        let equals = self.expect_rune('=')?;
+        let tag = self.parse_int_value()?;
+        let opts = self.parse_optional_compact_options()?;
+        let open_brace = self.expect_rune('{')?;
+        let decls = self.parse_body(Parser::parse_message_element, MessageElement::Error)?;
+        let close_brace = self.rune();
+        return Ok(FieldDecl::Group(GroupNode::new(label, keyword, name, equals, tag, opts, open_brace, decls, close_brace)))
+    }
+
+    fn parse_map_field(&mut self) -> ParseResult<FieldDecl> {
+        let keyword = self.expect_keyword("map")?;
+        let open_angle = self.expect_rune('<')?;
+        let key_type = self.expect_ident()?;
+        let comma = self.expect_rune(',')?;
+        let value_type = self.parse_type_name()?;
+        let close_angle = self.expect_rune('>')?;
+        let map_type = MapTypeNode::new(keyword, open_angle, key_type, comma, value_type, close_angle);
+        let name = self.expect_ident()?;
+        let equals = self.expect_rune('=')?;
+        let tag = self.parse_int_value()?;
+        let opts = self.parse_optional_compact_options()?;
+        let semicolon = self.expect_rune(';')?;
+        return Ok(FieldDecl::Map(MapFieldNode::new(map_type, name, equals, tag, opts, semicolon)))
+    }
+
+    fn parse_enum(&mut self) -> ParseResult<EnumNode> {
+        let keyword = self.expect_keyword("enum")?;
+        let name = self.expect_ident()?;
+        let open_brace = self.expect_rune('{')?;
+        let decls = self.parse_body(Parser::parse_enum_element, EnumElement::Error)?;
+        let close_brace = self.rune();
+        return Ok(EnumNode::new(keyword, name, open_brace, decls, close_brace))
+    }
+
+    fn parse_enum_element(&mut self) -> ParseResult<EnumElement> {
+        let tok = self.peek();
+        if tok.is_rune(';') {
+            return Ok(EnumElement::Empty(EmptyDeclNode::new(self.rune())))
+        }
+        if tok.is_ident("option") {
+            return Ok(EnumElement::Option(self.parse_option_decl()?))
+        }
+        if tok.is_ident("reserved") && !self.peek_at(1).is_rune('=') {
+            return Ok(EnumElement::Reserved(self.parse_reserved()?))
+        }
+        if self.is_ident() {
+            return Ok(EnumElement::Value(self.parse_enum_value()?))
+        }
+        return Err(self.unexpected("\"option\", \"reserved\", enum value name, ';' or '}'"))
+    }
+
+    fn parse_enum_value(&mut self) -> ParseResult<EnumValueNode> {
+        let name = self.expect_ident()?;
+        let equals = self.expect_rune('=')?;
+        let number = self.parse_int_value()?;
+        let opts = self.parse_optional_compact_options()?;
+        let semicolon = self.expect_rune(';')?;
+        return Ok(EnumValueNode::new(name, equals, number, opts, semicolon))
+    }
+
+    fn parse_service(&mut self) -> ParseResult<ServiceNode> {
+        let keyword = self.expect_keyword("service")?;
+        let name = self.expect_ident()?;
+        let open_brace = self.expect_rune('{')?;
+        let decls = self.parse_body(Parser::parse_service_element, ServiceElement::Error)?;
+        let close_brace = self.rune();
+        return Ok(ServiceNode::new(keyword, name, open_brace, decls, close_brace))
+    }
+
+    fn parse_service_element(&mut self) -> ParseResult<ServiceElement> {
+        let tok = self.peek();
+        if tok.is_rune(';') {
+            return Ok(ServiceElement::Empty(EmptyDeclNode::new(self.rune())))
+        }
+        if tok.is_ident("option") {
+            return Ok(ServiceElement::Option(self.parse_option_decl()?))
+        }
+        if tok.is_ident("rpc") {
+            return Ok(ServiceElement::RPC(self.parse_rpc()?))
+        }
+        return Err(self.unexpected("\"rpc\", \"option\", ';' or '}'"))
+    }
+
+    fn parse_rpc(&mut self) -> ParseResult<RPCNode> {
+        let keyword = self.expect_keyword("rpc")?;
+        let name = self.expect_ident()?;
+        let input = self.parse_rpc_type()?;
+        let returns = self.expect_keyword("returns")?;
+        let output = self.parse_rpc_type()?;
+        if !self.peek().is_rune('{') {
+            let semicolon = self.expect_rune(';')?;
+            return Ok(RPCNode::new(keyword, name, input, returns, output, semicolon))
+        }
+        let open_brace = self.rune();
+        let decls = self.parse_body(Parser::parse_method_element, MethodElement::Error)?;
+        let close_brace = self.rune();
+        return Ok(RPCNode::new_with_body(keyword, name, input, returns, output, open_brace, decls, close_brace))
+    }
+
+    fn parse_rpc_type(&mut self) -> ParseResult<RPCTypeNode> {
+        let open_paren = self.expect_rune('(')?;
+        let mut stream = None;
+        // "stream" is only a keyword when a type name follows it.
+        if self.peek().is_ident("stream") && !self.peek_at(1).is_rune(')') && !self.peek_at(1).is_rune('.') {
+            stream = Some(self.ident().to_keyword());
+        }
+        let message_type = self.parse_type_name()?;
+        let close_paren = self.expect_rune(')')?;
+        return Ok(RPCTypeNode::new(open_paren, stream, message_type, close_paren))
+    }
+
+    fn parse_method_element(&mut self) -> ParseResult<MethodElement> {
+        let tok = self.peek();
+        if tok.is_rune(';') {
+            return Ok(MethodElement::Empty(EmptyDeclNode::new(self.rune())))
+        }
+        if tok.is_ident("option") {
+            return Ok(MethodElement::Option(self.parse_option_decl()?))
+        }
+        return Err(self.unexpected("\"option\", ';' or '}'"))
+    }
+
+    fn parse_reserved(&mut self) -> ParseResult<ReservedNode> {
+        let keyword = self.expect_keyword("reserved")?;
+        let mut commas = vec![];
+        match self.peek().kind() {
+            TokenKind::String(_) => {
+                let mut names = vec![];
+                loop {
+                    names.push(self.parse_reserved_name()?);
+                    if !self.peek().is_rune(',') {
+                        break
+                    }
+                    commas.push(self.rune());
+                }
+                let semicolon = self.expect_rune(';')?;
+                return ReservedNode::new_names(keyword, names, commas, semicolon)
+            },
+            TokenKind::Ident(_) => {
+                let mut identifiers = vec![];
+                loop {
+                    identifiers.push(self.expect_ident()?);
+                    if !self.peek().is_rune(',') {
+                        break
+                    }
+                    commas.push(self.rune());
+                }
+                let semicolon = self.expect_rune(';')?;
+                return ReservedNode::new_identifiers(keyword, identifiers, commas, semicolon)
+            },
+            _ => {
+                let (ranges, commas) = self.parse_ranges()?;
+                let semicolon = self.expect_rune(';')?;
+                return ReservedNode::new_ranges(keyword, ranges, commas, semicolon)
+            }
+        }
+    }
+
+    fn parse_reserved_name(&mut self) -> ParseResult<StringLiteralNode> {
+        let mut components = self.parse_string_components()?;
+        if components.len() > 1 {
+            let second = &components[1];
+            return Err(Error::syntax(PosRange { start: second.start().clone(), end: second.end().clone() }, "reserved names must be a single string literal"))
+        }
+        return Ok(components.pop().unwrap())
+    }
+
+    fn parse_extension_range(&mut self) -> ParseResult<ExtensionRangeNode> {
+        let keyword = self.expect_keyword("extensions")?;
+        let (ranges, commas) = self.parse_ranges()?;
+        let opts = self.parse_optional_compact_options()?;
+        let semicolon = self.expect_rune(';')?;
+        return ExtensionRangeNode::new(keyword, ranges, commas, opts, semicolon)
+    }
+
+    fn parse_ranges(&mut self) -> ParseResult<(Vec<RangeNode>, Vec<RuneNode>)> {
+        let mut ranges = vec![];
+        let mut commas = vec![];
+        loop {
+            ranges.push(self.parse_range()?);
+            if !self.peek().is_rune(',') {
+                break
+            }
+            commas.push(self.rune());
+        }
+        return Ok((ranges, commas))
+    }
+
+    fn parse_range(&mut self) -> ParseResult<RangeNode> {
+        let start_val = self.parse_int_value()?;
+        if !self.peek().is_ident("to") {
+            return Ok(RangeNode::new_single(start_val))
+        }
+        let to = self.ident().to_keyword();
+        if self.peek().is_ident("max") {
+            let max = self.ident().to_keyword();
+            return Ok(RangeNode::new_to_max(start_val, to, max))
+        }
+        let end_val = self.parse_int_value()?;
+        return Ok(RangeNode::new(start_val, to, end_val))
+    }
+
+    fn parse_extend(&mut self) -> ParseResult<ExtendNode> {
+        let keyword = self.expect_keyword("extend")?;
+        let extendee = self.parse_type_name()?;
+        let open_brace = self.expect_rune('{')?;
+        let decls = self.parse_body(Parser::parse_extend_element, ExtendElement::Error)?;
+        let close_brace = self.rune();
+        return Ok(ExtendNode::new(keyword, extendee, open_brace, decls, close_brace))
+    }
+
+    fn parse_extend_element(&mut self) -> ParseResult<ExtendElement> {
+        let tok = self.peek();
+        if tok.is_rune(';') {
+            return Ok(ExtendElement::Empty(EmptyDeclNode::new(self.rune())))
+        }
+        if tok.is_ident("map") && self.peek_at(1).is_rune('<') {
+            let tok = tok.clone();
+            return Err(self.error_at(&tok, "map fields are not allowed in extend blocks"))
+        }
+        if self.is_ident() || tok.is_rune('.') {
+            return Ok(self.parse_field_decl()?.into_extend_element())
+        }
+        return Err(self.unexpected("field, ';' or '}'"))
+    }
+
+    /// Parses a possibly qualified name such as `Foo`, `foo.Bar` or
+    /// `.foo.Bar`.
+    fn parse_type_name(&mut self) -> ParseResult<Box<dyn IdentValueNodeTrait>> {
+        let leading_dot = if self.peek().is_rune('.') {
+            Some(self.rune())
+        } else {
+            None
+        };
+        let first = self.expect_ident()?;
+        if leading_dot.is_none() && !self.peek().is_rune('.') {
+            return Ok(Box::new(first))
+        }
+        let mut components = vec![first];
+        let mut dots = vec![];
+        while self.peek().is_rune('.') {
+            dots.push(self.rune());
+            components.push(self.expect_ident()?);
+        }
+        return Ok(Box::new(CompoundIdentNode::new(leading_dot, components, dots)?))
+    }
+
+    fn parse_option_decl(&mut self) -> ParseResult<OptionNode> {
+        let keyword = self.expect_keyword("option")?;
+        let name = self.parse_option_name()?;
+        let equals = self.expect_rune('=')?;
+        let val = self.parse_value()?;
+        let semicolon = self.expect_rune(';')?;
+        return Ok(OptionNode::new(keyword, name, equals, val, semicolon))
+    }
+
+    fn parse_option_name(&mut self) -> ParseResult<OptionNameNode> {
+        let mut parts = vec![self.parse_field_reference()?];
+        let mut dots = vec![];
+        while self.peek().is_rune('.') {
+            dots.push(self.rune());
+            parts.push(self.parse_field_reference()?);
+        }
+        return OptionNameNode::new(parts, dots)
+    }
+
+    fn parse_field_reference(&mut self) -> ParseResult<FieldReferenceNode> {
+        if self.peek().is_rune('(') {
+            let open = self.rune();
+            let name = self.parse_type_name()?;
+            let close = self.expect_rune(')')?;
+            return Ok(FieldReferenceNode::new_extension(open, name, close))
+        }
+        if self.is_ident() {
+            return Ok(FieldReferenceNode::new(self.ident()))
+        }
+        return Err(self.unexpected("identifier or '('"))
+    }
+
+    fn parse_compact_options(&mut self) -> ParseResult<CompactOptionsNode> {
+        let open = self.expect_rune('[')?;
+        let mut opts = vec![];
+        let mut commas = vec![];
+        loop {
+            let name = self.parse_option_name()?;
+            let equals = self.expect_rune('=')?;
+            let val = self.parse_value()?;
+            opts.push(OptionNode::new_compact(name, equals, val));
+            if !self.peek().is_rune(',') {
+                break
+            }
+            commas.push(self.rune());
+        }
+        let close = self.expect_rune(']')?;
+        return CompactOptionsNode::new(open, opts, commas, close)
+    }
+
+    fn parse_int_value(&mut self) -> ParseResult<Box<dyn IntValueNodeTrait>> {
+        if self.peek().is_rune('-') {
+            if let TokenKind::Int(_) = self.peek_at(1).kind() {
+                let minus = self.rune();
+                let uint = self.uint();
+                return Ok(Box::new(NegativeIntLiteralNode::new(minus, uint)?))
+            }
+            self.next();
+            return Err(self.unexpected("int literal"))
+        }
+        if let TokenKind::Int(_) = self.peek().kind() {
+            return Ok(Box::new(self.uint()))
+        }
+        return Err(self.unexpected("int literal"))
+    }
+
+    fn uint(&mut self) -> UintLiteralNode {
+        let tok = self.next();
+        let v = match tok.kind() {
+            TokenKind::Int(v) => *v,
+            _ => unreachable!("uint() called on a non-integer token")
+        };
+        return UintLiteralNode::new(v, tok.into_info())
+    }
+
+    fn float(&mut self) -> FloatLiteralNode {
+        let tok = self.next();
+        let v = match tok.kind() {
+            TokenKind::Float(v) => *v,
+            _ => unreachable!("float() called on a non-float token")
+        };
+        return FloatLiteralNode::new(v, tok.into_info())
+    }
+
+    fn parse_value(&mut self) -> ParseResult<ValueNode> {
+        let tok = self.peek().clone();
+        match tok.kind() {
+            TokenKind::String(_) => return self.parse_string_value(),
+            TokenKind::Int(_) => return Ok(ValueNode::Uint(self.uint())),
+            TokenKind::Float(_) => return Ok(ValueNode::Float(self.float())),
+            TokenKind::Ident(s) => {
+                if s == "true" || s == "false" {
+                    return Ok(ValueNode::Bool(BoolLiteralNode::new(self.ident().to_keyword())?))
+                }
+                if s == "inf" || s == "nan" {
+                    return Ok(ValueNode::SpecialFloat(SpecialFloatLiteralNode::new(self.ident().to_keyword())?))
+                }
+                return Ok(ValueNode::Identifier(self.ident()))
+            },
+            TokenKind::Rune('-') | TokenKind::Rune('+') => {
+                let sign_char = if tok.is_rune('-') { '-' } else { '+' };
+                let next = self.peek_at(1).clone();
+                match next.kind() {
+                    TokenKind::Int(v) => {
+                        let sign = self.rune();
+                        if sign_char == '-' && *v > i64::MIN.unsigned_abs() {
+                            // Like protoc, read negative values too big for
+                            // an int64 as doubles.
+                            let f = FloatLiteralNode::new(*v as f64, self.next().into_info());
+                            return Ok(ValueNode::SignedFloat(SignedFloatLiteralNode::new(sign, Box::new(f))))
+                        }
+                        let uint = self.uint();
+                        if sign_char == '-' {
+                            return Ok(ValueNode::NegativeInt(NegativeIntLiteralNode::new(sign, uint)?))
+                        }
+                        return Ok(ValueNode::PositiveUint(PositiveUintLiteralNode::new(sign, uint)))
+                    },
+                    TokenKind::Float(_) => {
+                        let sign = self.rune();
+                        let f = self.float();
+                        return Ok(ValueNode::SignedFloat(SignedFloatLiteralNode::new(sign, Box::new(f))))
+                    },
+                    TokenKind::Ident(s) if s == "inf" || s == "nan" => {
+                        let sign = self.rune();
+                        let special = SpecialFloatLiteralNode::new(self.ident().to_keyword())?;
+                        return Ok(ValueNode::SignedFloat(SignedFloatLiteralNode::new(sign, Box::new(special))))
+                    },
+                    _ => {
+                        self.next();
+                        return Err(self.unexpected("numeric literal, \"inf\" or \"nan\""))
+                    }
+                }
+            },
+            TokenKind::Rune('{') => return Ok(ValueNode::Message(self.parse_message_literal()?)),
+            _ => return Err(self.unexpected("option value"))
+        }
+    }
+
+    /// Parses a message value in text format, delimited by `{}` or `<>`.
+    fn parse_message_literal(&mut self) -> ParseResult<MessageLiteralNode> {
+        let close_char = if self.peek().is_rune('<') { '>' } else { '}' };
+        let open = self.rune();
+        let mut elements = vec![];
+        let mut seps = vec![];
+        while !self.peek().is_rune(close_char) {
+            elements.push(self.parse_message_field()?);
+            if self.peek().is_rune(',') || self.peek().is_rune(';') {
+                seps.push(Some(self.rune()));
+            } else {
+                seps.push(None);
+            }
+        }
+        let close = self.rune();
+        return MessageLiteralNode::new(open, elements, seps, close)
+    }
+
+    fn parse_message_field(&mut self) -> ParseResult<MessageFieldNode> {
+        let name = self.parse_message_field_name()?;
+        if self.peek().is_rune(':') {
+            let sep = self.rune();
+            let val = self.parse_message_field_value(true)?;
+            return Ok(MessageFieldNode::new(name, Some(sep), val))
+        }
+        // Without a ':', the value must be a message or a list of messages.
+        let tok = self.peek();
+        if !tok.is_rune('{') && !tok.is_rune('<') && !tok.is_rune('[') {
+            return Err(self.unexpected("':', '{', '<' or '['"))
+        }
+        let val = self.parse_message_field_value(false)?;
+        return Ok(MessageFieldNode::new(name, None, val))
+    }
+
+    /// Parses the name of a field in a message literal: a plain
+    /// identifier, an extension name like `[foo.bar]`, or an `Any` type
+    /// URL like `[type.googleapis.com/foo.Bar]`.
+    fn parse_message_field_name(&mut self) -> ParseResult<FieldReferenceNode> {
+        if self.peek().is_rune('[') {
+            let open = self.rune();
+            let name = self.parse_type_name()?;
+            if self.peek().is_rune('/') {
+                let slash = self.rune();
+                let type_name = self.parse_type_name()?;
+                let close = self.expect_rune(']')?;
+                return Ok(FieldReferenceNode::new_any_type_ref(open, name, slash, type_name, close))
+            }
+            let close = self.expect_rune(']')?;
+            return Ok(FieldReferenceNode::new_extension(open, name, close))
+        }
+        if self.is_ident() {
+            return Ok(FieldReferenceNode::new(self.ident()))
+        }
+        return Err(self.unexpected("field name, '[' or '}'"))
+    }
+
+    fn parse_message_field_value(&mut self, allow_scalars: bool) -> ParseResult<ValueNode> {
+        let tok = self.peek();
+        if tok.is_rune('{') || tok.is_rune('<') {
+            return Ok(ValueNode::Message(self.parse_message_literal()?))
+        }
+        if tok.is_rune('[') {
+            return Ok(ValueNode::Array(self.parse_array_literal(allow_scalars)?))
+        }
+        return self.parse_value()
+    }
+
+    /// Parses a list value inside a message literal. Lists may be empty
+    /// and may hold either scalars or messages.
+    fn parse_array_literal(&mut self, allow_scalars: bool) -> ParseResult<ArrayLiteralNode> {
+        let open = self.expect_rune('[')?;
+        let mut elements = vec![];
+        let mut commas = vec![];
+        if !self.peek().is_rune(']') {
+            loop {
+                let tok = self.peek();
+                if tok.is_rune('{') || tok.is_rune('<') {
+                    elements.push(ValueNode::Message(self.parse_message_literal()?));
+                } else if allow_scalars && !tok.is_rune('[') {
+                    elements.push(self.parse_value()?);
+                } else {
+                    return Err(self.unexpected("'{' or '<'"))
+                }
+                if !self.peek().is_rune(',') {
+                    break
+                }
+                commas.push(self.rune());
+            }
+        }
+        let close = self.expect_rune(']')?;
+        return ArrayLiteralNode::new(open, elements, commas, close)
+    }
+
+    /// Parses one or more adjacent string literals, which are concatenated.
+    fn parse_string_components(&mut self) -> ParseResult<Vec<StringLiteralNode>> {
+        let mut components = vec![];
+        while let TokenKind::String(bytes) = self.peek().kind() {
+            let bytes = bytes.clone();
+            let tok = self.next();
+            components.push(StringLiteralNode::from_bytes(bytes, tok.into_info()));
+        }
+        if components.is_empty() {
+            return Err(self.unexpected("string literal"))
+        }
+        return Ok(components)
+    }
+
+    fn parse_string_literal(&mut self) -> ParseResult<Box<dyn StringValueNodeTrait>> {
+        let mut components = self.parse_string_components()?;
+        if components.len() == 1 {
+            return Ok(Box::new(components.pop().unwrap()))
+        }
+        return Ok(Box::new(CompoundStringLiteralNode::new(components)?))
+    }
+
+    fn parse_string_value(&mut self) -> ParseResult<ValueNode> {
+        let mut components = self.parse_string_components()?;
+        if components.len() == 1 {
+            return Ok(ValueNode::String(components.pop().unwrap()))
+        }
+        return Ok(ValueNode::CompoundString(CompoundStringLiteralNode::new(components)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        return parse("a.proto", source).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn parses_declarations_in_order() {
+        let source = "message M {\n  message N {}\n  enum E { A = 0; }\n}\nenum F { B = 1 [(c) = -2]; }\n;\noption (d).e = \"f\";\n";
+        let file = parse("a.proto", source).unwrap();
+        let lines: Vec<i32> = file.decls().iter().map(|d| d.start().line()).collect();
+        assert_eq!(lines, vec![1, 5, 6, 7]);
+        assert_eq!(file.decls()[0].end().line(), 4);
+    }
+
+    #[test]
+    fn reports_where_parsing_stopped() {
+        assert_eq!(error("message M {\n  message = 1;\n}\n"), "a.proto:2:11: syntax error: unexpected '=', expecting identifier");
+        assert_eq!(error("enum E { A = x; }"), "a.proto:1:14: syntax error: unexpected identifier \"x\", expecting int literal");
+    }
+
+    #[test]
+    fn header_declarations() {
+        let file = parse("a.proto", "syntax = \"proto3\";\npackage a.b;\nimport public \"c.proto\";\nimport weak \"d.proto\";\n").unwrap();
+        assert!(file.syntax().is_some());
+        assert_eq!(file.decls().len(), 3);
+        assert!(parse("a.proto", "edition = \"2023\";\n").unwrap().edition().is_some());
+        assert_eq!(error("syntax = \"proto4\";"), "a.proto:1:10: syntax value must be \"proto2\" or \"proto3\", got \"proto4\"");
+        assert_eq!(error("package a;\npackage b;\n"), "a.proto:2:1: multiple package declarations");
+    }
+
+    #[test]
+    fn field_declarations() {
+        let file = parse("a.proto", "message M {\n  optional int32 a = 1 [default = 5, (x) = 6];\n  repeated group G = 2 { required string s = 1; }\n  map<string, M> m = 3;\n  .a.B b = 4;\n}\n").unwrap();
+        assert_eq!(file.decls().len(), 1);
+        assert_eq!(error("message M { group g = 1 {} }"), "a.proto:1:19: group names must start with a capital letter");
+        assert_eq!(error("extend M { map<string, string> m = 1; }"), "a.proto:1:12: map fields are not allowed in extend blocks");
+    }
+
+    #[test]
+    fn oneofs() {
+        assert!(parse("a.proto", "message M { oneof o { option (x) = 1; int32 a = 1; group G = 2 {} } }").is_ok());
+        assert_eq!(error("message M { oneof o { optional int32 a = 1; } }"), "a.proto:1:23: fields in oneofs must not have labels");
+        assert_eq!(error("message M { oneof o { map<int32, int32> m = 1; } }"), "a.proto:1:23: map fields are not allowed in oneofs");
+    }
+
+    #[test]
+    fn services() {
+        assert!(parse("a.proto", "service S {\n  option deprecated = true;\n  rpc A (M) returns (stream .a.M);\n  rpc B (stream M) returns (M) { option idempotency_level = NO_SIDE_EFFECTS; }\n}\n").is_ok());
+        assert_eq!(error("service S { rpc A (M) (M); }"), "a.proto:1:23: syntax error: unexpected '(', expecting \"returns\"");
+    }
+
+    #[test]
+    fn reserved_and_extension_ranges() {
+        assert!(parse("a.proto", "message M {\n  reserved 1, 5 to 10, 100 to max;\n  reserved \"a\", \"b\";\n  extensions 1000 to max [verification = UNVERIFIED];\n}\nenum E { A = 0; reserved -5 to -1; reserved C; }\n").is_ok());
+        assert_eq!(error("message M { reserved \"a\" \"b\"; }"), "a.proto:1:26: reserved names must be a single string literal");
+        assert_eq!(error("message M { reserved 1, \"a\"; }"), "a.proto:1:25: syntax error: unexpected string literal, expecting int literal");
+    }
+
+    fn value(source: &str) -> ValueNode {
+        let tokens = lexer::lex("a.proto", source).unwrap();
+        return Parser::new(tokens).parse_value().unwrap()
+    }
+
+    #[test]
+    fn value_kinds() {
+        assert!(matches!(value("\"a\""), ValueNode::String(_)));
+        assert!(matches!(value("\"a\" 'b'"), ValueNode::CompoundString(_)));
+        assert!(matches!(value("5"), ValueNode::Uint(_)));
+        assert!(matches!(value("+5"), ValueNode::PositiveUint(_)));
+        assert!(matches!(value("-5"), ValueNode::NegativeInt(_)));
+        assert!(matches!(value("1.5"), ValueNode::Float(_)));
+        assert!(matches!(value("inf"), ValueNode::SpecialFloat(_)));
+        assert!(matches!(value("-nan"), ValueNode::SignedFloat(_)));
+        assert!(matches!(value("false"), ValueNode::Bool(_)));
+        assert!(matches!(value("FOO"), ValueNode::Identifier(_)));
+    }
+
+    #[test]
+    fn message_literals() {
+        let source = "option (a) = {\n  b: 1\n  c { d: [1, 2] }\n  e: [{ f: \"g\" }, < f: 'h' >];\n  [ext.field]: 5,\n  [type.googleapis.com/pkg.Msg] { i: true }\n};\n";
+        assert!(parse("a.proto", source).is_ok());
+        assert!(matches!(value("{ a: 1 }"), ValueNode::Message(_)));
+        assert_eq!(error("option (a) = { b 1 };"), "a.proto:1:18: syntax error: unexpected int literal, expecting ':', '{', '<' or '['");
+    }
+
+    fn message_names(file: &FileNode) -> Vec<String> {
+        return file.decls().iter().filter_map(|d| match d {
+            FileElement::Message(m) => Some(m.name().as_terminal().unwrap().raw_text().to_string()),
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn recovery_reports_every_error() {
+        let source = "syntax = \"proto3\";\nmessage M {\n  int32 x = 1 2;\n  int32 y = ;\n  int32 z = 3;\n  string s = 4 [json_name = \"a\\qb\"];\n}\nmessage N { int32 q = 1; }\n";
+        let (file, errors) = parse_partial("a.proto", source);
+        let messages: Vec<String> = errors.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "a.proto:3:15: syntax error: unexpected int literal, expecting ';'",
+            "a.proto:4:13: syntax error: unexpected ';', expecting int literal",
+            "a.proto:6:31: invalid escape sequence \\q"
+        ]);
+        assert_eq!(message_names(&file), vec!["M", "N"]);
+        assert!(parse("a.proto", source).is_err());
+    }
+
+    #[test]
+    fn keyword_values() {
+        let file = parse("a.proto", "option (a) = inf;\noption (b) = -nan;\noption (c) = false;\n").unwrap();
+        let values: Vec<&ValueNode> = file.decls().iter().filter_map(|d| match d {
+            FileElement::Option(o) => Some(o.val()),
+            _ => None
+        }).collect();
+        assert!(matches!(values[0], ValueNode::SpecialFloat(_)));
+        assert!(matches!(values[1], ValueNode::SignedFloat(_)));
+        assert!(matches!(values[2], ValueNode::Bool(_)));
+    }
+}
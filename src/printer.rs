@@ -0,0 +1,70 @@
+//! Regenerates source text from an AST. Every terminal node keeps the
+//! comments and whitespace in front of it, plus its raw spelling, so
+//! printing a tree produced by the parser gives back the original file
+//! byte for byte.
+
+use crate::node::NodeTrait;
+use std::fmt::{self, Write};
+
+/// Returns the source text of `node` and everything below it.
+pub fn print(node: &dyn NodeTrait) -> String {
+    let mut out = String::new();
+    // Writing to a String cannot fail.
+    write(node, &mut out).unwrap();
+    return out
+}
+
+/// Writes the source text of `node` and everything below it to `out`.
+pub fn write<W: Write>(node: &dyn NodeTrait, out: &mut W) -> fmt::Result {
+    if let Some(terminal) = node.as_terminal() {
+        for c in terminal.leading_comments().iter() {
+            out.write_str(c.leading_whitespace())?;
+            out.write_str(c.text())?;
+        }
+        out.write_str(terminal.leading_whitespace())?;
+        out.write_str(terminal.raw_text())?;
+        for c in terminal.trailing_comments().iter() {
+            out.write_str(c.leading_whitespace())?;
+            out.write_str(c.text())?;
+        }
+        return Ok(())
+    }
+    if let Some(composite) = node.as_composite() {
+        for child in composite.children().iter() {
+            write(child.as_ref(), out)?;
+        }
+    }
+    return Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::wellknown;
+    use crate::parser::parse_partial;
+
+    #[test]
+    fn prints_the_source_back() {
+        let source = "// Leading.\nsyntax = \"proto2\";  // trailing\n\npackage  a.b ;\n\n/* detached */\n\nmessage M {\n\toptional int32 x = 1 [default = -inf, (c).d = { e: [1, 2] f <g: \"h\" \"i\"> }];\n  extensions 10 to max;\n  oneof o { group G = 2 { } }\n}\nservice S { rpc R (stream M) returns (M); }\n// Last.\n";
+        let file = parse("a.proto", source).unwrap();
+        assert_eq!(print(&file), source);
+    }
+
+    #[test]
+    fn prints_the_well_known_types_back() {
+        for name in wellknown::names() {
+            let source = wellknown::source(name).unwrap();
+            let file = parse(name, source).unwrap();
+            assert_eq!(print(&file), source, "{}", name);
+        }
+    }
+
+    #[test]
+    fn prints_a_partial_tree_back() {
+        let source = "message M {\n  int32 x = ;\n  int32 y = 2;\n}\n";
+        let (file, errors) = parse_partial("a.proto", source);
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(print(&file), source);
+    }
+}
@@ -0,0 +1,426 @@
+use std::any::Any;
+use crate::node::{NodeKind, CompositeNode, RuneNode, NodeTrait, TerminalNodeTrait, CompositeNodeTrait};
+use crate::identifiers::{IdentNode, KeywordNode};
+use crate::values::{IntValueNodeTrait, StringLiteralNode};
+use crate::options::CompactOptionsNode;
+use crate::source_pos::{SourcePos, Comment};
+use crate::error::{Error, check_separators, check_not_empty};
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut, walk_separated_mut};
+
+/// The `max` keyword, which stands for the largest allowed value at the
+/// end of a range.
+pub type MaxNode = KeywordNode;
+
+/// A single range in a `reserved` or `extensions` declaration, such as
+/// `5`, `1 to 5` or `10 to max`.
+#[derive(Clone)]
+pub struct RangeNode {
+    composite_node: CompositeNode,
+    start_val: Box<dyn IntValueNodeTrait>,
+    to: Option<KeywordNode>,
+    end_val: Option<Box<dyn IntValueNodeTrait>>,
+    max: Option<MaxNode>
+}
+
+impl RangeNode {
+    /// Returns a RangeNode for a single value, like `5`.
+    pub fn new_single(start_val: Box<dyn IntValueNodeTrait>) -> Self {
+        let mut comp = CompositeNode::new();
+        comp.push_child(start_val.as_node_trait());
+        return RangeNode {
+            composite_node: comp,
+            start_val,
+            to: None,
+            end_val: None,
+            max: None
+        }
+    }
+
+    /// Returns a RangeNode with an explicit end, like `1 to 5`.
+    pub fn new(start_val: Box<dyn IntValueNodeTrait>, to: KeywordNode, end_val: Box<dyn IntValueNodeTrait>) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![start_val.as_node_trait(), Box::new(to.clone()), end_val.as_node_trait()];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return RangeNode {
+            composite_node: comp,
+            start_val,
+            to: Some(to),
+            end_val: Some(end_val),
+            max: None
+        }
+    }
+
+    /// Returns a RangeNode that ends at `max`, like `10 to max`.
+    pub fn new_to_max(start_val: Box<dyn IntValueNodeTrait>, to: KeywordNode, max: MaxNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![start_val.as_node_trait(), Box::new(to.clone()), Box::new(max.clone())];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+        return RangeNode {
+            composite_node: comp,
+            start_val,
+            to: Some(to),
+            end_val: None,
+            max: Some(max)
+        }
+    }
+
+    pub fn start_value(&self) -> &dyn IntValueNodeTrait {
+        return self.start_val.as_ref()
+    }
+
+    pub fn to(&self) -> Option<&KeywordNode> {
+        return self.to.as_ref()
+    }
+
+    /// Returns the explicit end of the range. This is `None` both for a
+    /// single value and for a range that ends at `max`.
+    pub fn end_value(&self) -> Option<&dyn IntValueNodeTrait> {
+        return self.end_val.as_deref()
+    }
+
+    pub fn max(&self) -> Option<&MaxNode> {
+        return self.max.as_ref()
+    }
+
+    pub fn is_max(&self) -> bool {
+        return self.max.is_some()
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, self.start_val.as_mut());
+        walk_opt_mut(v, self.to.as_mut());
+        if let Some(end_val) = self.end_val.as_mut() {
+            walk_mut(v, end_val.as_mut());
+        }
+        walk_opt_mut(v, self.max.as_mut());
+        *self = match (self.to.clone(), self.end_val.clone(), self.max.clone()) {
+            (Some(to), Some(end_val), _) => RangeNode::new(self.start_val.clone(), to, end_val),
+            (Some(to), None, Some(max)) => RangeNode::new_to_max(self.start_val.clone(), to, max),
+            _ => RangeNode::new_single(self.start_val.clone())
+        };
+    }
+}
+
+impl NodeTrait for RangeNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_range(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_range_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Range
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A `reserved` declaration. It holds either ranges of numbers or names,
+/// never both. Names are string literals, except in files that use
+/// editions, where they are plain identifiers.
+#[derive(Clone)]
+pub struct ReservedNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    ranges: Vec<RangeNode>,
+    names: Vec<StringLiteralNode>,
+    identifiers: Vec<IdentNode>,
+    commas: Vec<RuneNode>,
+    semicolon: RuneNode
+}
+
+impl ReservedNode {
+    /// Returns a ReservedNode for reserved numbers, like
+    /// `reserved 1 to 5, 10;`.
+    pub fn new_ranges(keyword: KeywordNode, ranges: Vec<RangeNode>, commas: Vec<RuneNode>, semicolon: RuneNode) -> Result<Self, Error> {
+        check_not_empty(&ranges, NodeKind::Reserved, &[&keyword, &semicolon])?;
+        check_separators(&ranges, &commas)?;
+        let elements: Vec<Box<dyn NodeTrait>> = ranges.iter().map(|r| -> Box<dyn NodeTrait> { Box::new(r.clone()) }).collect();
+        return Ok(ReservedNode {
+            composite_node: reserved_children(&keyword, elements, &commas, &semicolon),
+            keyword,
+            ranges,
+            names: vec![],
+            identifiers: vec![],
+            commas,
+            semicolon
+        })
+    }
+
+    /// Returns a ReservedNode for reserved names, like
+    /// `reserved "foo", "bar";`.
+    pub fn new_names(keyword: KeywordNode, names: Vec<StringLiteralNode>, commas: Vec<RuneNode>, semicolon: RuneNode) -> Result<Self, Error> {
+        check_not_empty(&names, NodeKind::Reserved, &[&keyword, &semicolon])?;
+        check_separators(&names, &commas)?;
+        let elements: Vec<Box<dyn NodeTrait>> = names.iter().map(|n| -> Box<dyn NodeTrait> { Box::new(n.clone()) }).collect();
+        return Ok(ReservedNode {
+            composite_node: reserved_children(&keyword, elements, &commas, &semicolon),
+            keyword,
+            ranges: vec![],
+            names,
+            identifiers: vec![],
+            commas,
+            semicolon
+        })
+    }
+
+    /// Returns a ReservedNode for reserved names written as identifiers,
+    /// like `reserved foo, bar;`.
+    pub fn new_identifiers(keyword: KeywordNode, identifiers: Vec<IdentNode>, commas: Vec<RuneNode>, semicolon: RuneNode) -> Result<Self, Error> {
+        check_not_empty(&identifiers, NodeKind::Reserved, &[&keyword, &semicolon])?;
+        check_separators(&identifiers, &commas)?;
+        let elements: Vec<Box<dyn NodeTrait>> = identifiers.iter().map(|i| -> Box<dyn NodeTrait> { Box::new(i.clone()) }).collect();
+        return Ok(ReservedNode {
+            composite_node: reserved_children(&keyword, elements, &commas, &semicolon),
+            keyword,
+            ranges: vec![],
+            names: vec![],
+            identifiers,
+            commas,
+            semicolon
+        })
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn ranges(&self) -> &[RangeNode] {
+        return &self.ranges
+    }
+
+    pub fn names(&self) -> &[StringLiteralNode] {
+        return &self.names
+    }
+
+    pub fn identifiers(&self) -> &[IdentNode] {
+        return &self.identifiers
+    }
+
+    pub fn commas(&self) -> &[RuneNode] {
+        return &self.commas
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_separated_mut(v, &mut self.ranges, &mut self.commas);
+        walk_separated_mut(v, &mut self.names, &mut self.commas);
+        walk_separated_mut(v, &mut self.identifiers, &mut self.commas);
+        walk_mut(v, &mut self.semicolon);
+        // Visiting can't empty the lists or change how many commas there
+        // are, so rebuilding can't fail.
+        *self = if !self.names.is_empty() {
+            ReservedNode::new_names(self.keyword.clone(), self.names.clone(), self.commas.clone(), self.semicolon.clone()).unwrap()
+        } else if !self.identifiers.is_empty() {
+            ReservedNode::new_identifiers(self.keyword.clone(), self.identifiers.clone(), self.commas.clone(), self.semicolon.clone()).unwrap()
+        } else {
+            ReservedNode::new_ranges(self.keyword.clone(), self.ranges.clone(), self.commas.clone(), self.semicolon.clone()).unwrap()
+        };
+    }
+}
+
+// Interleaves the elements of a reserved declaration with the commas that
+// separate them.
+fn reserved_children(keyword: &KeywordNode, elements: Vec<Box<dyn NodeTrait>>, commas: &[RuneNode], semicolon: &RuneNode) -> CompositeNode {
+    let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(elements.len() + commas.len() + 2);
+    children.push(Box::new(keyword.clone()));
+    for (i, element) in elements.into_iter().enumerate() {
+        if i > 0 {
+            children.push(Box::new(commas[i - 1].clone()));
+        }
+        children.push(element);
+    }
+    children.push(Box::new(semicolon.clone()));
+
+    let mut comp = CompositeNode::new();
+    comp.push_children(children);
+    return comp
+}
+
+impl NodeTrait for ReservedNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_reserved(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_reserved_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Reserved
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// An `extensions` declaration, such as `extensions 100 to 199 [verification = UNVERIFIED];`.
+#[derive(Clone)]
+pub struct ExtensionRangeNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    ranges: Vec<RangeNode>,
+    commas: Vec<RuneNode>,
+    options: Option<CompactOptionsNode>,
+    semicolon: RuneNode
+}
+
+impl ExtensionRangeNode {
+    pub fn new(keyword: KeywordNode, ranges: Vec<RangeNode>, commas: Vec<RuneNode>, opts: Option<CompactOptionsNode>, semicolon: RuneNode) -> Result<Self, Error> {
+        check_not_empty(&ranges, NodeKind::ExtensionRange, &[&keyword, &semicolon])?;
+        check_separators(&ranges, &commas)?;
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(ranges.len() + commas.len() + 3);
+        children.push(Box::new(keyword.clone()));
+        for (i, range) in ranges.iter().enumerate() {
+            if i > 0 {
+                children.push(Box::new(commas[i - 1].clone()));
+            }
+            children.push(Box::new(range.clone()));
+        }
+        if let Some(opts) = opts.clone() {
+            children.push(Box::new(opts));
+        }
+        children.push(Box::new(semicolon.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return Ok(ExtensionRangeNode {
+            composite_node: comp,
+            keyword,
+            ranges,
+            commas,
+            options: opts,
+            semicolon
+        })
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn ranges(&self) -> &[RangeNode] {
+        return &self.ranges
+    }
+
+    pub fn commas(&self) -> &[RuneNode] {
+        return &self.commas
+    }
+
+    pub fn options(&self) -> Option<&CompactOptionsNode> {
+        return self.options.as_ref()
+    }
+
+    pub fn semicolon(&self) -> &RuneNode {
+        return &self.semicolon
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_separated_mut(v, &mut self.ranges, &mut self.commas);
+        walk_opt_mut(v, self.options.as_mut());
+        walk_mut(v, &mut self.semicolon);
+        // Visiting can't change how many ranges and commas there are.
+        *self = ExtensionRangeNode::new(self.keyword.clone(), self.ranges.clone(), self.commas.clone(), self.options.clone(), self.semicolon.clone()).unwrap();
+    }
+}
+
+impl NodeTrait for ExtensionRangeNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_extension_range(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_extension_range_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::ExtensionRange
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
@@ -0,0 +1,415 @@
+use std::any::Any;
+use crate::node::{NodeKind, CompositeNode, RuneNode, EmptyDeclNode, ErrorNode, NodeTrait, TerminalNodeTrait, CompositeNodeTrait, node_enum};
+use crate::identifiers::{IdentNode, KeywordNode, IdentValueNodeTrait};
+use crate::source_pos::{SourcePos, Comment};
+use crate::options::OptionNode;
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut};
+
+/// A `service` declaration and the RPC methods it defines.
+#[derive(Clone)]
+pub struct ServiceNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    name: IdentNode,
+    open_brace: RuneNode,
+    decls: Vec<ServiceElement>,
+    close_brace: RuneNode
+}
+
+impl ServiceNode {
+    pub fn new(keyword: KeywordNode, name: IdentNode, open_brace: RuneNode, decls: Vec<ServiceElement>, close_brace: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(4 + decls.len());
+        children.push(Box::new(keyword.clone()));
+        children.push(Box::new(name.clone()));
+        children.push(Box::new(open_brace.clone()));
+        for decl in decls.iter() {
+            children.push(decl.as_node_trait());
+        }
+        children.push(Box::new(close_brace.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return ServiceNode {
+            composite_node: comp,
+            keyword,
+            name,
+            open_brace,
+            decls,
+            close_brace
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn open_brace(&self) -> &RuneNode {
+        return &self.open_brace
+    }
+
+    pub fn decls(&self) -> &[ServiceElement] {
+        return &self.decls
+    }
+
+    pub fn close_brace(&self) -> &RuneNode {
+        return &self.close_brace
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.open_brace);
+        for decl in self.decls.iter_mut() {
+            walk_mut(v, decl.as_node_mut());
+        }
+        walk_mut(v, &mut self.close_brace);
+        *self = ServiceNode::new(self.keyword.clone(), self.name.clone(), self.open_brace.clone(), self.decls.clone(), self.close_brace.clone());
+    }
+}
+
+impl NodeTrait for ServiceNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_service(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_service_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::Service
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A declaration that may appear in the body of a service.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum ServiceElement {
+    Option(OptionNode),
+    RPC(RPCNode),
+    Empty(EmptyDeclNode),
+    Error(ErrorNode)
+}
+
+node_enum!(ServiceElement { Option, RPC, Empty, Error });
+
+/// An `rpc` declaration in a service. It ends either with a `;` or with a
+/// body of options in braces.
+#[derive(Clone)]
+pub struct RPCNode {
+    composite_node: CompositeNode,
+    keyword: KeywordNode,
+    name: IdentNode,
+    input: RPCTypeNode,
+    returns: KeywordNode,
+    output: RPCTypeNode,
+    semicolon: Option<RuneNode>,
+    open_brace: Option<RuneNode>,
+    decls: Vec<MethodElement>,
+    close_brace: Option<RuneNode>
+}
+
+impl RPCNode {
+    /// Returns an RPCNode for a method that ends with a `;`.
+    pub fn new(keyword: KeywordNode, name: IdentNode, input: RPCTypeNode, returns: KeywordNode, output: RPCTypeNode, semicolon: RuneNode) -> Self {
+        let children: Vec<Box<dyn NodeTrait>> = vec![
+            Box::new(keyword.clone()),
+            Box::new(name.clone()),
+            Box::new(input.clone()),
+            Box::new(returns.clone()),
+            Box::new(output.clone()),
+            Box::new(semicolon.clone())
+        ];
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return RPCNode {
+            composite_node: comp,
+            keyword,
+            name,
+            input,
+            returns,
+            output,
+            semicolon: Some(semicolon),
+            open_brace: None,
+            decls: vec![],
+            close_brace: None
+        }
+    }
+
+    /// Returns an RPCNode for a method that has a body in braces.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_body(keyword: KeywordNode, name: IdentNode, input: RPCTypeNode, returns: KeywordNode, output: RPCTypeNode, open_brace: RuneNode, decls: Vec<MethodElement>, close_brace: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(7 + decls.len());
+        children.push(Box::new(keyword.clone()));
+        children.push(Box::new(name.clone()));
+        children.push(Box::new(input.clone()));
+        children.push(Box::new(returns.clone()));
+        children.push(Box::new(output.clone()));
+        children.push(Box::new(open_brace.clone()));
+        for decl in decls.iter() {
+            children.push(decl.as_node_trait());
+        }
+        children.push(Box::new(close_brace.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return RPCNode {
+            composite_node: comp,
+            keyword,
+            name,
+            input,
+            returns,
+            output,
+            semicolon: None,
+            open_brace: Some(open_brace),
+            decls,
+            close_brace: Some(close_brace)
+        }
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword
+    }
+
+    pub fn name(&self) -> &IdentNode {
+        return &self.name
+    }
+
+    pub fn input(&self) -> &RPCTypeNode {
+        return &self.input
+    }
+
+    pub fn returns(&self) -> &KeywordNode {
+        return &self.returns
+    }
+
+    pub fn output(&self) -> &RPCTypeNode {
+        return &self.output
+    }
+
+    pub fn semicolon(&self) -> Option<&RuneNode> {
+        return self.semicolon.as_ref()
+    }
+
+    pub fn open_brace(&self) -> Option<&RuneNode> {
+        return self.open_brace.as_ref()
+    }
+
+    pub fn decls(&self) -> &[MethodElement] {
+        return &self.decls
+    }
+
+    pub fn close_brace(&self) -> Option<&RuneNode> {
+        return self.close_brace.as_ref()
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.keyword);
+        walk_mut(v, &mut self.name);
+        walk_mut(v, &mut self.input);
+        walk_mut(v, &mut self.returns);
+        walk_mut(v, &mut self.output);
+        walk_opt_mut(v, self.semicolon.as_mut());
+        walk_opt_mut(v, self.open_brace.as_mut());
+        for decl in self.decls.iter_mut() {
+            walk_mut(v, decl.as_node_mut());
+        }
+        walk_opt_mut(v, self.close_brace.as_mut());
+        *self = match (self.semicolon.clone(), self.open_brace.clone(), self.close_brace.clone()) {
+            (None, Some(open_brace), Some(close_brace)) => RPCNode::new_with_body(self.keyword.clone(), self.name.clone(), self.input.clone(), self.returns.clone(), self.output.clone(), open_brace, self.decls.clone(), close_brace),
+            (semicolon, _, _) => RPCNode::new(self.keyword.clone(), self.name.clone(), self.input.clone(), self.returns.clone(), self.output.clone(), semicolon.unwrap())
+        };
+    }
+}
+
+impl NodeTrait for RPCNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_rpc(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_rpc_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::RPC
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A declaration that may appear in the body of an RPC.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum MethodElement {
+    Option(OptionNode),
+    Empty(EmptyDeclNode),
+    Error(ErrorNode)
+}
+
+node_enum!(MethodElement { Option, Empty, Error });
+
+/// The parenthesized request or response type of an RPC, such as
+/// `(stream foo.Request)`.
+#[derive(Clone)]
+pub struct RPCTypeNode {
+    composite_node: CompositeNode,
+    open_paren: RuneNode,
+    stream: Option<KeywordNode>,
+    message_type: Box<dyn IdentValueNodeTrait>,
+    close_paren: RuneNode
+}
+
+impl RPCTypeNode {
+    pub fn new(open_paren: RuneNode, stream: Option<KeywordNode>, message_type: Box<dyn IdentValueNodeTrait>, close_paren: RuneNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(4);
+        children.push(Box::new(open_paren.clone()));
+        if let Some(stream) = stream.clone() {
+            children.push(Box::new(stream));
+        }
+        children.push(message_type.as_node_trait());
+        children.push(Box::new(close_paren.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return RPCTypeNode {
+            composite_node: comp,
+            open_paren,
+            stream,
+            message_type,
+            close_paren
+        }
+    }
+
+    pub fn open_paren(&self) -> &RuneNode {
+        return &self.open_paren
+    }
+
+    pub fn stream(&self) -> Option<&KeywordNode> {
+        return self.stream.as_ref()
+    }
+
+    pub fn is_stream(&self) -> bool {
+        return self.stream.is_some()
+    }
+
+    pub fn message_type(&self) -> &dyn IdentValueNodeTrait {
+        return self.message_type.as_ref()
+    }
+
+    pub fn close_paren(&self) -> &RuneNode {
+        return &self.close_paren
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.open_paren);
+        walk_opt_mut(v, self.stream.as_mut());
+        walk_mut(v, self.message_type.as_mut());
+        walk_mut(v, &mut self.close_paren);
+        *self = RPCTypeNode::new(self.open_paren.clone(), self.stream.clone(), self.message_type.clone(), self.close_paren.clone());
+    }
+}
+
+impl NodeTrait for RPCTypeNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_rpc_type(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_rpc_type_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::RPCType
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
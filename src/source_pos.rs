@@ -22,6 +22,22 @@ impl SourcePos {
 		}
 	}
 
+	pub fn filename(&self) -> &str {
+		return &self.filename
+	}
+
+	pub fn line(&self) -> i32 {
+		return self.line
+	}
+
+	pub fn col(&self) -> i32 {
+		return self.col
+	}
+
+	pub fn offset(&self) -> i32 {
+		return self.offset
+	}
+
 	pub fn set_line(&mut self, line: i32) -> &mut Self {
 		self.line = line;
 		self
@@ -37,11 +53,14 @@ impl SourcePos {
 		self
 	}
 
-	pub fn to_string(&self) -> String {
+}
+
+impl std::fmt::Display for SourcePos {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		if self.line <= 0 || self.col <= 0 {
-			return self.filename.clone()
+			return write!(f, "{}", self.filename)
 		}
-		return format!("{}:{}:{}", self.filename, self.line, self.col)
+		return write!(f, "{}:{}:{}", self.filename, self.line, self.col)
 	}
 }
 
@@ -67,6 +86,18 @@ impl Comment {
 		}
 	}
 
+	pub fn pos_range(&self) -> &PosRange {
+		return &self.pos_range
+	}
+
+	pub fn leading_whitespace(&self) -> &str {
+		return &self.leading_whitespace
+	}
+
+	pub fn text(&self) -> &str {
+		return &self.text
+	}
+
 	pub fn set_leading_whitespace(&mut self, lw: &str) -> &mut Self {
 		self.leading_whitespace = lw.to_string();
 		self
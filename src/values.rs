@@ -1,11 +1,13 @@
-use std::error::Error;
-
-use crate::node::{TerminalNode, TokenInfo, CompositeNode, NodeTrait, RuneNode};
-use crate::source_pos::{SourcePos, Comment};
-use crate::identifiers::KeywordNode;
+use std::any::Any;
+use crate::node::{NodeKind, TerminalNode, TokenInfo, CompositeNode, NodeTrait, RuneNode, TerminalNodeTrait, CompositeNodeTrait};
+use crate::source_pos::{SourcePos, PosRange, Comment};
+use crate::error::{Error, check_separators, check_not_empty};
+use crate::identifiers::{IdentNode, KeywordNode};
+use crate::options::FieldReferenceNode;
 use std::fmt::Debug;
 use dyn_clone::DynClone;
 use dyn_clone::clone_trait_object;
+use crate::visit::{Visitor, VisitorMut, walk_mut, walk_opt_mut, walk_all_mut};
 
 pub trait _ValueNodeTrait<T>: DynClone {
     fn value(self) -> T;
@@ -19,29 +21,61 @@ pub trait ValueNodeTrait<T>: _ValueNodeTrait<T> + NodeTrait {
 
 clone_trait_object!(<T> ValueNodeTrait<T>);
 
-pub trait StringValueNode {
-    fn as_string(self) -> String;
+pub trait StringValueNode: DynClone {
+    fn as_string(&self) -> String;
+    /// Returns the unescaped bytes of the string, which may not be valid
+    /// UTF-8. `as_string` replaces such bytes.
+    fn as_bytes(&self) -> Vec<u8>;
+}
+
+clone_trait_object!(StringValueNode);
+
+pub trait StringValueNodeTrait: StringValueNode + NodeTrait {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait>;
+    fn as_string_value_node(&self) -> Box<dyn StringValueNode>;
 }
 
+clone_trait_object!(StringValueNodeTrait);
+
 #[derive(Debug, Clone)]
 pub struct StringLiteralNode {
     terminal_node: TerminalNode,
-    val: String
+    val: String,
+    bytes: Vec<u8>
 }
 
 impl StringLiteralNode {
     pub fn new(val: &str, info: TokenInfo) -> Self {
+        return StringLiteralNode::from_bytes(val.as_bytes().to_vec(), info)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>, info: TokenInfo) -> Self {
         return StringLiteralNode {
             terminal_node: info.as_terminal_node(),
-            val: val.to_string()
+            val: String::from_utf8_lossy(&bytes).to_string(),
+            bytes
         }
     }
 }
 
+impl StringValueNodeTrait for StringLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_string_value_node(&self) -> Box<dyn StringValueNode> {
+        return Box::new(self.clone())
+    }
+}
+
 impl StringValueNode for StringLiteralNode {
-    fn as_string(self) -> String {
+    fn as_string(&self) -> String {
         return self.val.clone()
     }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        return self.bytes.clone()
+    }
 }
 
 impl _ValueNodeTrait<String> for StringLiteralNode {
@@ -50,13 +84,23 @@ impl _ValueNodeTrait<String> for StringLiteralNode {
     }
 }
 
+impl ValueNodeTrait<String> for StringLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<String>> {
+        return Box::new(self.clone())
+    }
+}
+
 impl NodeTrait for StringLiteralNode {
     fn start(&self) -> &SourcePos {
-        return &self.terminal_node.start()
+        return self.terminal_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.terminal_node.end()
+        return self.terminal_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -66,32 +110,66 @@ impl NodeTrait for StringLiteralNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.terminal_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.terminal_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.terminal_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_string_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_string_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::StringLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
 #[derive(Clone)]
 pub struct CompoundStringLiteralNode {
     composite_node: CompositeNode,
+    components: Vec<StringLiteralNode>,
     val: String
 }
 
 impl CompoundStringLiteralNode {
-    pub fn new(components: Vec<StringLiteralNode>) -> Result<Self, &'static str> {
-        if components.len() == 0 {
-            return Err("must have atlast one component")
-        }
+    pub fn new(components: Vec<StringLiteralNode>) -> Result<Self, Error> {
+        check_not_empty(&components, NodeKind::CompoundStringLiteral, &[])?;
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(components.len());
         let mut b = "".to_string();
-        for (i, c) in components.iter().enumerate() {
-            children[i] = Box::new(c.clone());
+        for c in components.iter() {
+            children.push(Box::new(c.clone()));
             b.push_str(&c.clone().value());
         }
         let mut comp:CompositeNode = CompositeNode::new();
         comp.push_children(children);
         return Ok(CompoundStringLiteralNode {
             composite_node: comp,
+            components,
             val: b
         })
     }
+
+    pub fn components(&self) -> &[StringLiteralNode] {
+        return &self.components
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_all_mut(v, &mut self.components);
+        // There is still at least one component, so this can't fail.
+        *self = CompoundStringLiteralNode::new(self.components.clone()).unwrap();
+    }
 }
 
 impl _ValueNodeTrait<String> for CompoundStringLiteralNode {
@@ -100,22 +178,92 @@ impl _ValueNodeTrait<String> for CompoundStringLiteralNode {
     }
 }
 
+impl ValueNodeTrait<String> for CompoundStringLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<String>> {
+        return Box::new(self.clone())
+    }
+}
+
+impl StringValueNodeTrait for CompoundStringLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_string_value_node(&self) -> Box<dyn StringValueNode> {
+        return Box::new(self.clone())
+    }
+}
+
 impl StringValueNode for CompoundStringLiteralNode {
-    fn as_string(self) -> String {
-        return self.val
+    fn as_string(&self) -> String {
+        return self.val.clone()
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        return self.components.iter().flat_map(|c| c.as_bytes()).collect()
     }
 }
 
-pub trait _IntValueNodeTrait {
+impl NodeTrait for CompoundStringLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_compound_string_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_compound_string_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::CompoundStringLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+pub trait _IntValueNodeTrait: DynClone {
     fn as_int64(&self) -> (i64, bool);
     fn as_uint64(&self) -> (u64, bool);
 }
 
+clone_trait_object!(_IntValueNodeTrait);
+
 pub trait IntValueNodeTrait: _IntValueNodeTrait + NodeTrait {
     fn as_node_trait(&self) -> Box<dyn NodeTrait>;
     fn as_int_value_node_trait(&self) -> Box<dyn _IntValueNodeTrait>;
 }
 
+clone_trait_object!(IntValueNodeTrait);
+
 #[derive(Clone)]
 pub struct UintLiteralNode {
     terminal_node: TerminalNode,
@@ -126,7 +274,7 @@ impl UintLiteralNode {
     pub fn new(val: u64, info: TokenInfo) -> Self {
         return UintLiteralNode {
             terminal_node: info.as_terminal_node(),
-            val: val
+            val
         }
     }
 }
@@ -137,13 +285,23 @@ impl _FloatValueNodeTrait for UintLiteralNode {
     }
 }
 
+impl FloatValueNodeTrait for UintLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_float_value_node_trait(&self) -> Box<dyn _FloatValueNodeTrait> {
+        return Box::new(self.clone())
+    }
+}
+
 impl NodeTrait for UintLiteralNode {
     fn start(&self) -> &SourcePos {
-        return &self.terminal_node.start()
+        return self.terminal_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.terminal_node.end()
+        return self.terminal_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -153,6 +311,30 @@ impl NodeTrait for UintLiteralNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.terminal_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.terminal_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.terminal_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_uint_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_uint_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::UintLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
 impl _ValueNodeTrait<u64> for UintLiteralNode {
@@ -161,9 +343,19 @@ impl _ValueNodeTrait<u64> for UintLiteralNode {
     }
 }
 
+impl ValueNodeTrait<u64> for UintLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<u64>> {
+        return Box::new(self.clone())
+    }
+}
+
 impl _IntValueNodeTrait for UintLiteralNode {
     fn as_int64(&self) -> (i64, bool) {
-        if self.val as i64 > i64::MAX {
+        if self.val > i64::MAX as u64 {
             return (0, false);
         }
         return (self.val as i64, true)
@@ -173,6 +365,17 @@ impl _IntValueNodeTrait for UintLiteralNode {
         return (self.val, true)
     }
 }
+
+impl IntValueNodeTrait for UintLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_int_value_node_trait(&self) -> Box<dyn _IntValueNodeTrait> {
+        return Box::new(self.clone())
+    }
+}
+
 #[derive(Clone)]
 pub struct PositiveUintLiteralNode {
     composite_node: CompositeNode,
@@ -183,16 +386,30 @@ pub struct PositiveUintLiteralNode {
 
 impl PositiveUintLiteralNode {
     pub fn new(sign: RuneNode, i: UintLiteralNode) -> PositiveUintLiteralNode {
-        let children: Vec<Box<NodeTrait>> = vec![Box::new(sign.clone()), Box::new(i.clone())];
+        let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(sign.clone()), Box::new(i.clone())];
         let mut comp = CompositeNode::new();
         comp.push_children(children);
         return PositiveUintLiteralNode {
             composite_node: comp,
             plus: sign,
-            uint: i.clone(),
-            val: i.val
+            val: i.val,
+            uint: i
         }
     }
+
+    pub fn plus(&self) -> &RuneNode {
+        return &self.plus
+    }
+
+    pub fn uint(&self) -> &UintLiteralNode {
+        return &self.uint
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.plus);
+        walk_mut(v, &mut self.uint);
+        *self = PositiveUintLiteralNode::new(self.plus.clone(), self.uint.clone());
+    }
 }
 
 impl _ValueNodeTrait<u64> for PositiveUintLiteralNode {
@@ -201,9 +418,19 @@ impl _ValueNodeTrait<u64> for PositiveUintLiteralNode {
     }
 }
 
+impl ValueNodeTrait<u64> for PositiveUintLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<u64>> {
+        return Box::new(self.clone())
+    }
+}
+
 impl _IntValueNodeTrait for PositiveUintLiteralNode {
     fn as_int64(&self) -> (i64, bool) {
-        if self.val as i64 > i64::MAX {
+        if self.val > i64::MAX as u64 {
             return (0, false)
         }
         return (self.val as i64, true)
@@ -213,8 +440,60 @@ impl _IntValueNodeTrait for PositiveUintLiteralNode {
     }
 }
 
+impl IntValueNodeTrait for PositiveUintLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_int_value_node_trait(&self) -> Box<dyn _IntValueNodeTrait> {
+        return Box::new(self.clone())
+    }
+}
+
+impl NodeTrait for PositiveUintLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_positive_uint_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_positive_uint_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::PositiveUintLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
 #[derive(Clone)]
-struct NegativeIntLiteralNode {
+pub struct NegativeIntLiteralNode {
     composite_node: CompositeNode,
     minus: RuneNode,
     uint: UintLiteralNode,
@@ -222,17 +501,44 @@ struct NegativeIntLiteralNode {
 }
 
 impl NegativeIntLiteralNode {
-    fn new(sign: RuneNode, i: UintLiteralNode) -> Self {
+    /// Fails if the magnitude is larger than that of `i64::MIN`.
+    pub fn new(sign: RuneNode, i: UintLiteralNode) -> Result<Self, Error> {
+        if i.val > i64::MIN.unsigned_abs() {
+            let pos_range = PosRange { start: sign.start().clone(), end: i.end().clone() };
+            return Err(Error::NumericOverflow { pos_range, literal: format!("-{}", i.val) })
+        }
+        return Ok(NegativeIntLiteralNode::build(sign, i))
+    }
+
+    // Builds the node without checking the magnitude. One too large only
+    // comes from a visitor replacing the digits; its value is clamped to
+    // `i64::MIN` and `as_int64` reports that it doesn't fit.
+    fn build(sign: RuneNode, i: UintLiteralNode) -> Self {
         let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(sign.clone()), Box::new(i.clone())];
         let mut comp = CompositeNode::new();
         comp.push_children(children);
+        let val = if i.val > i64::MIN.unsigned_abs() { i64::MIN } else { (i.val as i64).wrapping_neg() };
         return NegativeIntLiteralNode {
             composite_node: comp,
             minus: sign,
-            uint: i.clone(),
-            val: -1*(i.val as i64)
+            val,
+            uint: i
         }
     }
+
+    pub fn minus(&self) -> &RuneNode {
+        return &self.minus
+    }
+
+    pub fn uint(&self) -> &UintLiteralNode {
+        return &self.uint
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.minus);
+        walk_mut(v, &mut self.uint);
+        *self = NegativeIntLiteralNode::build(self.minus.clone(), self.uint.clone());
+    }
 }
 
 impl _ValueNodeTrait<i64> for NegativeIntLiteralNode {
@@ -241,9 +547,19 @@ impl _ValueNodeTrait<i64> for NegativeIntLiteralNode {
     }
 }
 
+impl ValueNodeTrait<i64> for NegativeIntLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<i64>> {
+        return Box::new(self.clone())
+    }
+}
+
 impl _IntValueNodeTrait for NegativeIntLiteralNode {
     fn as_int64(&self) -> (i64, bool) {
-        return (self.val, true)
+        return (self.val, self.uint.val <= i64::MIN.unsigned_abs())
     }
 
     fn as_uint64(&self) -> (u64, bool) {
@@ -254,13 +570,65 @@ impl _IntValueNodeTrait for NegativeIntLiteralNode {
     }
 }
 
+impl IntValueNodeTrait for NegativeIntLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_int_value_node_trait(&self) -> Box<dyn _IntValueNodeTrait> {
+        return Box::new(self.clone())
+    }
+}
+
+impl NodeTrait for NegativeIntLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_negative_int_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_negative_int_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::NegativeIntLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
 pub trait _FloatValueNodeTrait: DynClone {
     fn as_float(&self) -> f64;
 }
 
 clone_trait_object!(_FloatValueNodeTrait);
 
-impl Debug for _FloatValueNodeTrait {
+impl Debug for dyn _FloatValueNodeTrait {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("_FloatValueNodeTrait")
             .field("as_float", &self.as_float())
@@ -294,7 +662,7 @@ impl FloatLiteralNode {
     pub fn new(val: f64, info: TokenInfo) -> Self {
         return FloatLiteralNode {
             terminal_node: info.as_terminal_node(),
-            val: val
+            val
         }
     }
 }
@@ -321,13 +689,23 @@ impl _ValueNodeTrait<f64> for FloatLiteralNode {
     }
 }
 
+impl ValueNodeTrait<f64> for FloatLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<f64>> {
+        return Box::new(self.clone())
+    }
+}
+
 impl NodeTrait for FloatLiteralNode {
     fn start(&self) -> &SourcePos {
-        return &self.terminal_node.start()
+        return self.terminal_node.start()
     }
 
     fn end(&self) -> &SourcePos {
-        return &self.terminal_node.end()
+        return self.terminal_node.end()
     }
 
     fn leading_comments(&self) -> Vec<Comment> {
@@ -337,6 +715,30 @@ impl NodeTrait for FloatLiteralNode {
     fn trailing_comments(&self) -> Vec<Comment> {
         return self.terminal_node.trailing_comments()
     }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.terminal_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.terminal_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_float_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_float_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::FloatLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
 #[derive(Clone)]
@@ -346,17 +748,24 @@ pub struct SpecialFloatLiteralNode {
 }
 
 impl SpecialFloatLiteralNode {
-    pub fn new(name: KeywordNode) -> Self {
-        let f: f64;
-        if name.clone().value() == "inf" {
-            f = f64::INFINITY
-        } else {
-            f = f64::NAN
-        }
-        return SpecialFloatLiteralNode {
+    /// Fails unless the keyword is `inf` or `nan`.
+    pub fn new(name: KeywordNode) -> Result<Self, Error> {
+        let f = match name.clone().value().as_str() {
+            "inf" => f64::INFINITY,
+            "nan" => f64::NAN,
+            other => {
+                let pos_range = PosRange { start: name.start().clone(), end: name.end().clone() };
+                return Err(Error::syntax(pos_range, &format!("expected \"inf\" or \"nan\", found \"{}\"", other)))
+            }
+        };
+        return Ok(SpecialFloatLiteralNode {
             keyword_node: name,
             val: f,
-        }
+        })
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword_node
     }
 }
 
@@ -366,12 +775,74 @@ impl _FloatValueNodeTrait for SpecialFloatLiteralNode {
     }
 }
 
+impl FloatValueNodeTrait for SpecialFloatLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_float_value_node_trait(&self) -> Box<dyn _FloatValueNodeTrait> {
+        return Box::new(self.clone())
+    }
+}
+
 impl _ValueNodeTrait<f64> for SpecialFloatLiteralNode {
     fn value(self) -> f64 {
         return self.val
     }
 }
 
+impl ValueNodeTrait<f64> for SpecialFloatLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<f64>> {
+        return Box::new(self.clone())
+    }
+}
+
+impl NodeTrait for SpecialFloatLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.keyword_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.keyword_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.keyword_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.keyword_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.keyword_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.keyword_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_special_float_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_special_float_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::SpecialFloatLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SignedFloatLiteralNode {
     composite_node: CompositeNode,
@@ -384,9 +855,8 @@ impl SignedFloatLiteralNode {
     pub fn new(sign: RuneNode, f: Box<dyn FloatValueNodeTrait>) -> Self {
         let children: Vec<Box<dyn NodeTrait>> = vec![Box::new(sign.clone()), f.as_node_trait()];
         let mut val = f.as_float_value_node_trait().as_float();
-        let comp_chars: Vec<char> = "-".chars().collect();
-        if sign.clone().rune() == comp_chars[0] {
-            val = -1 as f64 * val;
+        if sign.rune() == '-' {
+            val = -val;
         }
         let mut comp = CompositeNode::new();
         comp.push_children(children);
@@ -397,6 +867,20 @@ impl SignedFloatLiteralNode {
             val
         }
     }
+
+    pub fn sign(&self) -> &RuneNode {
+        return &self.sign
+    }
+
+    pub fn float(&self) -> &dyn FloatValueNodeTrait {
+        return self.float.as_ref()
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.sign);
+        walk_mut(v, self.float.as_mut());
+        *self = SignedFloatLiteralNode::new(self.sign.clone(), self.float.clone());
+    }
 }
 
 impl _ValueNodeTrait<f64> for SignedFloatLiteralNode {
@@ -405,24 +889,99 @@ impl _ValueNodeTrait<f64> for SignedFloatLiteralNode {
     }
 }
 
+impl ValueNodeTrait<f64> for SignedFloatLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<f64>> {
+        return Box::new(self.clone())
+    }
+}
+
 impl _FloatValueNodeTrait for SignedFloatLiteralNode {
     fn as_float(&self) -> f64 {
         return self.val
     }
 }
 
+impl FloatValueNodeTrait for SignedFloatLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_float_value_node_trait(&self) -> Box<dyn _FloatValueNodeTrait> {
+        return Box::new(self.clone())
+    }
+}
+
+impl NodeTrait for SignedFloatLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_signed_float_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_signed_float_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::SignedFloatLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
 #[derive(Clone)]
-struct BoolLiteralNode {
+pub struct BoolLiteralNode {
     keyword_node: KeywordNode,
     val: bool
 }
 
 impl BoolLiteralNode {
-    pub fn new(name: KeywordNode) -> Self {
-        return BoolLiteralNode {
-            keyword_node: name.clone(),
-            val: name.value() == "true"
-        }
+    /// Fails unless the keyword is `true` or `false`.
+    pub fn new(name: KeywordNode) -> Result<Self, Error> {
+        let val = match name.clone().value().as_str() {
+            "true" => true,
+            "false" => false,
+            other => {
+                let pos_range = PosRange { start: name.start().clone(), end: name.end().clone() };
+                return Err(Error::syntax(pos_range, &format!("expected \"true\" or \"false\", found \"{}\"", other)))
+            }
+        };
+        return Ok(BoolLiteralNode {
+            keyword_node: name,
+            val
+        })
+    }
+
+    pub fn keyword(&self) -> &KeywordNode {
+        return &self.keyword_node
     }
 }
 
@@ -432,22 +991,213 @@ impl _ValueNodeTrait<bool> for BoolLiteralNode {
     }
 }
 
+impl ValueNodeTrait<bool> for BoolLiteralNode {
+    fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return Box::new(self.clone())
+    }
+
+    fn as_value_node_trait(&self) -> Box<dyn _ValueNodeTrait<bool>> {
+        return Box::new(self.clone())
+    }
+}
+
+impl NodeTrait for BoolLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.keyword_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.keyword_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.keyword_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.keyword_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.keyword_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.keyword_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_bool_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_bool_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::BoolLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// Expands `$body` for whichever node a `ValueNode` holds, with `$v`
+/// bound to a reference to it.
+macro_rules! with_value_node {
+    ($val:expr, $v:ident => $body:expr) => {
+        match $val {
+            ValueNode::String($v) => $body,
+            ValueNode::CompoundString($v) => $body,
+            ValueNode::Uint($v) => $body,
+            ValueNode::PositiveUint($v) => $body,
+            ValueNode::NegativeInt($v) => $body,
+            ValueNode::Float($v) => $body,
+            ValueNode::SpecialFloat($v) => $body,
+            ValueNode::SignedFloat($v) => $body,
+            ValueNode::Bool($v) => $body,
+            ValueNode::Identifier($v) => $body,
+            ValueNode::Array($v) => $body,
+            ValueNode::Message($v) => $body
+        }
+    };
+}
+
+/// The value of an option, or of a field inside a message literal. It can
+/// hold any kind of value, so a single list of options can mix types.
+#[derive(Clone)]
+pub enum ValueNode {
+    String(StringLiteralNode),
+    CompoundString(CompoundStringLiteralNode),
+    Uint(UintLiteralNode),
+    PositiveUint(PositiveUintLiteralNode),
+    NegativeInt(NegativeIntLiteralNode),
+    Float(FloatLiteralNode),
+    SpecialFloat(SpecialFloatLiteralNode),
+    SignedFloat(SignedFloatLiteralNode),
+    Bool(BoolLiteralNode),
+    Identifier(IdentNode),
+    Array(ArrayLiteralNode),
+    Message(MessageLiteralNode)
+}
+
+impl ValueNode {
+    pub fn as_node_trait(&self) -> Box<dyn NodeTrait> {
+        return with_value_node!(self, v => Box::new(v.clone()))
+    }
+
+    /// Returns the node held in the value.
+    pub fn as_node(&self) -> &dyn NodeTrait {
+        return with_value_node!(self, v => v)
+    }
+
+    pub fn as_node_mut(&mut self) -> &mut dyn NodeTrait {
+        return with_value_node!(self, v => v)
+    }
+
+    /// Returns the value as a string if it is a string literal, simple or
+    /// compound.
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            ValueNode::String(v) => return Some(v.as_string()),
+            ValueNode::CompoundString(v) => return Some(v.as_string()),
+            _ => return None
+        }
+    }
+
+    /// Returns the value as an integer node if it is an integer literal.
+    pub fn as_int_value(&self) -> Option<&dyn IntValueNodeTrait> {
+        match self {
+            ValueNode::Uint(v) => return Some(v),
+            ValueNode::PositiveUint(v) => return Some(v),
+            ValueNode::NegativeInt(v) => return Some(v),
+            _ => return None
+        }
+    }
+
+    /// Returns the value as a float node if it is any numeric literal.
+    /// Integers are accepted because they are valid float values.
+    pub fn as_float_value(&self) -> Option<&dyn FloatValueNodeTrait> {
+        match self {
+            ValueNode::Uint(v) => return Some(v),
+            ValueNode::Float(v) => return Some(v),
+            ValueNode::SpecialFloat(v) => return Some(v),
+            ValueNode::SignedFloat(v) => return Some(v),
+            _ => return None
+        }
+    }
+
+    /// Returns the node's value if it is a string literal, simple or
+    /// compound.
+    pub fn as_string_value(&self) -> Option<&dyn StringValueNodeTrait> {
+        match self {
+            ValueNode::String(v) => return Some(v),
+            ValueNode::CompoundString(v) => return Some(v),
+            _ => return None
+        }
+    }
+}
+
+impl NodeTrait for ValueNode {
+    fn start(&self) -> &SourcePos {
+        return with_value_node!(self, v => v.start())
+    }
+
+    fn end(&self) -> &SourcePos {
+        return with_value_node!(self, v => v.end())
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return with_value_node!(self, v => v.leading_comments())
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return with_value_node!(self, v => v.trailing_comments())
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return with_value_node!(self, v => v.as_terminal())
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return with_value_node!(self, v => v.as_composite())
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        with_value_node!(self, n => n.accept(v))
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        with_value_node!(self, n => n.accept_mut(v))
+    }
+
+    fn kind(&self) -> NodeKind {
+        return with_value_node!(self, v => v.kind())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return with_value_node!(self, v => v.as_any())
+    }
+}
+
 #[derive(Clone)]
-struct ArrayLiteralNode<T> {
+pub struct ArrayLiteralNode {
     composite_node: CompositeNode,
     open_bracket: RuneNode,
-    elements: Vec<Box<dyn ValueNodeTrait<T>>>,
+    elements: Vec<ValueNode>,
     commas: Vec<RuneNode>,
     close_bracket: RuneNode
 }
 
-impl<T> ArrayLiteralNode<T> {
-    fn new(open_bracket: RuneNode, vals: Vec<Box<dyn ValueNodeTrait<T>>>, commas: Vec<RuneNode>, close_bracket: RuneNode) -> Self {
+impl ArrayLiteralNode {
+    pub fn new(open_bracket: RuneNode, vals: Vec<ValueNode>, commas: Vec<RuneNode>, close_bracket: RuneNode) -> Result<Self, Error> {
+        check_separators(&vals, &commas)?;
         let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity((vals.len()*2) + 1);
         children.push(Box::new(open_bracket.clone()));
         for (i, val) in vals.iter().enumerate() {
             if i > 0 {
-                children.push(commas.get(i-1).unwrap().as_node_trait());
+                children.push(Box::new(commas[i-1].clone()));
             }
             children.push(val.as_node_trait());
         }
@@ -456,74 +1206,220 @@ impl<T> ArrayLiteralNode<T> {
         let mut comp = CompositeNode::new();
         comp.push_children(children);
 
-        return ArrayLiteralNode {
+        return Ok(ArrayLiteralNode {
             composite_node: comp,
             open_bracket,
             elements: vals,
-            commas: commas,
+            commas,
             close_bracket
+        })
+    }
+
+    pub fn open_bracket(&self) -> &RuneNode {
+        return &self.open_bracket
+    }
+
+    pub fn elements(&self) -> &[ValueNode] {
+        return &self.elements
+    }
+
+    pub fn commas(&self) -> &[RuneNode] {
+        return &self.commas
+    }
+
+    pub fn close_bracket(&self) -> &RuneNode {
+        return &self.close_bracket
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.open_bracket);
+        let mut commas = self.commas.iter_mut();
+        for el in self.elements.iter_mut() {
+            walk_mut(v, el.as_node_mut());
+            walk_opt_mut(v, commas.next());
         }
+        walk_mut(v, &mut self.close_bracket);
+        // Visiting can't change how many elements and commas there are.
+        *self = ArrayLiteralNode::new(self.open_bracket.clone(), self.elements.clone(), self.commas.clone(), self.close_bracket.clone()).unwrap();
     }
 }
 
-impl<T: Clone> _ValueNodeTrait<Vec<Box<dyn ValueNodeTrait<T>>>> for ArrayLiteralNode<T> {
-    fn value(self) -> Vec<Box<dyn ValueNodeTrait<T>>> {
+impl _ValueNodeTrait<Vec<ValueNode>> for ArrayLiteralNode {
+    fn value(self) -> Vec<ValueNode> {
         return self.elements
     }
 }
 
-struct MessageLiteralNode<T> {
+impl NodeTrait for ArrayLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_array_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_array_literal_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::ArrayLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
+}
+
+/// A message value in text format, such as `{ foo: 1 bar { baz: "x" } }`.
+/// It may be delimited by braces or angle brackets.
+#[derive(Clone)]
+pub struct MessageLiteralNode {
     composite_node: CompositeNode,
     open: RuneNode,
-    elements: Vec<MessageFieldNode<T>>,
-    seps: Vec<RuneNode>,
+    elements: Vec<MessageFieldNode>,
+    // One entry per element: the optional ',' or ';' that follows it.
+    seps: Vec<Option<RuneNode>>,
     close: RuneNode
 }
 
-impl<T> MessageLiteralNode<T> { 
-  fn new(open_sym: RuneNode, vals: Vec<MessageFieldNode<T>>, seps: Vec<RuneNode>, close_sym: RuneNode) -> Self {
-    let mut num_children = vals.len() + 2;
-    for (_, sep) in seps.iter().enumerate() {
-        num_children += 1;
+impl MessageLiteralNode {
+    pub fn new(open_sym: RuneNode, vals: Vec<MessageFieldNode>, seps: Vec<Option<RuneNode>>, close_sym: RuneNode) -> Result<Self, Error> {
+        if seps.len() != vals.len() {
+            let pos_range = PosRange { start: open_sym.start().clone(), end: close_sym.end().clone() };
+            return Err(Error::MismatchedSeparators { pos_range, expected: vals.len(), found: seps.len() })
+        }
+        let num_children = (vals.len() * 2) + 2;
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(num_children);
+        children.push(Box::new(open_sym.clone()));
+        for (i, val) in vals.iter().enumerate() {
+            children.push(Box::new(val.clone()));
+            if let Some(sep) = seps[i].clone() {
+                children.push(Box::new(sep));
+            }
+        }
+        children.push(Box::new(close_sym.clone()));
+
+        let mut comp = CompositeNode::new();
+        comp.push_children(children);
+
+        return Ok(MessageLiteralNode {
+            composite_node: comp,
+            open: open_sym,
+            elements: vals,
+            seps,
+            close: close_sym
+        })
     }
-    let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(num_children);
-    children.push(Box::new(open_sym.clone()));
-    for (i, val) in vals.iter().enumerate() {
-        //children.push(val);
-        children.push(seps.get(i).unwrap().as_node_trait());
+
+    pub fn open(&self) -> &RuneNode {
+        return &self.open
     }
-    children.push(Box::new(close_sym.clone()));
 
-    let mut comp = CompositeNode::new();
-    comp.push_children(children);
+    pub fn elements(&self) -> &[MessageFieldNode] {
+        return &self.elements
+    }
+
+    pub fn seps(&self) -> &[Option<RuneNode>] {
+        return &self.seps
+    }
+
+    pub fn close(&self) -> &RuneNode {
+        return &self.close
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.open);
+        for (el, sep) in self.elements.iter_mut().zip(self.seps.iter_mut()) {
+            walk_mut(v, el);
+            walk_opt_mut(v, sep.as_mut());
+        }
+        walk_mut(v, &mut self.close);
+        // Visiting can't change how many elements and separators there are.
+        *self = MessageLiteralNode::new(self.open.clone(), self.elements.clone(), self.seps.clone(), self.close.clone()).unwrap();
+    }
+}
+
+impl NodeTrait for MessageLiteralNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_message_literal(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_message_literal_mut(self);
+    }
 
-    return MessageLiteralNode {
-        composite_node: comp,
-        open: open_sym,
-        elements: vals,
-        seps: seps,
-        close: close_sym
+    fn kind(&self) -> NodeKind {
+        return NodeKind::MessageLiteral
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
     }
-  }
 }
 
-pub struct MessageFieldNode<T> {
+/// A single field in a message literal, such as `foo: 1` or `bar { }`.
+/// The `:` is optional when the value is a message or a list of
+/// messages.
+#[derive(Clone)]
+pub struct MessageFieldNode {
     composite_node: CompositeNode,
-    //name: FieldReferenceNode,
+    name: FieldReferenceNode,
     sep: Option<RuneNode>,
-    val: Box<dyn ValueNodeTrait<T>>
+    val: ValueNode
 }
 
-impl<T> MessageFieldNode<T> {
-    fn new(sep: Option<RuneNode>, val: Box<dyn ValueNodeTrait<T>>) -> Self {
-        let mut num_children = 2;
-        if sep.is_some() {
-            num_children += 1;
-        }
-        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(num_children);
-        //children.push(Box::new(name));
-        if sep.is_some() {
-            children.push(sep.clone().unwrap().as_node_trait());
+impl MessageFieldNode {
+    pub fn new(name: FieldReferenceNode, sep: Option<RuneNode>, val: ValueNode) -> Self {
+        let mut children: Vec<Box<dyn NodeTrait>> = Vec::with_capacity(3);
+        children.push(Box::new(name.clone()));
+        if let Some(s) = sep.clone() {
+            children.push(Box::new(s));
         }
         children.push(val.as_node_trait());
 
@@ -532,9 +1428,110 @@ impl<T> MessageFieldNode<T> {
 
         return MessageFieldNode {
             composite_node: comp,
-            sep: sep,
-            val: val
+            name,
+            sep,
+            val
         }
     }
+
+    pub fn name(&self) -> &FieldReferenceNode {
+        return &self.name
+    }
+
+    pub fn sep(&self) -> Option<&RuneNode> {
+        return self.sep.as_ref()
+    }
+
+    pub fn val(&self) -> &ValueNode {
+        return &self.val
+    }
+
+    pub(crate) fn walk_children_mut(&mut self, v: &mut dyn VisitorMut) {
+        walk_mut(v, &mut self.name);
+        walk_opt_mut(v, self.sep.as_mut());
+        walk_mut(v, self.val.as_node_mut());
+        *self = MessageFieldNode::new(self.name.clone(), self.sep.clone(), self.val.clone());
+    }
+}
+
+impl NodeTrait for MessageFieldNode {
+    fn start(&self) -> &SourcePos {
+        return self.composite_node.start()
+    }
+
+    fn end(&self) -> &SourcePos {
+        return self.composite_node.end()
+    }
+
+    fn leading_comments(&self) -> Vec<Comment> {
+        return self.composite_node.leading_comments()
+    }
+
+    fn trailing_comments(&self) -> Vec<Comment> {
+        return self.composite_node.trailing_comments()
+    }
+
+    fn as_terminal(&self) -> Option<&dyn TerminalNodeTrait> {
+        return self.composite_node.as_terminal()
+    }
+
+    fn as_composite(&self) -> Option<&dyn CompositeNodeTrait> {
+        return self.composite_node.as_composite()
+    }
+
+    fn accept(&self, v: &mut dyn Visitor) {
+        v.visit_message_field(self);
+    }
+
+    fn accept_mut(&mut self, v: &mut dyn VisitorMut) {
+        v.visit_message_field_mut(self);
+    }
+
+    fn kind(&self) -> NodeKind {
+        return NodeKind::MessageField
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identifiers::IdentNode;
+    use crate::file::FileElement;
+    use crate::lexer::lex;
+    use crate::parser::parse;
+
+    #[test]
+    fn keyword_literals_check_their_keyword() {
+        let tokens = lex("a.proto", "true x").unwrap();
+        let keyword = |i: usize| IdentNode::new(tokens[i].info().raw_text(), tokens[i].info().clone());
+        assert!(BoolLiteralNode::new(keyword(0)).unwrap().value());
+        assert_eq!(BoolLiteralNode::new(keyword(1)).err().unwrap().to_string(), "a.proto:1:6: expected \"true\" or \"false\", found \"x\"");
+        assert_eq!(SpecialFloatLiteralNode::new(keyword(1)).err().unwrap().to_string(), "a.proto:1:6: expected \"inf\" or \"nan\", found \"x\"");
+    }
+
+    // Replaces every unsigned literal with one too large to negate.
+    struct Enlarge;
+
+    impl VisitorMut for Enlarge {
+        fn visit_uint_literal_mut(&mut self, node: &mut UintLiteralNode) {
+            let mut info = TokenInfo::new(PosRange { start: node.start().clone(), end: node.end().clone() });
+            info.set_raw_text("9223372036854775809");
+            *node = UintLiteralNode::new(9223372036854775809, info);
+        }
+    }
+
+    #[test]
+    fn rebuilt_negative_literals_may_overflow() {
+        let mut file = parse("a.proto", "option (a) = -5;").unwrap();
+        walk_mut(&mut Enlarge, &mut file);
+        let value = match &file.decls()[0] {
+            FileElement::Option(o) => o.val().as_int_value().unwrap(),
+            _ => panic!("expected an option")
+        };
+        assert_eq!(value.as_int64(), (i64::MIN, false));
+    }
+}
@@ -0,0 +1,971 @@
+//! Traversal of the AST.
+//!
+//! [`Visitor`] has a `visit_*` method for every concrete node type. Each
+//! one defaults to the matching `walk_*` function, which visits the node's
+//! children in source order, so an implementation only overrides the
+//! methods for the nodes it is interested in. An override that still wants
+//! the children visited calls the `walk_*` function itself.
+//!
+//! [`VisitorMut`] is the same for changing a tree in place. A node that is
+//! walked with [`walk_mut`] rebuilds itself from its children afterwards,
+//! so its positions and the children returned by
+//! [`CompositeNodeTrait::children`](crate::node::CompositeNodeTrait::children)
+//! reflect any change made below it.
+//!
+//! Both kinds of visitor also have `pre_visit` and `post_visit` hooks that
+//! [`walk`] and [`walk_mut`] call around every node, whatever its type.
+
+use crate::node::{NodeTrait, CompositeNodeTrait, TerminalNode, RuneNode, CompositeNode, EmptyDeclNode, ErrorNode};
+use crate::identifiers::{IdentNode, CompoundIdentNode};
+use crate::values::{StringLiteralNode, CompoundStringLiteralNode, UintLiteralNode, PositiveUintLiteralNode, NegativeIntLiteralNode, FloatLiteralNode, SpecialFloatLiteralNode, SignedFloatLiteralNode, BoolLiteralNode, ArrayLiteralNode, MessageLiteralNode, MessageFieldNode};
+use crate::options::{OptionNode, OptionNameNode, FieldReferenceNode, CompactOptionsNode};
+use crate::file::{FileNode, SyntaxNode, EditionNode, PackageNode, ImportNode};
+use crate::message::{MessageNode, MessageBody, ExtendNode};
+use crate::field::{FieldNode, GroupNode, MapTypeNode, MapFieldNode};
+use crate::oneof::OneOfNode;
+use crate::enums::{EnumNode, EnumValueNode};
+use crate::ranges::{RangeNode, ReservedNode, ExtensionRangeNode};
+use crate::service::{ServiceNode, RPCNode, RPCTypeNode};
+
+/// Visits `node` and everything below it, calling `v.pre_visit` before and
+/// `v.post_visit` after. If `pre_visit` returns false, the node is skipped
+/// and `post_visit` is not called for it.
+pub fn walk(v: &mut dyn Visitor, node: &dyn NodeTrait) {
+    if !v.pre_visit(node) {
+        return
+    }
+    node.accept(v);
+    v.post_visit(node);
+}
+
+/// Visits `node` and everything below it for changes, calling
+/// `v.pre_visit` before and `v.post_visit` after. If `pre_visit` returns
+/// false, the node is skipped and `post_visit` is not called for it.
+pub fn walk_mut(v: &mut dyn VisitorMut, node: &mut dyn NodeTrait) {
+    if !v.pre_visit(&*node) {
+        return
+    }
+    node.accept_mut(v);
+    v.post_visit(&*node);
+}
+
+/// Lets the default methods of [`Visitor`] hand the visitor on to the
+/// `walk_*` functions. It is implemented for every visitor.
+pub trait AsVisitor {
+    fn as_visitor(&mut self) -> &mut dyn Visitor;
+}
+
+impl<T: Visitor> AsVisitor for T {
+    fn as_visitor(&mut self) -> &mut dyn Visitor {
+        return self
+    }
+}
+
+/// Lets the default methods of [`VisitorMut`] hand the visitor on to the
+/// `walk_*_mut` functions. It is implemented for every visitor.
+pub trait AsVisitorMut {
+    fn as_visitor_mut(&mut self) -> &mut dyn VisitorMut;
+}
+
+impl<T: VisitorMut> AsVisitorMut for T {
+    fn as_visitor_mut(&mut self) -> &mut dyn VisitorMut {
+        return self
+    }
+}
+
+/// A read-only visitor over the AST. See the [module docs](self).
+pub trait Visitor: AsVisitor {
+    /// Called before each node is visited. Returning false skips the node
+    /// and everything below it.
+    fn pre_visit(&mut self, _node: &dyn NodeTrait) -> bool {
+        return true
+    }
+
+    /// Called after a node and everything below it have been visited.
+    fn post_visit(&mut self, _node: &dyn NodeTrait) {}
+
+    fn visit_file(&mut self, node: &FileNode) {
+        walk_file(self.as_visitor(), node);
+    }
+
+    fn visit_syntax(&mut self, node: &SyntaxNode) {
+        walk_syntax(self.as_visitor(), node);
+    }
+
+    fn visit_edition(&mut self, node: &EditionNode) {
+        walk_edition(self.as_visitor(), node);
+    }
+
+    fn visit_package(&mut self, node: &PackageNode) {
+        walk_package(self.as_visitor(), node);
+    }
+
+    fn visit_import(&mut self, node: &ImportNode) {
+        walk_import(self.as_visitor(), node);
+    }
+
+    fn visit_option(&mut self, node: &OptionNode) {
+        walk_option(self.as_visitor(), node);
+    }
+
+    fn visit_option_name(&mut self, node: &OptionNameNode) {
+        walk_option_name(self.as_visitor(), node);
+    }
+
+    fn visit_field_reference(&mut self, node: &FieldReferenceNode) {
+        walk_field_reference(self.as_visitor(), node);
+    }
+
+    fn visit_compact_options(&mut self, node: &CompactOptionsNode) {
+        walk_compact_options(self.as_visitor(), node);
+    }
+
+    fn visit_message(&mut self, node: &MessageNode) {
+        walk_message(self.as_visitor(), node);
+    }
+
+    fn visit_extend(&mut self, node: &ExtendNode) {
+        walk_extend(self.as_visitor(), node);
+    }
+
+    fn visit_field(&mut self, node: &FieldNode) {
+        walk_field(self.as_visitor(), node);
+    }
+
+    fn visit_group(&mut self, node: &GroupNode) {
+        walk_group(self.as_visitor(), node);
+    }
+
+    fn visit_map_type(&mut self, node: &MapTypeNode) {
+        walk_map_type(self.as_visitor(), node);
+    }
+
+    fn visit_map_field(&mut self, node: &MapFieldNode) {
+        walk_map_field(self.as_visitor(), node);
+    }
+
+    fn visit_one_of(&mut self, node: &OneOfNode) {
+        walk_one_of(self.as_visitor(), node);
+    }
+
+    fn visit_enum(&mut self, node: &EnumNode) {
+        walk_enum(self.as_visitor(), node);
+    }
+
+    fn visit_enum_value(&mut self, node: &EnumValueNode) {
+        walk_enum_value(self.as_visitor(), node);
+    }
+
+    fn visit_reserved(&mut self, node: &ReservedNode) {
+        walk_reserved(self.as_visitor(), node);
+    }
+
+    fn visit_extension_range(&mut self, node: &ExtensionRangeNode) {
+        walk_extension_range(self.as_visitor(), node);
+    }
+
+    fn visit_range(&mut self, node: &RangeNode) {
+        walk_range(self.as_visitor(), node);
+    }
+
+    fn visit_service(&mut self, node: &ServiceNode) {
+        walk_service(self.as_visitor(), node);
+    }
+
+    fn visit_rpc(&mut self, node: &RPCNode) {
+        walk_rpc(self.as_visitor(), node);
+    }
+
+    fn visit_rpc_type(&mut self, node: &RPCTypeNode) {
+        walk_rpc_type(self.as_visitor(), node);
+    }
+
+    fn visit_empty_decl(&mut self, node: &EmptyDeclNode) {
+        walk_empty_decl(self.as_visitor(), node);
+    }
+
+    fn visit_error(&mut self, node: &ErrorNode) {
+        walk_error(self.as_visitor(), node);
+    }
+
+    fn visit_compound_ident(&mut self, node: &CompoundIdentNode) {
+        walk_compound_ident(self.as_visitor(), node);
+    }
+
+    fn visit_compound_string_literal(&mut self, node: &CompoundStringLiteralNode) {
+        walk_compound_string_literal(self.as_visitor(), node);
+    }
+
+    fn visit_positive_uint_literal(&mut self, node: &PositiveUintLiteralNode) {
+        walk_positive_uint_literal(self.as_visitor(), node);
+    }
+
+    fn visit_negative_int_literal(&mut self, node: &NegativeIntLiteralNode) {
+        walk_negative_int_literal(self.as_visitor(), node);
+    }
+
+    fn visit_signed_float_literal(&mut self, node: &SignedFloatLiteralNode) {
+        walk_signed_float_literal(self.as_visitor(), node);
+    }
+
+    fn visit_array_literal(&mut self, node: &ArrayLiteralNode) {
+        walk_array_literal(self.as_visitor(), node);
+    }
+
+    fn visit_message_literal(&mut self, node: &MessageLiteralNode) {
+        walk_message_literal(self.as_visitor(), node);
+    }
+
+    fn visit_message_field(&mut self, node: &MessageFieldNode) {
+        walk_message_field(self.as_visitor(), node);
+    }
+
+    /// Visits the children of a bare CompositeNode. The parser never
+    /// produces one, but it can be reached through
+    /// [`CompositeNodeTrait::children`](crate::node::CompositeNodeTrait::children).
+    fn visit_composite(&mut self, node: &CompositeNode) {
+        walk_composite(self.as_visitor(), node);
+    }
+
+    // Leaves of the tree. Keywords are IdentNodes.
+
+    fn visit_ident(&mut self, _node: &IdentNode) {}
+
+    fn visit_rune(&mut self, _node: &RuneNode) {}
+
+    fn visit_terminal(&mut self, _node: &TerminalNode) {}
+
+    fn visit_string_literal(&mut self, _node: &StringLiteralNode) {}
+
+    fn visit_uint_literal(&mut self, _node: &UintLiteralNode) {}
+
+    fn visit_float_literal(&mut self, _node: &FloatLiteralNode) {}
+
+    fn visit_special_float_literal(&mut self, _node: &SpecialFloatLiteralNode) {}
+
+    fn visit_bool_literal(&mut self, _node: &BoolLiteralNode) {}
+}
+
+/// A visitor that can change the AST in place. See the
+/// [module docs](self).
+pub trait VisitorMut: AsVisitorMut {
+    /// Called before each node is visited. Returning false skips the node
+    /// and everything below it.
+    fn pre_visit(&mut self, _node: &dyn NodeTrait) -> bool {
+        return true
+    }
+
+    /// Called after a node and everything below it have been visited.
+    fn post_visit(&mut self, _node: &dyn NodeTrait) {}
+
+    fn visit_file_mut(&mut self, node: &mut FileNode) {
+        walk_file_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_syntax_mut(&mut self, node: &mut SyntaxNode) {
+        walk_syntax_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_edition_mut(&mut self, node: &mut EditionNode) {
+        walk_edition_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_package_mut(&mut self, node: &mut PackageNode) {
+        walk_package_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_import_mut(&mut self, node: &mut ImportNode) {
+        walk_import_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_option_mut(&mut self, node: &mut OptionNode) {
+        walk_option_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_option_name_mut(&mut self, node: &mut OptionNameNode) {
+        walk_option_name_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_field_reference_mut(&mut self, node: &mut FieldReferenceNode) {
+        walk_field_reference_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_compact_options_mut(&mut self, node: &mut CompactOptionsNode) {
+        walk_compact_options_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_message_mut(&mut self, node: &mut MessageNode) {
+        walk_message_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_extend_mut(&mut self, node: &mut ExtendNode) {
+        walk_extend_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_field_mut(&mut self, node: &mut FieldNode) {
+        walk_field_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_group_mut(&mut self, node: &mut GroupNode) {
+        walk_group_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_map_type_mut(&mut self, node: &mut MapTypeNode) {
+        walk_map_type_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_map_field_mut(&mut self, node: &mut MapFieldNode) {
+        walk_map_field_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_one_of_mut(&mut self, node: &mut OneOfNode) {
+        walk_one_of_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_enum_mut(&mut self, node: &mut EnumNode) {
+        walk_enum_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_enum_value_mut(&mut self, node: &mut EnumValueNode) {
+        walk_enum_value_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_reserved_mut(&mut self, node: &mut ReservedNode) {
+        walk_reserved_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_extension_range_mut(&mut self, node: &mut ExtensionRangeNode) {
+        walk_extension_range_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_range_mut(&mut self, node: &mut RangeNode) {
+        walk_range_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_service_mut(&mut self, node: &mut ServiceNode) {
+        walk_service_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_rpc_mut(&mut self, node: &mut RPCNode) {
+        walk_rpc_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_rpc_type_mut(&mut self, node: &mut RPCTypeNode) {
+        walk_rpc_type_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_empty_decl_mut(&mut self, node: &mut EmptyDeclNode) {
+        walk_empty_decl_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_error_mut(&mut self, node: &mut ErrorNode) {
+        walk_error_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_compound_ident_mut(&mut self, node: &mut CompoundIdentNode) {
+        walk_compound_ident_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_compound_string_literal_mut(&mut self, node: &mut CompoundStringLiteralNode) {
+        walk_compound_string_literal_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_positive_uint_literal_mut(&mut self, node: &mut PositiveUintLiteralNode) {
+        walk_positive_uint_literal_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_negative_int_literal_mut(&mut self, node: &mut NegativeIntLiteralNode) {
+        walk_negative_int_literal_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_signed_float_literal_mut(&mut self, node: &mut SignedFloatLiteralNode) {
+        walk_signed_float_literal_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_array_literal_mut(&mut self, node: &mut ArrayLiteralNode) {
+        walk_array_literal_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_message_literal_mut(&mut self, node: &mut MessageLiteralNode) {
+        walk_message_literal_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_message_field_mut(&mut self, node: &mut MessageFieldNode) {
+        walk_message_field_mut(self.as_visitor_mut(), node);
+    }
+
+    fn visit_composite_mut(&mut self, node: &mut CompositeNode) {
+        walk_composite_mut(self.as_visitor_mut(), node);
+    }
+
+    // Leaves of the tree. Keywords are IdentNodes.
+
+    fn visit_ident_mut(&mut self, _node: &mut IdentNode) {}
+
+    fn visit_rune_mut(&mut self, _node: &mut RuneNode) {}
+
+    fn visit_terminal_mut(&mut self, _node: &mut TerminalNode) {}
+
+    fn visit_string_literal_mut(&mut self, _node: &mut StringLiteralNode) {}
+
+    fn visit_uint_literal_mut(&mut self, _node: &mut UintLiteralNode) {}
+
+    fn visit_float_literal_mut(&mut self, _node: &mut FloatLiteralNode) {}
+
+    fn visit_special_float_literal_mut(&mut self, _node: &mut SpecialFloatLiteralNode) {}
+
+    fn visit_bool_literal_mut(&mut self, _node: &mut BoolLiteralNode) {}
+}
+
+fn walk_opt<T: NodeTrait>(v: &mut dyn Visitor, node: Option<&T>) {
+    if let Some(node) = node {
+        walk(v, node);
+    }
+}
+
+// Visits a list of nodes along with the separators between them.
+fn walk_separated<T: NodeTrait>(v: &mut dyn Visitor, nodes: &[T], seps: &[RuneNode]) {
+    for (i, node) in nodes.iter().enumerate() {
+        walk(v, node);
+        if let Some(sep) = seps.get(i) {
+            walk(v, sep);
+        }
+    }
+}
+
+pub fn walk_file(v: &mut dyn Visitor, node: &FileNode) {
+    walk_opt(v, node.syntax());
+    walk_opt(v, node.edition());
+    for decl in node.decls().iter() {
+        walk(v, decl.as_node());
+    }
+    walk(v, node.eof());
+}
+
+pub fn walk_syntax(v: &mut dyn Visitor, node: &SyntaxNode) {
+    walk(v, node.keyword());
+    walk(v, node.equals());
+    walk(v, node.syntax());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_edition(v: &mut dyn Visitor, node: &EditionNode) {
+    walk(v, node.keyword());
+    walk(v, node.equals());
+    walk(v, node.edition());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_package(v: &mut dyn Visitor, node: &PackageNode) {
+    walk(v, node.keyword());
+    walk(v, node.name());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_import(v: &mut dyn Visitor, node: &ImportNode) {
+    walk(v, node.keyword());
+    walk_opt(v, node.public());
+    walk_opt(v, node.weak());
+    walk(v, node.name());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_option(v: &mut dyn Visitor, node: &OptionNode) {
+    walk_opt(v, node.keyword());
+    walk(v, node.name());
+    walk(v, node.equals());
+    walk(v, node.val().as_node());
+    walk_opt(v, node.semicolon());
+}
+
+pub fn walk_option_name(v: &mut dyn Visitor, node: &OptionNameNode) {
+    walk_separated(v, node.parts(), node.dots());
+}
+
+pub fn walk_field_reference(v: &mut dyn Visitor, node: &FieldReferenceNode) {
+    walk_opt(v, node.open());
+    if let Some(url_prefix) = node.url_prefix() {
+        walk(v, url_prefix);
+    }
+    walk_opt(v, node.slash());
+    walk(v, node.name());
+    walk_opt(v, node.close());
+}
+
+pub fn walk_compact_options(v: &mut dyn Visitor, node: &CompactOptionsNode) {
+    walk(v, node.open_bracket());
+    walk_separated(v, node.options(), node.commas());
+    walk(v, node.close_bracket());
+}
+
+fn walk_message_body(v: &mut dyn Visitor, body: &MessageBody) {
+    walk_opt(v, body.open_brace());
+    for decl in body.decls().iter() {
+        walk(v, decl.as_node());
+    }
+    walk_opt(v, body.close_brace());
+}
+
+pub fn walk_message(v: &mut dyn Visitor, node: &MessageNode) {
+    walk(v, node.keyword());
+    walk(v, node.name());
+    walk_message_body(v, node.body());
+}
+
+pub fn walk_extend(v: &mut dyn Visitor, node: &ExtendNode) {
+    walk(v, node.keyword());
+    walk(v, node.extendee());
+    walk(v, node.open_brace());
+    for decl in node.decls().iter() {
+        walk(v, decl.as_node());
+    }
+    walk(v, node.close_brace());
+}
+
+pub fn walk_field(v: &mut dyn Visitor, node: &FieldNode) {
+    walk_opt(v, node.label());
+    walk(v, node.field_type());
+    walk(v, node.name());
+    walk(v, node.equals());
+    walk(v, node.tag());
+    walk_opt(v, node.options());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_group(v: &mut dyn Visitor, node: &GroupNode) {
+    walk_opt(v, node.label());
+    walk(v, node.keyword());
+    walk(v, node.name());
+    walk(v, node.equals());
+    walk(v, node.tag());
+    walk_opt(v, node.options());
+    walk_message_body(v, node.body());
+}
+
+pub fn walk_map_type(v: &mut dyn Visitor, node: &MapTypeNode) {
+    walk(v, node.keyword());
+    walk(v, node.open_angle());
+    walk(v, node.key_type());
+    walk(v, node.comma());
+    walk(v, node.value_type());
+    walk(v, node.close_angle());
+}
+
+pub fn walk_map_field(v: &mut dyn Visitor, node: &MapFieldNode) {
+    walk(v, node.map_type());
+    walk(v, node.name());
+    walk(v, node.equals());
+    walk(v, node.tag());
+    walk_opt(v, node.options());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_one_of(v: &mut dyn Visitor, node: &OneOfNode) {
+    walk(v, node.keyword());
+    walk(v, node.name());
+    walk(v, node.open_brace());
+    for decl in node.decls().iter() {
+        walk(v, decl.as_node());
+    }
+    walk(v, node.close_brace());
+}
+
+pub fn walk_enum(v: &mut dyn Visitor, node: &EnumNode) {
+    walk(v, node.keyword());
+    walk(v, node.name());
+    walk(v, node.open_brace());
+    for decl in node.decls().iter() {
+        walk(v, decl.as_node());
+    }
+    walk(v, node.close_brace());
+}
+
+pub fn walk_enum_value(v: &mut dyn Visitor, node: &EnumValueNode) {
+    walk(v, node.name());
+    walk(v, node.equals());
+    walk(v, node.number());
+    walk_opt(v, node.options());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_reserved(v: &mut dyn Visitor, node: &ReservedNode) {
+    walk(v, node.keyword());
+    // Only one of the three lists is ever used.
+    walk_separated(v, node.ranges(), node.commas());
+    walk_separated(v, node.names(), node.commas());
+    walk_separated(v, node.identifiers(), node.commas());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_extension_range(v: &mut dyn Visitor, node: &ExtensionRangeNode) {
+    walk(v, node.keyword());
+    walk_separated(v, node.ranges(), node.commas());
+    walk_opt(v, node.options());
+    walk(v, node.semicolon());
+}
+
+pub fn walk_range(v: &mut dyn Visitor, node: &RangeNode) {
+    walk(v, node.start_value());
+    walk_opt(v, node.to());
+    if let Some(end_value) = node.end_value() {
+        walk(v, end_value);
+    }
+    walk_opt(v, node.max());
+}
+
+pub fn walk_service(v: &mut dyn Visitor, node: &ServiceNode) {
+    walk(v, node.keyword());
+    walk(v, node.name());
+    walk(v, node.open_brace());
+    for decl in node.decls().iter() {
+        walk(v, decl.as_node());
+    }
+    walk(v, node.close_brace());
+}
+
+pub fn walk_rpc(v: &mut dyn Visitor, node: &RPCNode) {
+    walk(v, node.keyword());
+    walk(v, node.name());
+    walk(v, node.input());
+    walk(v, node.returns());
+    walk(v, node.output());
+    walk_opt(v, node.semicolon());
+    walk_opt(v, node.open_brace());
+    for decl in node.decls().iter() {
+        walk(v, decl.as_node());
+    }
+    walk_opt(v, node.close_brace());
+}
+
+pub fn walk_rpc_type(v: &mut dyn Visitor, node: &RPCTypeNode) {
+    walk(v, node.open_paren());
+    walk_opt(v, node.stream());
+    walk(v, node.message_type());
+    walk(v, node.close_paren());
+}
+
+pub fn walk_empty_decl(v: &mut dyn Visitor, node: &EmptyDeclNode) {
+    walk(v, node.semicolon());
+}
+
+pub fn walk_error(v: &mut dyn Visitor, node: &ErrorNode) {
+    for tok in node.tokens().iter() {
+        walk(v, tok);
+    }
+}
+
+pub fn walk_compound_ident(v: &mut dyn Visitor, node: &CompoundIdentNode) {
+    walk_opt(v, node.leading_dot());
+    walk_separated(v, node.components(), node.dots());
+}
+
+pub fn walk_compound_string_literal(v: &mut dyn Visitor, node: &CompoundStringLiteralNode) {
+    for c in node.components().iter() {
+        walk(v, c);
+    }
+}
+
+pub fn walk_positive_uint_literal(v: &mut dyn Visitor, node: &PositiveUintLiteralNode) {
+    walk(v, node.plus());
+    walk(v, node.uint());
+}
+
+pub fn walk_negative_int_literal(v: &mut dyn Visitor, node: &NegativeIntLiteralNode) {
+    walk(v, node.minus());
+    walk(v, node.uint());
+}
+
+pub fn walk_signed_float_literal(v: &mut dyn Visitor, node: &SignedFloatLiteralNode) {
+    walk(v, node.sign());
+    walk(v, node.float());
+}
+
+pub fn walk_array_literal(v: &mut dyn Visitor, node: &ArrayLiteralNode) {
+    walk(v, node.open_bracket());
+    for (i, el) in node.elements().iter().enumerate() {
+        walk(v, el.as_node());
+        walk_opt(v, node.commas().get(i));
+    }
+    walk(v, node.close_bracket());
+}
+
+pub fn walk_message_literal(v: &mut dyn Visitor, node: &MessageLiteralNode) {
+    walk(v, node.open());
+    for (el, sep) in node.elements().iter().zip(node.seps().iter()) {
+        walk(v, el);
+        walk_opt(v, sep.as_ref());
+    }
+    walk(v, node.close());
+}
+
+pub fn walk_message_field(v: &mut dyn Visitor, node: &MessageFieldNode) {
+    walk(v, node.name());
+    walk_opt(v, node.sep());
+    walk(v, node.val().as_node());
+}
+
+pub fn walk_composite(v: &mut dyn Visitor, node: &CompositeNode) {
+    for c in node.children().iter() {
+        walk(v, c.as_ref());
+    }
+}
+
+// The children of most nodes are private, so each node walks its own
+// children for changes and then rebuilds itself from them.
+
+pub fn walk_file_mut(v: &mut dyn VisitorMut, node: &mut FileNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_syntax_mut(v: &mut dyn VisitorMut, node: &mut SyntaxNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_edition_mut(v: &mut dyn VisitorMut, node: &mut EditionNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_package_mut(v: &mut dyn VisitorMut, node: &mut PackageNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_import_mut(v: &mut dyn VisitorMut, node: &mut ImportNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_option_mut(v: &mut dyn VisitorMut, node: &mut OptionNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_option_name_mut(v: &mut dyn VisitorMut, node: &mut OptionNameNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_field_reference_mut(v: &mut dyn VisitorMut, node: &mut FieldReferenceNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_compact_options_mut(v: &mut dyn VisitorMut, node: &mut CompactOptionsNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_message_mut(v: &mut dyn VisitorMut, node: &mut MessageNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_extend_mut(v: &mut dyn VisitorMut, node: &mut ExtendNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_field_mut(v: &mut dyn VisitorMut, node: &mut FieldNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_group_mut(v: &mut dyn VisitorMut, node: &mut GroupNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_map_type_mut(v: &mut dyn VisitorMut, node: &mut MapTypeNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_map_field_mut(v: &mut dyn VisitorMut, node: &mut MapFieldNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_one_of_mut(v: &mut dyn VisitorMut, node: &mut OneOfNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_enum_mut(v: &mut dyn VisitorMut, node: &mut EnumNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_enum_value_mut(v: &mut dyn VisitorMut, node: &mut EnumValueNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_reserved_mut(v: &mut dyn VisitorMut, node: &mut ReservedNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_extension_range_mut(v: &mut dyn VisitorMut, node: &mut ExtensionRangeNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_range_mut(v: &mut dyn VisitorMut, node: &mut RangeNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_service_mut(v: &mut dyn VisitorMut, node: &mut ServiceNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_rpc_mut(v: &mut dyn VisitorMut, node: &mut RPCNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_rpc_type_mut(v: &mut dyn VisitorMut, node: &mut RPCTypeNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_empty_decl_mut(v: &mut dyn VisitorMut, node: &mut EmptyDeclNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_error_mut(v: &mut dyn VisitorMut, node: &mut ErrorNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_compound_ident_mut(v: &mut dyn VisitorMut, node: &mut CompoundIdentNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_compound_string_literal_mut(v: &mut dyn VisitorMut, node: &mut CompoundStringLiteralNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_positive_uint_literal_mut(v: &mut dyn VisitorMut, node: &mut PositiveUintLiteralNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_negative_int_literal_mut(v: &mut dyn VisitorMut, node: &mut NegativeIntLiteralNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_signed_float_literal_mut(v: &mut dyn VisitorMut, node: &mut SignedFloatLiteralNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_array_literal_mut(v: &mut dyn VisitorMut, node: &mut ArrayLiteralNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_message_literal_mut(v: &mut dyn VisitorMut, node: &mut MessageLiteralNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_message_field_mut(v: &mut dyn VisitorMut, node: &mut MessageFieldNode) {
+    node.walk_children_mut(v);
+}
+
+pub fn walk_composite_mut(v: &mut dyn VisitorMut, node: &mut CompositeNode) {
+    node.walk_children_mut(v);
+}
+
+pub(crate) fn walk_opt_mut<T: NodeTrait>(v: &mut dyn VisitorMut, node: Option<&mut T>) {
+    if let Some(node) = node {
+        walk_mut(v, node);
+    }
+}
+
+pub(crate) fn walk_all_mut<T: NodeTrait>(v: &mut dyn VisitorMut, nodes: &mut [T]) {
+    for node in nodes.iter_mut() {
+        walk_mut(v, node);
+    }
+}
+
+// Visits a list of nodes along with the separators between them.
+pub(crate) fn walk_separated_mut<T: NodeTrait>(v: &mut dyn VisitorMut, nodes: &mut [T], seps: &mut [RuneNode]) {
+    let mut seps = seps.iter_mut();
+    for node in nodes.iter_mut() {
+        walk_mut(v, node);
+        if let Some(sep) = seps.next() {
+            walk_mut(v, sep);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer;
+    use crate::node::TokenInfo;
+    use crate::parser::parse;
+    use crate::printer::print;
+    use crate::source_pos::PosRange;
+
+    const SOURCE: &str = "package a;\nmessage Foo {\n  message N { optional Foo f = 1; }\n}\nmessage O { optional a.Foo g = 1 [(x) = { y: Foo }]; }\n";
+
+    fn raw(node: &dyn NodeTrait) -> String {
+        return node.as_terminal().unwrap().raw_text().to_string()
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        tokens: Vec<String>,
+        skip_line: i32,
+        pre: usize,
+        post: usize
+    }
+
+    impl Visitor for Recorder {
+        fn pre_visit(&mut self, node: &dyn NodeTrait) -> bool {
+            if node.start().line() == self.skip_line {
+                return false
+            }
+            self.pre += 1;
+            if node.as_terminal().is_some() {
+                self.tokens.push(raw(node));
+            }
+            return true
+        }
+
+        fn post_visit(&mut self, _node: &dyn NodeTrait) {
+            self.post += 1;
+        }
+
+        fn visit_message(&mut self, node: &MessageNode) {
+            self.events.push(format!("enter {}", raw(node.name())));
+            walk_message(self, node);
+            self.events.push(format!("exit {}", raw(node.name())));
+        }
+
+        fn visit_field(&mut self, node: &FieldNode) {
+            self.events.push(format!("field {}", raw(node.name())));
+            walk_field(self, node);
+        }
+    }
+
+    #[test]
+    fn walk_visits_nodes_in_source_order() {
+        let file = parse("a.proto", SOURCE).unwrap();
+        let mut v = Recorder::default();
+        walk(&mut v, &file);
+        assert_eq!(v.events, vec!["enter Foo", "enter N", "field f", "exit N", "exit Foo", "enter O", "field g", "exit O"]);
+        let tokens: Vec<String> = lexer::lex("a.proto", SOURCE).unwrap().iter().map(|t| t.info().raw_text().to_string()).collect();
+        assert_eq!(v.tokens, tokens);
+        assert_eq!(v.pre, v.post);
+    }
+
+    #[test]
+    fn pre_visit_can_skip_a_subtree() {
+        let file = parse("a.proto", SOURCE).unwrap();
+        let mut v = Recorder { skip_line: 3, ..Recorder::default() };
+        walk(&mut v, &file);
+        assert_eq!(v.events, vec!["enter Foo", "exit Foo", "enter O", "field g", "exit O"]);
+        assert!(!v.tokens.contains(&"N".to_string()));
+        assert_eq!(v.pre, v.post);
+    }
+
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_ident_mut(&mut self, node: &mut IdentNode) {
+            if raw(node) != "Foo" {
+                return
+            }
+            let mut info = TokenInfo::new(PosRange { start: node.start().clone(), end: node.end().clone() });
+            info.set_raw_text("Bar").set_leading_whitespace(node.as_terminal().unwrap().leading_whitespace());
+            *node = IdentNode::new("Bar", info);
+        }
+    }
+
+    #[test]
+    fn walk_mut_rebuilds_changed_nodes() {
+        let mut file = parse("a.proto", SOURCE).unwrap();
+        walk_mut(&mut Rename, &mut file);
+        assert_eq!(print(&file), SOURCE.replace("Foo", "Bar"));
+    }
+}
@@ -0,0 +1,29 @@
+//! The well-known types and descriptor.proto, bundled with the crate so
+//! that files importing them compile without a copy on disk. The sources
+//! are those of protobuf 23.2, without their documentation comments.
+//! `Compiler` falls back to them for imports its accessor can't find.
+
+const FILES: &[(&str, &str)] = &[
+    ("google/protobuf/any.proto", include_str!("wellknown/google/protobuf/any.proto")),
+    ("google/protobuf/api.proto", include_str!("wellknown/google/protobuf/api.proto")),
+    ("google/protobuf/compiler/plugin.proto", include_str!("wellknown/google/protobuf/compiler/plugin.proto")),
+    ("google/protobuf/descriptor.proto", include_str!("wellknown/google/protobuf/descriptor.proto")),
+    ("google/protobuf/duration.proto", include_str!("wellknown/google/protobuf/duration.proto")),
+    ("google/protobuf/empty.proto", include_str!("wellknown/google/protobuf/empty.proto")),
+    ("google/protobuf/field_mask.proto", include_str!("wellknown/google/protobuf/field_mask.proto")),
+    ("google/protobuf/source_context.proto", include_str!("wellknown/google/protobuf/source_context.proto")),
+    ("google/protobuf/struct.proto", include_str!("wellknown/google/protobuf/struct.proto")),
+    ("google/protobuf/timestamp.proto", include_str!("wellknown/google/protobuf/timestamp.proto")),
+    ("google/protobuf/type.proto", include_str!("wellknown/google/protobuf/type.proto")),
+    ("google/protobuf/wrappers.proto", include_str!("wellknown/google/protobuf/wrappers.proto"))
+];
+
+/// Returns the source of the bundled file imported as `name`.
+pub fn source(name: &str) -> Option<&'static str> {
+    return FILES.iter().find(|(n, _)| *n == name).map(|(_, src)| *src)
+}
+
+/// Returns the import names of all the bundled files.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    return FILES.iter().map(|(n, _)| *n)
+}
@@ -0,0 +1,1144 @@
+//! The protobuf binary format for the descriptor model, as read and written
+//! by protoc, buf and the language runtimes.
+//!
+//! Fields are written in field number order, with unset fields left out,
+//! so encoding a descriptor gives the same bytes as protoc. The repeated
+//! numeric fields of `SourceCodeInfo` are packed and all others are not,
+//! as declared in descriptor.proto. The decoder accepts both forms.
+
+use crate::descriptor::*;
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireType {
+    Varint,
+    Fixed64,
+    LengthDelimited,
+    StartGroup,
+    EndGroup,
+    Fixed32
+}
+
+impl WireType {
+    fn from_bits(bits: u64) -> Option<Self> {
+        match bits {
+            0 => return Some(WireType::Varint),
+            1 => return Some(WireType::Fixed64),
+            2 => return Some(WireType::LengthDelimited),
+            3 => return Some(WireType::StartGroup),
+            4 => return Some(WireType::EndGroup),
+            5 => return Some(WireType::Fixed32),
+            _ => return None
+        }
+    }
+
+    fn bits(&self) -> u64 {
+        match self {
+            WireType::Varint => return 0,
+            WireType::Fixed64 => return 1,
+            WireType::LengthDelimited => return 2,
+            WireType::StartGroup => return 3,
+            WireType::EndGroup => return 4,
+            WireType::Fixed32 => return 5
+        }
+    }
+}
+
+/// A message of the descriptor model that can be written to and read from
+/// the binary format.
+pub trait Message: Default {
+    /// Writes the fields of the message, without a tag or length.
+    fn encode(&self, w: &mut Writer);
+
+    /// Reads the value of one field whose tag has just been read, and
+    /// stores it in the message. Fields the message doesn't know are
+    /// skipped.
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error>;
+
+    fn encode_to_vec(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        self.encode(&mut w);
+        return w.into_bytes()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let mut r = Reader::new(bytes);
+        let mut m = Self::default();
+        r.merge_all(&mut m)?;
+        return Ok(m)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Writer {
+    buf: Vec<u8>
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        return Writer { buf: vec![] }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        return self.buf
+    }
+
+    pub fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.buf.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        self.buf.push(v as u8);
+    }
+
+    pub fn tag(&mut self, number: u32, wire_type: WireType) {
+        self.varint(((number as u64) << 3) | wire_type.bits());
+    }
+
+    /// Writes the bytes of an already encoded field as they are.
+    pub fn raw(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn length_delimited(&mut self, number: u32, bytes: &[u8]) {
+        self.tag(number, WireType::LengthDelimited);
+        self.varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn string(&mut self, number: u32, v: &Option<String>) {
+        if let Some(v) = v {
+            self.length_delimited(number, v.as_bytes());
+        }
+    }
+
+    pub fn strings(&mut self, number: u32, vs: &[String]) {
+        for v in vs.iter() {
+            self.length_delimited(number, v.as_bytes());
+        }
+    }
+
+    pub fn bytes(&mut self, number: u32, v: &Option<Vec<u8>>) {
+        if let Some(v) = v {
+            self.length_delimited(number, v);
+        }
+    }
+
+    pub fn int32(&mut self, number: u32, v: Option<i32>) {
+        if let Some(v) = v {
+            self.tag(number, WireType::Varint);
+            // Negative values are sign-extended to 64 bits.
+            self.varint(v as i64 as u64);
+        }
+    }
+
+    pub fn int32s(&mut self, number: u32, vs: &[i32]) {
+        for &v in vs.iter() {
+            self.int32(number, Some(v));
+        }
+    }
+
+    pub fn packed_int32s(&mut self, number: u32, vs: &[i32]) {
+        if vs.is_empty() {
+            return
+        }
+        let mut packed = Writer::new();
+        for &v in vs.iter() {
+            packed.varint(v as i64 as u64);
+        }
+        self.length_delimited(number, &packed.buf);
+    }
+
+    pub fn int64(&mut self, number: u32, v: Option<i64>) {
+        if let Some(v) = v {
+            self.tag(number, WireType::Varint);
+            self.varint(v as u64);
+        }
+    }
+
+    pub fn uint64(&mut self, number: u32, v: Option<u64>) {
+        if let Some(v) = v {
+            self.tag(number, WireType::Varint);
+            self.varint(v);
+        }
+    }
+
+    pub fn bool(&mut self, number: u32, v: Option<bool>) {
+        if let Some(v) = v {
+            self.tag(number, WireType::Varint);
+            self.varint(v as u64);
+        }
+    }
+
+    pub fn double(&mut self, number: u32, v: Option<f64>) {
+        if let Some(v) = v {
+            self.tag(number, WireType::Fixed64);
+            self.buf.extend_from_slice(&v.to_bits().to_le_bytes());
+        }
+    }
+
+    pub fn message<M: Message>(&mut self, number: u32, m: &Option<M>) {
+        if let Some(m) = m {
+            self.length_delimited(number, &m.encode_to_vec());
+        }
+    }
+
+    pub fn messages<M: Message>(&mut self, number: u32, ms: &[M]) {
+        for m in ms.iter() {
+            self.length_delimited(number, &m.encode_to_vec());
+        }
+    }
+}
+
+/// Reads the binary format from a slice. Each read checks the wire type
+/// of the field against the type expected for it.
+#[derive(Debug)]
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    // Where `buf` starts in the outermost message, for error offsets.
+    base: usize
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        return Reader { buf, pos: 0, base: 0 }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        return Error::Decode { offset: self.base + self.pos, message: message.to_string() }
+    }
+
+    fn at_end(&self) -> bool {
+        return self.pos >= self.buf.len()
+    }
+
+    fn merge_all<M: Message>(&mut self, m: &mut M) -> Result<(), Error> {
+        while !self.at_end() {
+            let (number, wire_type) = self.tag()?;
+            m.merge_field(self, number, wire_type)?;
+        }
+        return Ok(())
+    }
+
+    pub fn varint(&mut self) -> Result<u64, Error> {
+        let mut v: u64 = 0;
+        for i in 0..10 {
+            let b = match self.buf.get(self.pos) {
+                Some(b) => *b,
+                None => return Err(self.error("truncated varint"))
+            };
+            self.pos += 1;
+            v |= ((b & 0x7f) as u64) << (i * 7);
+            if b < 0x80 {
+                return Ok(v)
+            }
+        }
+        return Err(self.error("varint longer than 10 bytes"))
+    }
+
+    pub fn tag(&mut self) -> Result<(u32, WireType), Error> {
+        let start = self.pos;
+        let key = self.varint()?;
+        let number = key >> 3;
+        let wire_type = WireType::from_bits(key & 7);
+        match wire_type {
+            Some(wt) if number > 0 && number <= u32::MAX as u64 => return Ok((number as u32, wt)),
+            _ => {
+                self.pos = start;
+                return Err(self.error("invalid field tag"))
+            }
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.buf.len() - self.pos < n {
+            return Err(self.error("unexpected end of data"))
+        }
+        let bytes = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        return Ok(bytes)
+    }
+
+    fn expect(&self, wire_type: WireType, expected: WireType) -> Result<(), Error> {
+        if wire_type != expected {
+            return Err(self.error(&format!("expected wire type {:?}, found {:?}", expected, wire_type)))
+        }
+        return Ok(())
+    }
+
+    fn length_delimited(&mut self, wire_type: WireType) -> Result<&'a [u8], Error> {
+        self.expect(wire_type, WireType::LengthDelimited)?;
+        let len = self.varint()?;
+        if len > (self.buf.len() - self.pos) as u64 {
+            return Err(self.error("length past the end of data"))
+        }
+        return self.take(len as usize)
+    }
+
+    /// Skips the value of a field the reader doesn't know.
+    pub fn skip(&mut self, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match wire_type {
+            WireType::Varint => {
+                self.varint()?;
+            },
+            WireType::Fixed64 => {
+                self.take(8)?;
+            },
+            WireType::Fixed32 => {
+                self.take(4)?;
+            },
+            WireType::LengthDelimited => {
+                self.length_delimited(wire_type)?;
+            },
+            WireType::StartGroup => {
+                loop {
+                    if self.at_end() {
+                        return Err(self.error("unterminated group"))
+                    }
+                    let (n, wt) = self.tag()?;
+                    if wt == WireType::EndGroup {
+                        if n != number {
+                            return Err(self.error("mismatched end of group"))
+                        }
+                        break
+                    }
+                    self.skip(n, wt)?;
+                }
+            },
+            WireType::EndGroup => return Err(self.error("unexpected end of group"))
+        }
+        return Ok(())
+    }
+
+    /// Returns the bytes of a field that was read with `skip`, starting at
+    /// `start`, tag included.
+    pub fn since(&self, start: usize) -> &'a [u8] {
+        return &self.buf[start..self.pos]
+    }
+
+    pub fn position(&self) -> usize {
+        return self.pos
+    }
+
+    pub fn string(&mut self, wire_type: WireType) -> Result<String, Error> {
+        let start = self.pos;
+        let bytes = self.length_delimited(wire_type)?;
+        match std::str::from_utf8(bytes) {
+            Ok(s) => return Ok(s.to_string()),
+            Err(_) => {
+                self.pos = start;
+                return Err(self.error("string field is not valid UTF-8"))
+            }
+        }
+    }
+
+    pub fn bytes(&mut self, wire_type: WireType) -> Result<Vec<u8>, Error> {
+        return Ok(self.length_delimited(wire_type)?.to_vec())
+    }
+
+    pub fn int32(&mut self, wire_type: WireType) -> Result<i32, Error> {
+        self.expect(wire_type, WireType::Varint)?;
+        // Larger values are truncated, as the runtimes do.
+        return Ok(self.varint()? as i32)
+    }
+
+    /// Reads one value of a repeated int32 field, or all of them if they
+    /// are packed.
+    pub fn int32s(&mut self, wire_type: WireType, out: &mut Vec<i32>) -> Result<(), Error> {
+        if wire_type != WireType::LengthDelimited {
+            out.push(self.int32(wire_type)?);
+            return Ok(())
+        }
+        let bytes = self.length_delimited(wire_type)?;
+        let mut packed = Reader { buf: bytes, pos: 0, base: self.base + self.pos - bytes.len() };
+        while !packed.at_end() {
+            out.push(packed.varint()? as i32);
+        }
+        return Ok(())
+    }
+
+    pub fn int64(&mut self, wire_type: WireType) -> Result<i64, Error> {
+        self.expect(wire_type, WireType::Varint)?;
+        return Ok(self.varint()? as i64)
+    }
+
+    pub fn uint64(&mut self, wire_type: WireType) -> Result<u64, Error> {
+        self.expect(wire_type, WireType::Varint)?;
+        return self.varint()
+    }
+
+    pub fn bool(&mut self, wire_type: WireType) -> Result<bool, Error> {
+        self.expect(wire_type, WireType::Varint)?;
+        return Ok(self.varint()? != 0)
+    }
+
+    pub fn double(&mut self, wire_type: WireType) -> Result<f64, Error> {
+        self.expect(wire_type, WireType::Fixed64)?;
+        let bytes = self.take(8)?;
+        let mut b = [0u8; 8];
+        b.copy_from_slice(bytes);
+        return Ok(f64::from_bits(u64::from_le_bytes(b)))
+    }
+
+    /// Reads an enum value. Numbers the enum doesn't define are dropped,
+    /// like unknown fields.
+    pub fn enum_value<E>(&mut self, wire_type: WireType, from_number: fn(i32) -> Option<E>) -> Result<Option<E>, Error> {
+        return Ok(from_number(self.int32(wire_type)?))
+    }
+
+    /// Reads a message field. A message that appears more than once is
+    /// merged into the earlier one, as the binary format requires.
+    pub fn message<M: Message>(&mut self, wire_type: WireType, into: &mut Option<M>) -> Result<(), Error> {
+        let m = into.get_or_insert_with(M::default);
+        return self.merge_message(wire_type, m)
+    }
+
+    pub fn messages<M: Message>(&mut self, wire_type: WireType, into: &mut Vec<M>) -> Result<(), Error> {
+        let mut m = M::default();
+        self.merge_message(wire_type, &mut m)?;
+        into.push(m);
+        return Ok(())
+    }
+
+    fn merge_message<M: Message>(&mut self, wire_type: WireType, m: &mut M) -> Result<(), Error> {
+        let bytes = self.length_delimited(wire_type)?;
+        let mut inner = Reader { buf: bytes, pos: 0, base: self.base + self.pos - bytes.len() };
+        return inner.merge_all(m)
+    }
+}
+
+// Stores a field the message doesn't know into `unknown_fields`, so it is
+// written back out unchanged.
+fn keep_unknown(r: &mut Reader, number: u32, wire_type: WireType, unknown_fields: &mut Vec<u8>) -> Result<(), Error> {
+    let mut w = Writer::new();
+    w.tag(number, wire_type);
+    let start = r.position();
+    r.skip(number, wire_type)?;
+    unknown_fields.extend_from_slice(&w.buf);
+    unknown_fields.extend_from_slice(r.since(start));
+    return Ok(())
+}
+
+impl Message for FileDescriptorSet {
+    fn encode(&self, w: &mut Writer) {
+        w.messages(1, &self.file);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => return r.messages(wire_type, &mut self.file),
+            _ => return r.skip(number, wire_type)
+        }
+    }
+}
+
+impl Message for FileDescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.string(2, &self.package);
+        w.strings(3, &self.dependency);
+        w.messages(4, &self.message_type);
+        w.messages(5, &self.enum_type);
+        w.messages(6, &self.service);
+        w.messages(7, &self.extension);
+        w.message(8, &self.options);
+        w.message(9, &self.source_code_info);
+        w.int32s(10, &self.public_dependency);
+        w.int32s(11, &self.weak_dependency);
+        w.string(12, &self.syntax);
+        w.string(13, &self.edition);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => self.package = Some(r.string(wire_type)?),
+            3 => self.dependency.push(r.string(wire_type)?),
+            4 => return r.messages(wire_type, &mut self.message_type),
+            5 => return r.messages(wire_type, &mut self.enum_type),
+            6 => return r.messages(wire_type, &mut self.service),
+            7 => return r.messages(wire_type, &mut self.extension),
+            8 => return r.message(wire_type, &mut self.options),
+            9 => return r.message(wire_type, &mut self.source_code_info),
+            10 => return r.int32s(wire_type, &mut self.public_dependency),
+            11 => return r.int32s(wire_type, &mut self.weak_dependency),
+            12 => self.syntax = Some(r.string(wire_type)?),
+            13 => self.edition = Some(r.string(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for DescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.messages(2, &self.field);
+        w.messages(3, &self.nested_type);
+        w.messages(4, &self.enum_type);
+        w.messages(5, &self.extension_range);
+        w.messages(6, &self.extension);
+        w.message(7, &self.options);
+        w.messages(8, &self.oneof_decl);
+        w.messages(9, &self.reserved_range);
+        w.strings(10, &self.reserved_name);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => return r.messages(wire_type, &mut self.field),
+            3 => return r.messages(wire_type, &mut self.nested_type),
+            4 => return r.messages(wire_type, &mut self.enum_type),
+            5 => return r.messages(wire_type, &mut self.extension_range),
+            6 => return r.messages(wire_type, &mut self.extension),
+            7 => return r.message(wire_type, &mut self.options),
+            8 => return r.messages(wire_type, &mut self.oneof_decl),
+            9 => return r.messages(wire_type, &mut self.reserved_range),
+            10 => self.reserved_name.push(r.string(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for ExtensionRange {
+    fn encode(&self, w: &mut Writer) {
+        w.int32(1, self.start);
+        w.int32(2, self.end);
+        w.message(3, &self.options);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.start = Some(r.int32(wire_type)?),
+            2 => self.end = Some(r.int32(wire_type)?),
+            3 => return r.message(wire_type, &mut self.options),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for ReservedRange {
+    fn encode(&self, w: &mut Writer) {
+        w.int32(1, self.start);
+        w.int32(2, self.end);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.start = Some(r.int32(wire_type)?),
+            2 => self.end = Some(r.int32(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for ExtensionRangeOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.messages(2, &self.declaration);
+        w.int32(3, self.verification.map(|v| v.number()));
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            2 => return r.messages(wire_type, &mut self.declaration),
+            3 => self.verification = r.enum_value(wire_type, VerificationState::from_number)?,
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for Declaration {
+    fn encode(&self, w: &mut Writer) {
+        w.int32(1, self.number);
+        w.string(2, &self.full_name);
+        w.string(3, &self.r#type);
+        w.bool(4, self.is_repeated);
+        w.bool(5, self.reserved);
+        w.bool(6, self.repeated);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.number = Some(r.int32(wire_type)?),
+            2 => self.full_name = Some(r.string(wire_type)?),
+            3 => self.r#type = Some(r.string(wire_type)?),
+            4 => self.is_repeated = Some(r.bool(wire_type)?),
+            5 => self.reserved = Some(r.bool(wire_type)?),
+            6 => self.repeated = Some(r.bool(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for FieldDescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.string(2, &self.extendee);
+        w.int32(3, self.number);
+        w.int32(4, self.label.map(|l| l.number()));
+        w.int32(5, self.r#type.map(|t| t.number()));
+        w.string(6, &self.type_name);
+        w.string(7, &self.default_value);
+        w.message(8, &self.options);
+        w.int32(9, self.oneof_index);
+        w.string(10, &self.json_name);
+        w.bool(17, self.proto3_optional);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => self.extendee = Some(r.string(wire_type)?),
+            3 => self.number = Some(r.int32(wire_type)?),
+            4 => self.label = r.enum_value(wire_type, FieldLabel::from_number)?,
+            5 => self.r#type = r.enum_value(wire_type, FieldType::from_number)?,
+            6 => self.type_name = Some(r.string(wire_type)?),
+            7 => self.default_value = Some(r.string(wire_type)?),
+            8 => return r.message(wire_type, &mut self.options),
+            9 => self.oneof_index = Some(r.int32(wire_type)?),
+            10 => self.json_name = Some(r.string(wire_type)?),
+            17 => self.proto3_optional = Some(r.bool(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for OneofDescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.message(2, &self.options);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => return r.message(wire_type, &mut self.options),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for EnumDescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.messages(2, &self.value);
+        w.message(3, &self.options);
+        w.messages(4, &self.reserved_range);
+        w.strings(5, &self.reserved_name);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => return r.messages(wire_type, &mut self.value),
+            3 => return r.message(wire_type, &mut self.options),
+            4 => return r.messages(wire_type, &mut self.reserved_range),
+            5 => self.reserved_name.push(r.string(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for EnumReservedRange {
+    fn encode(&self, w: &mut Writer) {
+        w.int32(1, self.start);
+        w.int32(2, self.end);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.start = Some(r.int32(wire_type)?),
+            2 => self.end = Some(r.int32(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for EnumValueDescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.int32(2, self.number);
+        w.message(3, &self.options);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => self.number = Some(r.int32(wire_type)?),
+            3 => return r.message(wire_type, &mut self.options),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for ServiceDescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.messages(2, &self.method);
+        w.message(3, &self.options);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => return r.messages(wire_type, &mut self.method),
+            3 => return r.message(wire_type, &mut self.options),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for MethodDescriptorProto {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.name);
+        w.string(2, &self.input_type);
+        w.string(3, &self.output_type);
+        w.message(4, &self.options);
+        w.bool(5, self.client_streaming);
+        w.bool(6, self.server_streaming);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name = Some(r.string(wire_type)?),
+            2 => self.input_type = Some(r.string(wire_type)?),
+            3 => self.output_type = Some(r.string(wire_type)?),
+            4 => return r.message(wire_type, &mut self.options),
+            5 => self.client_streaming = Some(r.bool(wire_type)?),
+            6 => self.server_streaming = Some(r.bool(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for FileOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &self.java_package);
+        w.string(8, &self.java_outer_classname);
+        w.int32(9, self.optimize_for.map(|o| o.number()));
+        w.bool(10, self.java_multiple_files);
+        w.string(11, &self.go_package);
+        w.bool(16, self.cc_generic_services);
+        w.bool(17, self.java_generic_services);
+        w.bool(18, self.py_generic_services);
+        w.bool(20, self.java_generate_equals_and_hash);
+        w.bool(23, self.deprecated);
+        w.bool(27, self.java_string_check_utf8);
+        w.bool(31, self.cc_enable_arenas);
+        w.string(36, &self.objc_class_prefix);
+        w.string(37, &self.csharp_namespace);
+        w.string(39, &self.swift_prefix);
+        w.string(40, &self.php_class_prefix);
+        w.string(41, &self.php_namespace);
+        w.bool(42, self.php_generic_services);
+        w.string(44, &self.php_metadata_namespace);
+        w.string(45, &self.ruby_package);
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.java_package = Some(r.string(wire_type)?),
+            8 => self.java_outer_classname = Some(r.string(wire_type)?),
+            9 => self.optimize_for = r.enum_value(wire_type, OptimizeMode::from_number)?,
+            10 => self.java_multiple_files = Some(r.bool(wire_type)?),
+            11 => self.go_package = Some(r.string(wire_type)?),
+            16 => self.cc_generic_services = Some(r.bool(wire_type)?),
+            17 => self.java_generic_services = Some(r.bool(wire_type)?),
+            18 => self.py_generic_services = Some(r.bool(wire_type)?),
+            20 => self.java_generate_equals_and_hash = Some(r.bool(wire_type)?),
+            23 => self.deprecated = Some(r.bool(wire_type)?),
+            27 => self.java_string_check_utf8 = Some(r.bool(wire_type)?),
+            31 => self.cc_enable_arenas = Some(r.bool(wire_type)?),
+            36 => self.objc_class_prefix = Some(r.string(wire_type)?),
+            37 => self.csharp_namespace = Some(r.string(wire_type)?),
+            39 => self.swift_prefix = Some(r.string(wire_type)?),
+            40 => self.php_class_prefix = Some(r.string(wire_type)?),
+            41 => self.php_namespace = Some(r.string(wire_type)?),
+            42 => self.php_generic_services = Some(r.bool(wire_type)?),
+            44 => self.php_metadata_namespace = Some(r.string(wire_type)?),
+            45 => self.ruby_package = Some(r.string(wire_type)?),
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for MessageOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.bool(1, self.message_set_wire_format);
+        w.bool(2, self.no_standard_descriptor_accessor);
+        w.bool(3, self.deprecated);
+        w.bool(7, self.map_entry);
+        w.bool(11, self.deprecated_legacy_json_field_conflicts);
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.message_set_wire_format = Some(r.bool(wire_type)?),
+            2 => self.no_standard_descriptor_accessor = Some(r.bool(wire_type)?),
+            3 => self.deprecated = Some(r.bool(wire_type)?),
+            7 => self.map_entry = Some(r.bool(wire_type)?),
+            11 => self.deprecated_legacy_json_field_conflicts = Some(r.bool(wire_type)?),
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for FieldOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.int32(1, self.ctype.map(|c| c.number()));
+        w.bool(2, self.packed);
+        w.bool(3, self.deprecated);
+        w.bool(5, self.lazy);
+        w.int32(6, self.jstype.map(|j| j.number()));
+        w.bool(10, self.weak);
+        w.bool(15, self.unverified_lazy);
+        w.bool(16, self.debug_redact);
+        w.int32(17, self.retention.map(|r| r.number()));
+        w.int32(18, self.target.map(|t| t.number()));
+        let targets: Vec<i32> = self.targets.iter().map(|t| t.number()).collect();
+        w.int32s(19, &targets);
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.ctype = r.enum_value(wire_type, CType::from_number)?,
+            2 => self.packed = Some(r.bool(wire_type)?),
+            3 => self.deprecated = Some(r.bool(wire_type)?),
+            5 => self.lazy = Some(r.bool(wire_type)?),
+            6 => self.jstype = r.enum_value(wire_type, JSType::from_number)?,
+            10 => self.weak = Some(r.bool(wire_type)?),
+            15 => self.unverified_lazy = Some(r.bool(wire_type)?),
+            16 => self.debug_redact = Some(r.bool(wire_type)?),
+            17 => self.retention = r.enum_value(wire_type, OptionRetention::from_number)?,
+            18 => self.target = r.enum_value(wire_type, OptionTargetType::from_number)?,
+            19 => {
+                let mut targets = vec![];
+                r.int32s(wire_type, &mut targets)?;
+                self.targets.extend(targets.into_iter().filter_map(OptionTargetType::from_number));
+            },
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for OneofOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+    }
+}
+
+impl Message for EnumOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.bool(2, self.allow_alias);
+        w.bool(3, self.deprecated);
+        w.bool(6, self.deprecated_legacy_json_field_conflicts);
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            2 => self.allow_alias = Some(r.bool(wire_type)?),
+            3 => self.deprecated = Some(r.bool(wire_type)?),
+            6 => self.deprecated_legacy_json_field_conflicts = Some(r.bool(wire_type)?),
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for EnumValueOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.bool(1, self.deprecated);
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.deprecated = Some(r.bool(wire_type)?),
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for ServiceOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.bool(33, self.deprecated);
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            33 => self.deprecated = Some(r.bool(wire_type)?),
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for MethodOptions {
+    fn encode(&self, w: &mut Writer) {
+        w.bool(33, self.deprecated);
+        w.int32(34, self.idempotency_level.map(|i| i.number()));
+        w.messages(999, &self.uninterpreted_option);
+        w.raw(&self.unknown_fields);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            33 => self.deprecated = Some(r.bool(wire_type)?),
+            34 => self.idempotency_level = r.enum_value(wire_type, IdempotencyLevel::from_number)?,
+            999 => return r.messages(wire_type, &mut self.uninterpreted_option),
+            _ => return keep_unknown(r, number, wire_type, &mut self.unknown_fields)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for UninterpretedOption {
+    fn encode(&self, w: &mut Writer) {
+        w.messages(2, &self.name);
+        w.string(3, &self.identifier_value);
+        w.uint64(4, self.positive_int_value);
+        w.int64(5, self.negative_int_value);
+        w.double(6, self.double_value);
+        w.bytes(7, &self.string_value);
+        w.string(8, &self.aggregate_value);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            2 => return r.messages(wire_type, &mut self.name),
+            3 => self.identifier_value = Some(r.string(wire_type)?),
+            4 => self.positive_int_value = Some(r.uint64(wire_type)?),
+            5 => self.negative_int_value = Some(r.int64(wire_type)?),
+            6 => self.double_value = Some(r.double(wire_type)?),
+            7 => self.string_value = Some(r.bytes(wire_type)?),
+            8 => self.aggregate_value = Some(r.string(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for NamePart {
+    fn encode(&self, w: &mut Writer) {
+        w.string(1, &Some(self.name_part.clone()));
+        w.bool(2, Some(self.is_extension));
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => self.name_part = r.string(wire_type)?,
+            2 => self.is_extension = r.bool(wire_type)?,
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for SourceCodeInfo {
+    fn encode(&self, w: &mut Writer) {
+        w.messages(1, &self.location);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => return r.messages(wire_type, &mut self.location),
+            _ => return r.skip(number, wire_type)
+        }
+    }
+}
+
+impl Message for Location {
+    fn encode(&self, w: &mut Writer) {
+        w.packed_int32s(1, &self.path);
+        w.packed_int32s(2, &self.span);
+        w.string(3, &self.leading_comments);
+        w.string(4, &self.trailing_comments);
+        w.strings(6, &self.leading_detached_comments);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => return r.int32s(wire_type, &mut self.path),
+            2 => return r.int32s(wire_type, &mut self.span),
+            3 => self.leading_comments = Some(r.string(wire_type)?),
+            4 => self.trailing_comments = Some(r.string(wire_type)?),
+            6 => self.leading_detached_comments.push(r.string(wire_type)?),
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+impl Message for GeneratedCodeInfo {
+    fn encode(&self, w: &mut Writer) {
+        w.messages(1, &self.annotation);
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => return r.messages(wire_type, &mut self.annotation),
+            _ => return r.skip(number, wire_type)
+        }
+    }
+}
+
+impl Message for Annotation {
+    fn encode(&self, w: &mut Writer) {
+        w.packed_int32s(1, &self.path);
+        w.string(2, &self.source_file);
+        w.int32(3, self.begin);
+        w.int32(4, self.end);
+        w.int32(5, self.semantic.map(|s| s.number()));
+    }
+
+    fn merge_field(&mut self, r: &mut Reader, number: u32, wire_type: WireType) -> Result<(), Error> {
+        match number {
+            1 => return r.int32s(wire_type, &mut self.path),
+            2 => self.source_file = Some(r.string(wire_type)?),
+            3 => self.begin = Some(r.int32(wire_type)?),
+            4 => self.end = Some(r.int32(wire_type)?),
+            5 => self.semantic = r.enum_value(wire_type, Semantic::from_number)?,
+            _ => return r.skip(number, wire_type)
+        }
+        return Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessor::MemoryAccessor;
+    use crate::compiler::Compiler;
+
+    // protoc 23.2's descriptor of google/protobuf/timestamp.proto.
+    const TIMESTAMP_PB: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/timestamp.pb"));
+    // protoc 23.2's descriptors of two more well-known types: defaults and
+    // proto2 type references in one, maps and a oneof in the other.
+    const DESCRIPTOR_PB: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/descriptor.pb"));
+    const STRUCT_PB: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/struct.pb"));
+    // googleapis' descriptor of google/cloud/location/locations.proto, which
+    // sets custom options. Its protoc predates json_name in descriptors.
+    const LOCATIONS_PB: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/locations.pb"));
+
+    #[test]
+    fn varints_round_trip() {
+        let values = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+        let mut w = Writer::new();
+        for v in values.iter() {
+            w.varint(*v);
+        }
+        let bytes = w.into_bytes();
+        let mut r = Reader::new(&bytes);
+        for v in values.iter() {
+            assert_eq!(r.varint().unwrap(), *v);
+        }
+        assert_eq!(r.position(), bytes.len());
+    }
+
+    #[test]
+    fn descriptor_round_trip() {
+        let fd = FileDescriptorProto::decode(TIMESTAMP_PB).unwrap();
+        assert_eq!(fd.name.as_deref(), Some("google/protobuf/timestamp.proto"));
+        assert_eq!(fd.message_type[0].field.len(), 2);
+        assert_eq!(fd.encode_to_vec(), TIMESTAMP_PB);
+    }
+
+    #[test]
+    fn unknown_fields_are_kept() {
+        let mut w = Writer::new();
+        w.string(1, &Some("a.b".to_string()));
+        w.tag(50000, WireType::Varint);
+        w.varint(7);
+        let bytes = w.into_bytes();
+        let opts = FileOptions::decode(&bytes).unwrap();
+        assert_eq!(opts.java_package.as_deref(), Some("a.b"));
+        assert_eq!(opts.encode_to_vec(), bytes);
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        assert!(FileDescriptorProto::decode(&TIMESTAMP_PB[..TIMESTAMP_PB.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn compiled_descriptor_matches_protoc() {
+        let mut c = Compiler::new(Box::new(MemoryAccessor::new()));
+        let files = c.compile("google/protobuf/timestamp.proto").unwrap();
+        assert_eq!(files.last().unwrap().encode_to_vec(), TIMESTAMP_PB);
+    }
+
+    #[test]
+    fn compiled_well_known_types_match_protoc() {
+        let mut c = Compiler::new(Box::new(MemoryAccessor::new()));
+        for (name, pb) in [("google/protobuf/descriptor.proto", DESCRIPTOR_PB), ("google/protobuf/struct.proto", STRUCT_PB)].iter() {
+            let files = c.compile(name).unwrap();
+            assert_eq!(files.last().unwrap().encode_to_vec(), *pb, "{}", name);
+        }
+    }
+
+    #[test]
+    fn compiled_custom_options_match_protoc() {
+        let mut accessor = MemoryAccessor::new();
+        accessor
+            .insert("google/api/http.proto", include_str!("../testdata/google/api/http.proto"))
+            .insert("google/api/annotations.proto", include_str!("../testdata/google/api/annotations.proto"))
+            .insert("google/api/client.proto", include_str!("../testdata/google/api/client.proto"))
+            .insert("google/cloud/location/locations.proto", include_str!("../testdata/google/cloud/location/locations.proto"));
+        let mut c = Compiler::new(Box::new(accessor));
+        let mut fd = c.compile("google/cloud/location/locations.proto").unwrap().pop().unwrap();
+        fn clear_json_names(msg: &mut DescriptorProto) {
+            for field in msg.field.iter_mut() {
+                field.json_name = None;
+            }
+            msg.nested_type.iter_mut().for_each(clear_json_names);
+        }
+        fd.message_type.iter_mut().for_each(clear_json_names);
+        assert_eq!(fd.encode_to_vec(), LOCATIONS_PB);
+    }
+}